@@ -0,0 +1,356 @@
+use core::cell::Cell;
+use rusty_bee::hardware::{Storage, StorageError, StorageKey};
+
+/// Two pages of flash, made of 32 bit words. Erasing a page sets all its
+/// bits, and writing a word can only clear bits. A word may be written
+/// twice between erases.
+pub trait Flash {
+    const PAGE_WORDS: usize;
+
+    fn read_word(&self, page: usize, index: usize) -> u32;
+    fn write_word(&self, page: usize, index: usize, value: u32);
+    fn erase_page(&self, page: usize);
+}
+
+/// Longest value `FlashStorage` keeps.
+const MAX_VALUE_LENGTH: usize = 64;
+
+const ERASED: u32 = 0xffff_ffff;
+/// Marks the first word of a page holding records, the low 16 bits hold
+/// the page's generation.
+const PAGE_TAG: u32 = 0x5a5a_0000;
+const PAGE_TAG_MASK: u32 = 0xffff_0000;
+/// Marks the header word of a record, which also holds the record's key
+/// in its low byte and the length of its value in the next one.
+const RECORD_TAG: u32 = 0xa5 << 24;
+const RECORD_TAG_MASK: u32 = 0xff << 24;
+/// Set in a record's header until its value is completely written.
+const RECORD_UNCOMMITTED: u32 = 1 << 16;
+
+const KEYS: [StorageKey; 4] = [
+    StorageKey::OutgoingFrameCounter,
+    StorageKey::NetworkParameters,
+    StorageKey::NetworkKey,
+    StorageKey::TrustCenterLinkKey,
+];
+
+fn key_id(key: StorageKey) -> u32 {
+    match key {
+        StorageKey::OutgoingFrameCounter => 0,
+        StorageKey::NetworkParameters => 1,
+        StorageKey::NetworkKey => 2,
+        StorageKey::TrustCenterLinkKey => 3,
+    }
+}
+
+/// Words taken by a record with a value of `length` bytes.
+fn record_words(length: usize) -> usize {
+    1 + length.div_ceil(4)
+}
+
+/// `Storage` on two pages of flash.
+///
+/// Values are appended to the current page as records, a header followed
+/// by the value, and the last record of a key holds its value. A record
+/// only counts once its header is marked as committed, after the value was
+/// written, so a reset in the middle of a write keeps the previous value.
+///
+/// When the page is full, the latest value of each key is copied to the
+/// other page, which then becomes the current one. Pages are numbered by a
+/// generation, so the newer one is used if a reset happened before the old
+/// page was erased.
+pub struct FlashStorage<F: Flash> {
+    flash: F,
+    page: Cell<usize>,
+    /// Index of the word the next record is written at.
+    end: Cell<usize>,
+}
+
+impl<F: Flash> FlashStorage<F> {
+    pub fn new(flash: F) -> Self {
+        let page = match [generation(&flash, 0), generation(&flash, 1)] {
+            [Some(first), Some(second)] => match (second.wrapping_sub(first) as i16) > 0 {
+                true => 1,
+                false => 0,
+            },
+            [Some(_), None] => 0,
+            [None, Some(_)] => 1,
+            [None, None] => {
+                // Never used before.
+                flash.erase_page(0);
+                flash.write_word(0, 0, PAGE_TAG);
+                0
+            }
+        };
+
+        let storage = Self {
+            flash,
+            page: Cell::new(page),
+            end: Cell::new(1),
+        };
+        storage.end.set(storage.scan(|_, _| {}));
+        storage
+    }
+
+    /// Pass the header and the index of the value of every record on the
+    /// current page to `visit`, returning the index after the last one.
+    fn scan(&self, mut visit: impl FnMut(u32, usize)) -> usize {
+        let page = self.page.get();
+        let mut index = 1;
+
+        while index < F::PAGE_WORDS {
+            let header = self.flash.read_word(page, index);
+            if header & RECORD_TAG_MASK != RECORD_TAG {
+                break;
+            }
+            let length = ((header >> 8) & 0xff) as usize;
+            visit(header, index + 1);
+            index += record_words(length);
+        }
+
+        index.min(F::PAGE_WORDS)
+    }
+
+    fn append(&self, key: StorageKey, value: &[u8]) {
+        let page = self.page.get();
+        let index = self.end.get();
+        let header = RECORD_TAG | RECORD_UNCOMMITTED | (value.len() as u32) << 8 | key_id(key);
+
+        self.flash.write_word(page, index, header);
+        for (offset, chunk) in value.chunks(4).enumerate() {
+            let mut word = [0xff; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.flash
+                .write_word(page, index + 1 + offset, u32::from_le_bytes(word));
+        }
+        self.flash
+            .write_word(page, index, header & !RECORD_UNCOMMITTED);
+
+        self.end.set(index + record_words(value.len()));
+    }
+
+    /// Copy the latest value of each key to the other page, and make it the
+    /// current one.
+    fn compact(&self) {
+        let mut values = [([0u8; MAX_VALUE_LENGTH], None); KEYS.len()];
+        for (key, (value, length)) in KEYS.iter().zip(values.iter_mut()) {
+            *length = self.read(*key, value);
+        }
+
+        let old_page = self.page.get();
+        let new_page = 1 - old_page;
+        let new_generation = generation(&self.flash, old_page)
+            .unwrap_or(0)
+            .wrapping_add(1);
+
+        self.flash.erase_page(new_page);
+        self.page.set(new_page);
+        self.end.set(1);
+        for (key, (value, length)) in KEYS.iter().zip(values.iter()) {
+            if let Some(length) = length {
+                self.append(*key, &value[..*length]);
+            }
+        }
+        // The new page is only used once everything was copied.
+        self.flash
+            .write_word(new_page, 0, PAGE_TAG | new_generation as u32);
+        self.flash.erase_page(old_page);
+    }
+}
+
+/// Generation of `page`, or `None` if it doesn't hold records.
+fn generation(flash: &impl Flash, page: usize) -> Option<u16> {
+    let header = flash.read_word(page, 0);
+    match header != ERASED && header & PAGE_TAG_MASK == PAGE_TAG {
+        true => Some(header as u16),
+        false => None,
+    }
+}
+
+impl<F: Flash> Storage for FlashStorage<F> {
+    fn read(&self, key: StorageKey, buffer: &mut [u8]) -> Option<usize> {
+        let mut latest = None;
+        self.scan(|header, index| {
+            if header & RECORD_UNCOMMITTED == 0 && header & 0xff == key_id(key) {
+                latest = Some((index, ((header >> 8) & 0xff) as usize));
+            }
+        });
+
+        let (index, length) = latest?;
+        let buffer = buffer.get_mut(..length)?;
+        for (offset, chunk) in buffer.chunks_mut(4).enumerate() {
+            let word = self
+                .flash
+                .read_word(self.page.get(), index + offset)
+                .to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+        Some(length)
+    }
+
+    fn write(&self, key: StorageKey, value: &[u8]) -> Result<(), StorageError> {
+        if value.len() > MAX_VALUE_LENGTH {
+            return Err(StorageError::TooLong);
+        }
+
+        if self.end.get() + record_words(value.len()) > F::PAGE_WORDS {
+            self.compact();
+            if self.end.get() + record_words(value.len()) > F::PAGE_WORDS {
+                return Err(StorageError::Failed);
+            }
+        }
+        self.append(key, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const TEST_PAGE_WORDS: usize = 96;
+
+    /// Flash in RAM, shared between the storages of a test to simulate
+    /// resets.
+    #[derive(Clone)]
+    struct TestFlash {
+        pages: Rc<RefCell<[[u32; TEST_PAGE_WORDS]; 2]>>,
+        /// Writes of each word since it was erased.
+        writes: Rc<RefCell<[[u8; TEST_PAGE_WORDS]; 2]>>,
+    }
+    impl TestFlash {
+        fn new() -> Self {
+            Self {
+                // Flash that was never erased.
+                pages: Rc::new(RefCell::new([[0; TEST_PAGE_WORDS]; 2])),
+                writes: Rc::new(RefCell::new([[0; TEST_PAGE_WORDS]; 2])),
+            }
+        }
+    }
+    impl Flash for TestFlash {
+        const PAGE_WORDS: usize = TEST_PAGE_WORDS;
+
+        fn read_word(&self, page: usize, index: usize) -> u32 {
+            self.pages.borrow()[page][index]
+        }
+
+        fn write_word(&self, page: usize, index: usize, value: u32) {
+            let writes = &mut self.writes.borrow_mut()[page][index];
+            *writes += 1;
+            assert!(*writes <= 2, "word written more than twice");
+            self.pages.borrow_mut()[page][index] &= value;
+        }
+
+        fn erase_page(&self, page: usize) {
+            self.pages.borrow_mut()[page] = [ERASED; TEST_PAGE_WORDS];
+            self.writes.borrow_mut()[page] = [0; TEST_PAGE_WORDS];
+        }
+    }
+
+    fn read(storage: &impl Storage, key: StorageKey) -> Option<Vec<u8>> {
+        let mut buffer = [0u8; MAX_VALUE_LENGTH];
+        let length = storage.read(key, &mut buffer)?;
+        Some(buffer[..length].to_vec())
+    }
+
+    #[test]
+    fn keeps_values_across_resets() {
+        let flash = TestFlash::new();
+        let storage = FlashStorage::new(flash.clone());
+        assert_eq!(read(&storage, StorageKey::NetworkKey), None);
+
+        storage
+            .write(StorageKey::OutgoingFrameCounter, b"\x01\x00\x00\x00")
+            .unwrap();
+        storage
+            .write(StorageKey::NetworkParameters, b"\x21\xd7\x34\x12\x0f")
+            .unwrap();
+        storage
+            .write(StorageKey::OutgoingFrameCounter, b"\x00\x04\x00\x00")
+            .unwrap();
+        assert_eq!(
+            storage.write(StorageKey::NetworkKey, &[0; MAX_VALUE_LENGTH + 1]),
+            Err(StorageError::TooLong)
+        );
+
+        let storage = FlashStorage::new(flash);
+        assert_eq!(
+            read(&storage, StorageKey::OutgoingFrameCounter).unwrap(),
+            b"\x00\x04\x00\x00"
+        );
+        assert_eq!(
+            read(&storage, StorageKey::NetworkParameters).unwrap(),
+            b"\x21\xd7\x34\x12\x0f"
+        );
+        assert_eq!(read(&storage, StorageKey::NetworkKey), None);
+    }
+
+    #[test]
+    fn moves_to_other_page_when_full() {
+        let flash = TestFlash::new();
+        let storage = FlashStorage::new(flash.clone());
+        storage
+            .write(StorageKey::TrustCenterLinkKey, &[0x5a; 16])
+            .unwrap();
+
+        // Enough writes to fill both pages a few times.
+        for counter in 0u32..100 {
+            storage
+                .write(StorageKey::OutgoingFrameCounter, &counter.to_le_bytes())
+                .unwrap();
+        }
+
+        let storage = FlashStorage::new(flash.clone());
+        assert_eq!(
+            read(&storage, StorageKey::OutgoingFrameCounter).unwrap(),
+            99u32.to_le_bytes()
+        );
+        assert_eq!(
+            read(&storage, StorageKey::TrustCenterLinkKey).unwrap(),
+            [0x5a; 16]
+        );
+        // Only the current page holds records.
+        let pages = flash.pages.borrow();
+        assert_eq!(pages[1 - storage.page.get()], [ERASED; TEST_PAGE_WORDS]);
+    }
+
+    #[test]
+    fn ignores_interrupted_writes() {
+        let flash = TestFlash::new();
+        let storage = FlashStorage::new(flash.clone());
+        storage.write(StorageKey::NetworkKey, &[0x11; 16]).unwrap();
+
+        // Reset after the header and half of a new value were written.
+        let end = storage.end.get();
+        let header = RECORD_TAG | RECORD_UNCOMMITTED | 16 << 8 | key_id(StorageKey::NetworkKey);
+        flash.write_word(0, end, header);
+        flash.write_word(0, end + 1, 0x2222_2222);
+
+        let storage = FlashStorage::new(flash.clone());
+        assert_eq!(read(&storage, StorageKey::NetworkKey).unwrap(), [0x11; 16]);
+
+        storage.write(StorageKey::NetworkKey, &[0x33; 16]).unwrap();
+        let storage = FlashStorage::new(flash);
+        assert_eq!(read(&storage, StorageKey::NetworkKey).unwrap(), [0x33; 16]);
+    }
+
+    #[test]
+    fn uses_newer_page_after_interrupted_move() {
+        let flash = TestFlash::new();
+        let storage = FlashStorage::new(flash.clone());
+        storage.write(StorageKey::NetworkKey, &[0x11; 16]).unwrap();
+
+        // Reset after the values were copied to the other page, before the
+        // old one was erased.
+        let old_page = flash.pages.borrow()[0];
+        storage.compact();
+        flash.pages.borrow_mut()[0] = old_page;
+        storage.write(StorageKey::NetworkKey, &[0x22; 16]).unwrap();
+
+        let storage = FlashStorage::new(flash);
+        assert_eq!(storage.page.get(), 1);
+        assert_eq!(read(&storage, StorageKey::NetworkKey).unwrap(), [0x22; 16]);
+    }
+}
@@ -1,4 +1,4 @@
 pub mod radio_driver;
 
 mod ieee802154;
-pub use self::ieee802154::IEEE802154Driver;
+pub use self::ieee802154::{configure_radio_driver, Channel};
@@ -1,4 +1,4 @@
-use crate::{serial_print, serial_println};
+use crate::serial_println;
 
 #[allow(dead_code)]
 pub struct RadioDriver {
@@ -8,6 +8,7 @@ pub struct RadioDriver {
     pub received_packet_details: &'static mut RadioPeripheralReceivedPacketDetails,
     pub config_and_state: &'static mut RadioPeripheralConfigurationAndState,
     pub power: &'static mut RadioPeripheralPower,
+    pub energy_detection: &'static mut RadioPeripheralEnergyDetection,
 }
 
 static mut PACKET_BUFFER: [u8; 512] = [0; 512];
@@ -25,6 +26,8 @@ impl RadioDriver {
             &mut *(RADIO_CONFIG_AND_STATE_OFFSET as *mut RadioPeripheralConfigurationAndState)
         };
         let power = unsafe { &mut *(RADIO_POWER_OFFSET as *mut RadioPeripheralPower) };
+        let energy_detection =
+            unsafe { &mut *(RADIO_ENERGY_DETECTION_OFFSET as *mut RadioPeripheralEnergyDetection) };
 
         serial_println!(
             "Current radio state is: {:?}",
@@ -59,6 +62,7 @@ impl RadioDriver {
             received_packet_details,
             config_and_state,
             power,
+            energy_detection,
         };
     }
 
@@ -98,7 +102,7 @@ impl RadioDriver {
         return Ok(());
     }
 
-    pub fn set_frequency(&mut self, frequency: u32) -> Result<(), ()> {
+    pub fn set_frequency(&self, frequency: u32) -> Result<(), ()> {
         if frequency < 2360 || frequency > 2500 {
             return Err(());
         }
@@ -122,48 +126,149 @@ impl RadioDriver {
         return Ok(());
     }
 
-    pub fn read_packet_blocking(&mut self) -> &'static [u8] {
-        // Disable all shortcuts.
+    pub fn set_transmit_power(&self, power: config_types::TransmissionPower) {
         unsafe {
-            self.shortcuts.shortcuts.write(0);
+            self.config_and_state.transmit_power.write(power);
         }
-        // Trigger the RXEN task if needed.
-        if self.config_and_state.radio_state.read() != config_types::RadioState::RxIdle {
-            self.disable_radio();
-            unsafe {
-                self.tasks.trigger_rx_enable.write(1);
+    }
+
+    /// Start the receiver if it isn't running, so it can listen for
+    /// packets or measure the channel.
+    fn enable_receiver(&self) {
+        match self.config_and_state.radio_state.read() {
+            config_types::RadioState::Rx
+            | config_types::RadioState::RxRampUp
+            | config_types::RadioState::RxIdle => {}
+            _ => {
+                // Disable all shortcuts.
+                unsafe {
+                    self.shortcuts.shortcuts.write(0);
+                }
+                self.disable_radio();
+                unsafe {
+                    self.tasks.trigger_rx_enable.write(1);
+                }
+                // Wait until we're in RxIdle state
+                while self.config_and_state.radio_state.read() != config_types::RadioState::RxIdle {
+                }
+                unsafe {
+                    self.events.events_packet_end.write(0);
+                    self.events.events_frame_start.write(0);
+                }
             }
         }
+    }
 
-        // Wait until we're in RxIdle state
+    /// Whether a packet is being received: its length was received, but not
+    /// the whole packet yet.
+    fn is_receiving_packet(&self) -> bool {
+        self.config_and_state.radio_state.read() == config_types::RadioState::Rx
+            && self.events.events_frame_start.read() == 1
+            && self.events.events_packet_end.read() == 0
+    }
+
+    /// Bring the receiver to RxIdle, where it can measure the channel,
+    /// stopping it if it is listening. It listens again on the next
+    /// `try_read_packet`.
+    fn stop_listening(&self) {
+        self.enable_receiver();
+        if self.config_and_state.radio_state.read() == config_types::RadioState::Rx {
+            unsafe {
+                self.tasks.trigger_radio_stop.write(1);
+            }
+        }
         while self.config_and_state.radio_state.read() != config_types::RadioState::RxIdle {}
+    }
+
+    /// Whether the channel is idle, using the energy above threshold mode
+    /// of the RADIO's clear channel assessment.
+    pub fn clear_channel_assessment(&self) -> bool {
+        // Someone else is sending.
+        if self.is_receiving_packet() {
+            return false;
+        }
+        self.stop_listening();
 
-        // Trigger the START task.
         unsafe {
-            self.tasks.trigger_radio_start.write(1);
+            self.events.events_cca_idle.write(0);
+            self.events.events_cca_busy.write(0);
+            // Busy when the energy is above -75 dBm.
+            self.energy_detection
+                .cca_control
+                .write(CCA_MODE_ENERGY_DETECTION | CCA_ENERGY_DETECTION_THRESHOLD << 8);
+            self.tasks.trigger_clear_channel_assessment_start.write(1);
         }
-        // Wait until we're back to RxIdle state
-        while self.config_and_state.radio_state.read() != config_types::RadioState::RxIdle {}
+        loop {
+            if self.events.events_cca_idle.read() == 1 {
+                return true;
+            }
+            if self.events.events_cca_busy.read() == 1 {
+                return false;
+            }
+        }
+    }
+
+    /// Energy measured on the channel over 128 µs, scaled from the RADIO's
+    /// 0 to 63 range to 0 to 255 as IEEE 802.15.4 expects.
+    pub fn energy_detect(&self) -> u8 {
+        // A packet being received is dropped, to measure right away.
+        self.stop_listening();
 
-        serial_println!(
-            "CRC matched: {:?}",
-            self.received_packet_details.crc_status.read()
-        );
         unsafe {
-            serial_print!(" < ");
-            // Print out the raw hex dump of the packet.
-            for i in 0..(PACKET_BUFFER[0] as usize) {
-                serial_print!("{:02x}", PACKET_BUFFER[i + 1]);
+            self.events.events_energy_detection_end.write(0);
+            // A single measurement.
+            self.energy_detection.energy_detection_count.write(0);
+            self.tasks.trigger_energy_detection_start.write(1);
+        }
+        while self.events.events_energy_detection_end.read() != 1 {}
+
+        scale_energy_level(self.energy_detection.energy_detection_sample.read())
+    }
+
+    /// Copy a received packet into `buffer`, without its length byte and
+    /// CRC, returning its length and the energy level it was received with,
+    /// scaled like `energy_detect`. Returns `None` without blocking if no
+    /// packet was received yet, starting the receiver if it isn't running.
+    /// Packets with a CRC mismatch are dropped.
+    pub fn try_read_packet(&self, buffer: &mut [u8]) -> Option<(usize, u8)> {
+        match self.config_and_state.radio_state.read() {
+            // Still listening, or in the middle of receiving a packet.
+            config_types::RadioState::Rx | config_types::RadioState::RxRampUp => return None,
+            _ => self.enable_receiver(),
+        }
+
+        let mut received = None;
+        if self.events.events_packet_end.read() == 1 {
+            unsafe {
+                self.events.events_packet_end.write(0);
+            }
+
+            let crc_status = self.received_packet_details.crc_status.read();
+
+            // The length byte counts the two CRC bytes at the end.
+            let packet_length = unsafe { PACKET_BUFFER[0] as usize }.saturating_sub(2);
+            if matches!(crc_status, CrcStatus::CrcOk) && packet_length <= buffer.len() {
+                // The RADIO replaces the first CRC byte with the energy
+                // level measured while receiving.
+                let energy_level = unsafe { PACKET_BUFFER[packet_length + 1] };
+                unsafe {
+                    buffer[..packet_length].copy_from_slice(&PACKET_BUFFER[1..packet_length + 1]);
+                }
+                received = Some((packet_length, scale_energy_level(energy_level as u32)));
             }
-            serial_println!("");
         }
 
-        // ======= Try to receive a packet??? =======
+        // Trigger the START task to listen for the next packet.
         unsafe {
-            return &PACKET_BUFFER;
+            self.events.events_frame_start.write(0);
+            self.tasks.trigger_radio_start.write(1);
         }
+
+        received
     }
 
+    /// Send `packet` right away, without checking that the channel is
+    /// clear, see `clear_channel_assessment` for that.
     pub fn write_packet_blocking(&self, packet: &[u8], length: u8) {
         // Disable all shortcuts.
         unsafe {
@@ -188,14 +293,6 @@ impl RadioDriver {
         unsafe {
             PACKET_BUFFER[1..(packet.len() + 1)].copy_from_slice(packet);
         }
-        unsafe {
-            serial_print!(" > ");
-            // Print out the raw hex dump of the packet.
-            for i in 0..(PACKET_BUFFER[0] as usize) {
-                serial_print!("{:02x}", PACKET_BUFFER[i + 1]);
-            }
-            serial_println!("");
-        }
 
         // Trigger the START task.
         unsafe {
@@ -246,9 +343,9 @@ pub struct RadioPeripheralTasks {
     /// TASKS_EDSTOP in Nordic's datasheet.
     trigger_energy_detection_stop: volatile_register::WO<u32>,
     /// TASKS_CCASTART in Nordic's datasheet.
-    trigger_eclear_channel_assessment_start: volatile_register::WO<u32>,
+    trigger_clear_channel_assessment_start: volatile_register::WO<u32>,
     /// TASKS_CCASTOP in Nordic's datasheet.
-    trigger_eclear_channel_assessment_stop: volatile_register::WO<u32>,
+    trigger_clear_channel_assessment_stop: volatile_register::WO<u32>,
 }
 
 const RADIO_EVENTS_OFFST: usize = RADIO_BASE_ADDRESS + 0x100;
@@ -270,6 +367,29 @@ pub struct RadioPeripheralEvents {
     ///
     /// EVENTS_END in Nordic's datasheet.
     events_packet_end: volatile_register::RW<u32>,
+
+    // padding, EVENTS_END is at 0x10C, EVENTS_FRAMESTART is at 0x138
+    _reserved: [u32; 10],
+
+    /// 1 if RADIO received the length of an IEEE 802.15.4 packet.
+    ///
+    /// EVENTS_FRAMESTART in Nordic's datasheet.
+    events_frame_start: volatile_register::RW<u32>,
+
+    /// 1 if RADIO is done measuring the energy on the channel.
+    ///
+    /// EVENTS_EDEND in Nordic's datasheet.
+    events_energy_detection_end: volatile_register::RW<u32>,
+    /// EVENTS_EDSTOPPED in Nordic's datasheet.
+    events_energy_detection_stopped: volatile_register::RW<u32>,
+    /// 1 if the clear channel assessment found the channel idle.
+    ///
+    /// EVENTS_CCAIDLE in Nordic's datasheet.
+    events_cca_idle: volatile_register::RW<u32>,
+    /// 1 if the clear channel assessment found the channel busy.
+    ///
+    /// EVENTS_CCABUSY in Nordic's datasheet.
+    events_cca_busy: volatile_register::RW<u32>,
 }
 
 const RADIO_SHORTCUTS_OFFSET: usize = RADIO_BASE_ADDRESS + 0x200;
@@ -561,6 +681,30 @@ pub struct RadioPeripheralConfigurationAndState {
     radio_state: volatile_register::RO<config_types::RadioState>,
 }
 
+const RADIO_ENERGY_DETECTION_OFFSET: usize = RADIO_BASE_ADDRESS + 0x664;
+#[repr(C)]
+pub struct RadioPeripheralEnergyDetection {
+    /// Number of extra 128 µs measurements to take the maximum of.
+    ///
+    /// EDCNT in Nordic's datasheet.
+    energy_detection_count: volatile_register::RW<u32>,
+    /// EDSAMPLE in Nordic's datasheet.
+    energy_detection_sample: volatile_register::RO<u32>,
+    /// CCACTRL in Nordic's datasheet.
+    cca_control: volatile_register::RW<u32>,
+}
+
+/// CCAMODE for busy when the energy is above the threshold.
+const CCA_MODE_ENERGY_DETECTION: u32 = 0;
+/// CCAEDTHRES for -75 dBm, the energy level from which the channel is
+/// busy: -75 dBm minus the RADIO's -93 dBm offset.
+const CCA_ENERGY_DETECTION_THRESHOLD: u32 = 18;
+
+/// Scale an energy level from the RADIO's 0 to 63 range to 0 to 255.
+fn scale_energy_level(level: u32) -> u8 {
+    (level * 4).min(255) as u8
+}
+
 const RADIO_POWER_OFFSET: usize = RADIO_BASE_ADDRESS + 0xFFC;
 #[repr(C)]
 pub struct RadioPeripheralPower {
@@ -1,21 +1,60 @@
 #![cfg_attr(not(test), no_std)]
-use rusty_bee::initialize_zigbee_stack;
-use rusty_bee::ZigbeeHardware;
+use rusty_bee::hardware::{
+    Clock, Radio, Random, ReceiveInfo, Storage, StorageError, StorageKey, MAX_CHANNEL, MIN_CHANNEL,
+};
+use rusty_bee::{initialize_zigbee_stack, ZigbeeHardware, ZigbeeStack};
 
 mod ieee802154_radio;
-use ieee802154_radio::IEEE802154Driver;
-use rusty_bee::network_layer::ZigbeePacket;
+use ieee802154::mac::command::CapabilityInformation as MacCapabilityInformation;
+use ieee802154::mac::{PanId, ShortAddress};
+use ieee802154_radio::radio_driver::config_types::TransmissionPower;
+use ieee802154_radio::radio_driver::RadioDriver;
+use ieee802154_radio::{configure_radio_driver, Channel};
+use rusty_bee::aps::binding::BindingTable;
+use rusty_bee::aps::ApsFrame;
+use rusty_bee::mac::{Mac, MAX_FRAME_LENGTH};
+use rusty_bee::network_layer::commands::{AddressList, CapabilityInformation};
+use rusty_bee::zdo::binding::LocalBindings;
+use rusty_bee::zdo::descriptors::{
+    LogicalType, NodeDescriptor, PowerDescriptor, SimpleDescriptor, FREQUENCY_BAND_2400_MHZ,
+};
+use rusty_bee::zdo::{LocalDevice, ZdoCommand, ZdoFrame, ZDO_ENDPOINT};
 
 pub mod factory_information;
+use factory_information::FactoryInformationReader;
+mod flash_storage;
+use flash_storage::FlashStorage;
+mod nvmc;
+use nvmc::NonVolatileMemoryController;
+mod rng;
+use rng::RandomNumberGenerator;
+mod timer;
+use timer::MicrosecondTimer;
 
 #[macro_use]
 pub mod debug_print;
 
-pub struct NRF52840ZigbeeHardware {}
+pub struct NRF52840ZigbeeHardware {
+    timer: MicrosecondTimer,
+    radio: RadioDriver,
+    rng: RandomNumberGenerator,
+    factory_information: FactoryInformationReader,
+    storage: FlashStorage<NonVolatileMemoryController>,
+}
 
 impl NRF52840ZigbeeHardware {
     pub fn new() -> Self {
-        Self {}
+        let mut radio = RadioDriver::new();
+        // TODO: pass as argument
+        configure_radio_driver(&mut radio, Channel::FIFTEEN);
+
+        Self {
+            timer: MicrosecondTimer::new(),
+            radio,
+            rng: RandomNumberGenerator::new(),
+            factory_information: FactoryInformationReader::new(),
+            storage: FlashStorage::new(NonVolatileMemoryController::new()),
+        }
     }
 }
 
@@ -23,51 +62,298 @@ impl ZigbeeHardware for NRF52840ZigbeeHardware {
     fn connect(&self) -> bool {
         true
     }
+
+    fn extended_address(&self) -> u64 {
+        self.factory_information.get_device_id()
+    }
 }
 
-#[no_mangle]
-pub extern "C" fn zigbee_init(num_reads: u32, param: u32) -> u64 {
-    let hardware = NRF52840ZigbeeHardware::new();
-    if !initialize_zigbee_stack(&hardware) {
-        return 1;
+impl Radio for NRF52840ZigbeeHardware {
+    fn transmit(&self, frame: &[u8]) {
+        // The length includes the CRC appended by the radio.
+        self.radio
+            .write_packet_blocking(frame, (frame.len() + 2) as u8);
+    }
+
+    fn receive(&self, buffer: &mut [u8]) -> Option<ReceiveInfo> {
+        let (length, energy_level) = self.radio.try_read_packet(buffer)?;
+        Some(ReceiveInfo {
+            length,
+            // The energy level is in steps of 1/4 dB above the lowest
+            // level the radio detects, -93 dBm.
+            rssi: (energy_level / 4) as i8 - 93,
+            lqi: energy_level,
+            timestamp_us: self.timer.now_us(),
+        })
     }
 
-    let mut radio = IEEE802154Driver::new();
+    fn set_channel(&self, channel: u8) -> bool {
+        if !(MIN_CHANNEL..=MAX_CHANNEL).contains(&channel) {
+            return false;
+        }
+        let frequency = 2405 + 5 * (channel - MIN_CHANNEL) as u32;
+        self.radio.set_frequency(frequency).is_ok()
+    }
 
-    if param == 1 {
-        radio.broadcast_beacon();
+    fn set_tx_power(&self, dbm: i8) {
+        let power = match dbm {
+            i8::MIN..=-40 => TransmissionPower::Negative40dBm,
+            -39..=-30 => TransmissionPower::Negative30dBm,
+            -29..=-20 => TransmissionPower::Negative20dBm,
+            -19..=-16 => TransmissionPower::Negative16dBm,
+            -15..=-12 => TransmissionPower::Negative12dBm,
+            -11..=-8 => TransmissionPower::Negative8dBm,
+            -7..=-4 => TransmissionPower::Negative4dBm,
+            -3..=1 => TransmissionPower::ZerodBM,
+            2 => TransmissionPower::Positive2dBm,
+            3 => TransmissionPower::Positive3dBm,
+            4 => TransmissionPower::Positive4dBm,
+            5 => TransmissionPower::Positive5dBm,
+            6 => TransmissionPower::Positive6dBm,
+            7 => TransmissionPower::Positive7dBm,
+            8..=i8::MAX => TransmissionPower::Positive8dBm,
+        };
+        self.radio.set_transmit_power(power);
     }
 
-    for _ in 0..(num_reads / 2) {
-        let packet = radio.read_packet();
-        serial_println!("Packet: {:?}", packet);
+    fn clear_channel_assessment(&self) -> bool {
+        self.radio.clear_channel_assessment()
+    }
 
-        match packet {
-            Ok((frame, _size)) => {
-                let zigbee = ZigbeePacket::try_parse_from(frame.payload);
-                serial_println!("Zigbee: {:?}", zigbee);
-            }
-            _ => {}
+    fn energy_detect(&self) -> u8 {
+        self.radio.energy_detect()
+    }
+}
+
+impl Clock for NRF52840ZigbeeHardware {
+    fn now_ms(&self) -> u32 {
+        self.timer.now_ms()
+    }
+
+    fn now_us(&self) -> u32 {
+        self.timer.now_us()
+    }
+
+    fn set_alarm_us(&self, at: u32) {
+        self.timer.set_alarm_us(at);
+    }
+
+    fn alarm_expired(&self) -> bool {
+        self.timer.alarm_expired()
+    }
+
+    fn cancel_alarm(&self) {
+        self.timer.cancel_alarm();
+    }
+}
+
+impl Random for NRF52840ZigbeeHardware {
+    fn random_u32(&self) -> u32 {
+        let mut bytes = [0u8; 4];
+        for byte in bytes.iter_mut() {
+            *byte = self.rng.next_u8();
         }
+        u32::from_le_bytes(bytes)
     }
+}
 
-    if param == 1 {
-        radio.send_assosciation_request();
-    }
-    for _ in 0..(num_reads / 2) {
-        let packet = radio.read_packet();
-        serial_println!("Packet: {:?}", packet);
-
-        match packet {
-            Ok((frame, _size)) => {
-                let zigbee = ZigbeePacket::try_parse_from(frame.payload);
-                serial_println!("Zigbee: {:?}", zigbee);
-            }
-            _ => {}
+impl Storage for NRF52840ZigbeeHardware {
+    fn read(&self, key: StorageKey, buffer: &mut [u8]) -> Option<usize> {
+        self.storage.read(key, buffer)
+    }
+
+    fn write(&self, key: StorageKey, value: &[u8]) -> Result<(), StorageError> {
+        self.storage.write(key, value)
+    }
+}
+
+const PAN_ID: u16 = 0xd721;
+const COORDINATOR_ADDRESS: u16 = 0x8dbc;
+
+/// Endpoints of this device, a home automation on/off switch.
+const ENDPOINTS: [SimpleDescriptor; 1] = [SimpleDescriptor {
+    endpoint: 1,
+    profile_id: 0x0104,
+    device_id: 0x0000,
+    device_version: 0,
+    // Basic and Identify.
+    input_clusters: AddressList {
+        bytes: b"\x00\x00\x03\x00",
+    },
+    // On/Off.
+    output_clusters: AddressList { bytes: b"\x06\x00" },
+}];
+
+/// Bindings of the switch to the lights it controls.
+const BINDING_TABLE_SIZE: usize = 8;
+
+fn local_device(mac: &Mac, ieee_address: u64) -> LocalDevice<'static> {
+    let nwk_address = match mac.short_address {
+        Some(ShortAddress(address)) => address,
+        // Not associated yet.
+        None => 0xfffe,
+    };
+
+    LocalDevice {
+        nwk_address,
+        ieee_address,
+        node_descriptor: NodeDescriptor {
+            logical_type: LogicalType::EndDevice,
+            complex_descriptor_available: false,
+            user_descriptor_available: false,
+            frequency_bands: FREQUENCY_BAND_2400_MHZ,
+            mac_capabilities: CapabilityInformation {
+                alternate_pan_coordinator: false,
+                full_function_device: true,
+                mains_power: true,
+                receiver_on_when_idle: true,
+                security_capability: false,
+                allocate_address: true,
+            },
+            manufacturer_code: 0x0000,
+            maximum_buffer_size: 127,
+            maximum_incoming_transfer_size: 127,
+            server_mask: 0x0000,
+            maximum_outgoing_transfer_size: 127,
+            descriptor_capabilities: 0,
+        },
+        power_descriptor: PowerDescriptor {
+            current_power_mode: 0,
+            // Mains power, at full level.
+            available_power_sources: 0b0001,
+            current_power_source: 0b0001,
+            current_power_source_level: 12,
+        },
+        endpoints: &ENDPOINTS,
+    }
+}
+
+/// Answer discovery, binding and management requests sent to the ZDO
+/// endpoint.
+fn answer_zdo_request(
+    stack: &mut ZigbeeStack<NRF52840ZigbeeHardware>,
+    device: &LocalDevice,
+    bindings: &mut LocalBindings<BINDING_TABLE_SIZE>,
+    source: u16,
+    aps: &ApsFrame,
+) {
+    let cluster_id = match (aps.destination_endpoint, aps.cluster_id) {
+        (Some(ZDO_ENDPOINT), Some(cluster_id)) => cluster_id,
+        _ => return,
+    };
+    let request = match ZdoFrame::try_parse_from(cluster_id, aps.payload) {
+        Ok(request) => request,
+        Err(_) => return,
+    };
+    // Bound devices are looked up by the address they announce.
+    if let ZdoCommand::DeviceAnnounce(announce) = &request.command {
+        let _ = stack
+            .addresses
+            .update(announce.ieee_address, announce.nwk_address);
+        return;
+    }
+
+    let mut scratch = [0u8; ENDPOINTS.len()];
+    let sent = match device.respond(&request, &mut scratch) {
+        Ok(Some(response)) => stack.send_zdo_frame(source, &response),
+        _ => match bindings.respond(&request) {
+            Some(response) => stack.send_zdo_frame(source, &response),
+            None => stack.answer_management_request(source, &request, bindings.bindings),
+        },
+    };
+    if let Err(error) = sent {
+        serial_println!("ZDO response not sent: {:?}", error);
+    }
+}
+
+/// Handle `count` frames addressed to this device.
+fn handle_frames(
+    count: u32,
+    stack: &mut ZigbeeStack<NRF52840ZigbeeHardware>,
+    ieee_address: u64,
+    binding_table: &mut BindingTable<BINDING_TABLE_SIZE>,
+) {
+    let mut handled = 0;
+    while handled < count {
+        stack.poll_retransmissions(|destination, counter| {
+            serial_println!("APS frame {} to {} not acknowledged", counter, destination);
+        });
+
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+        let zigbee = match stack.receive(&mut buffer) {
+            Some((zigbee, _)) => zigbee,
+            None => continue,
+        };
+        handled += 1;
+
+        let device = local_device(&stack.mac, ieee_address);
+        let mut bindings = LocalBindings {
+            ieee_address: device.ieee_address,
+            bindings: binding_table,
+        };
+        if let Some(aps) = stack.handle_aps_frame(&zigbee) {
+            answer_zdo_request(stack, &device, &mut bindings, zigbee.source, &aps);
+        }
+    }
+}
+
+fn associate(hardware: &NRF52840ZigbeeHardware, mac: &mut Mac) {
+    let capability_information = MacCapabilityInformation {
+        full_function_device: true,
+        mains_power: true,
+        allocate_address: true,
+        frame_protection: false,
+        idle_receive: true,
+    };
+    match mac.send_association_request(
+        hardware,
+        PanId(PAN_ID),
+        ShortAddress(COORDINATOR_ADDRESS),
+        capability_information,
+    ) {
+        Ok(_) => {
+            serial_println!("Assosciation successful!");
         }
+        Err(_) => {
+            serial_println!("Assosciation failed :(");
+            return;
+        }
+    }
+
+    // Send a data request!
+    match mac.send_data_request(hardware) {
+        Ok(_) => {
+            serial_println!("Data request successful!")
+        }
+        Err(_) => {
+            serial_println!("Data request failed :(");
+            return;
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn zigbee_init(num_reads: u32, param: u32) -> u64 {
+    let hardware = NRF52840ZigbeeHardware::new();
+    let mut stack = match initialize_zigbee_stack(&hardware) {
+        Some(stack) => stack,
+        None => return 1,
+    };
+
+    let mac_address = hardware.extended_address();
+    let mut binding_table = BindingTable::<BINDING_TABLE_SIZE>::new();
+
+    if param == 1 {
+        let _ = stack.mac.send_beacon_request(&hardware);
+    }
+    handle_frames(num_reads / 2, &mut stack, mac_address, &mut binding_table);
+
+    if param == 1 {
+        associate(&hardware, &mut stack.mac);
     }
+    handle_frames(num_reads / 2, &mut stack, mac_address, &mut binding_table);
 
-    return radio.mac_address;
+    return mac_address;
     //return 0;
 }
 
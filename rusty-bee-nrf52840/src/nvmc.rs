@@ -0,0 +1,90 @@
+use crate::flash_storage::Flash;
+
+/// Size of a flash page, the unit flash is erased in.
+const PAGE_SIZE: usize = 4096;
+/// The last two pages of the 1 MB of flash, kept for `FlashStorage`. The
+/// firmware's linker script must not place anything there.
+const STORAGE_PAGE_ADDRESSES: [usize; 2] = [0x000F_E000, 0x000F_F000];
+
+/// The non-volatile memory controller, which writes and erases the flash.
+pub struct NonVolatileMemoryController {
+    registers: &'static mut NvmcPeripheralConfiguration,
+    ready: &'static mut NvmcPeripheralReady,
+}
+
+impl NonVolatileMemoryController {
+    pub fn new() -> Self {
+        let registers = unsafe { &mut *(NVMC_CONFIG_OFFSET as *mut NvmcPeripheralConfiguration) };
+        let ready = unsafe { &mut *(NVMC_READY_OFFSET as *mut NvmcPeripheralReady) };
+
+        return Self { registers, ready };
+    }
+
+    fn wait_until_ready(&self) {
+        while self.ready.ready.read() == 0 {}
+    }
+
+    fn word_address(page: usize, index: usize) -> *mut u32 {
+        (STORAGE_PAGE_ADDRESSES[page] + index * 4) as *mut u32
+    }
+}
+
+impl Flash for NonVolatileMemoryController {
+    const PAGE_WORDS: usize = PAGE_SIZE / 4;
+
+    fn read_word(&self, page: usize, index: usize) -> u32 {
+        unsafe { core::ptr::read_volatile(Self::word_address(page, index)) }
+    }
+
+    fn write_word(&self, page: usize, index: usize, value: u32) {
+        unsafe {
+            self.registers.config.write(NVMC_CONFIG_WRITE);
+            core::ptr::write_volatile(Self::word_address(page, index), value);
+        }
+        self.wait_until_ready();
+        unsafe {
+            self.registers.config.write(NVMC_CONFIG_READ);
+        }
+    }
+
+    fn erase_page(&self, page: usize) {
+        unsafe {
+            self.registers.config.write(NVMC_CONFIG_ERASE);
+            self.registers
+                .erase_page
+                .write(STORAGE_PAGE_ADDRESSES[page] as u32);
+        }
+        self.wait_until_ready();
+        unsafe {
+            self.registers.config.write(NVMC_CONFIG_READ);
+        }
+    }
+}
+
+const NVMC_BASE_ADDRESS: usize = 0x4001E000;
+
+const NVMC_CONFIG_READ: u32 = 0;
+const NVMC_CONFIG_WRITE: u32 = 1;
+const NVMC_CONFIG_ERASE: u32 = 2;
+
+const NVMC_READY_OFFSET: usize = NVMC_BASE_ADDRESS + 0x400;
+#[repr(C)]
+pub struct NvmcPeripheralReady {
+    /// 1 once the last write or erase is done.
+    ///
+    /// READY in Nordic's datasheet.
+    ready: volatile_register::RO<u32>,
+}
+
+const NVMC_CONFIG_OFFSET: usize = NVMC_BASE_ADDRESS + 0x504;
+#[repr(C)]
+pub struct NvmcPeripheralConfiguration {
+    /// Whether the flash can be read only, written or erased.
+    ///
+    /// CONFIG in Nordic's datasheet.
+    config: volatile_register::RW<u32>,
+    /// Erases the page at the address written to it.
+    ///
+    /// ERASEPAGE in Nordic's datasheet.
+    erase_page: volatile_register::RW<u32>,
+}
@@ -0,0 +1,70 @@
+/// The RNG peripheral, generating random numbers from thermal noise.
+pub struct RandomNumberGenerator {
+    tasks: &'static mut RngPeripheralTasks,
+    events: &'static mut RngPeripheralEvents,
+    value: &'static mut RngPeripheralValue,
+}
+
+impl RandomNumberGenerator {
+    pub fn new() -> Self {
+        let tasks = unsafe { &mut *(RNG_TASKS_OFFSET as *mut RngPeripheralTasks) };
+        let events = unsafe { &mut *(RNG_EVENTS_OFFSET as *mut RngPeripheralEvents) };
+        let value = unsafe { &mut *(RNG_VALUE_OFFSET as *mut RngPeripheralValue) };
+
+        unsafe {
+            // Slower, but without a bias towards zeros or ones.
+            value.config.write(RNG_CONFIG_BIAS_CORRECTION);
+        }
+
+        return Self {
+            tasks,
+            events,
+            value,
+        };
+    }
+
+    /// Wait for the next random byte.
+    pub fn next_u8(&self) -> u8 {
+        unsafe {
+            self.events.events_value_ready.write(0);
+            self.tasks.trigger_start.write(1);
+        }
+        while self.events.events_value_ready.read() != 1 {}
+        unsafe {
+            self.tasks.trigger_stop.write(1);
+        }
+
+        self.value.value.read() as u8
+    }
+}
+
+const RNG_BASE_ADDRESS: usize = 0x4000D000;
+
+const RNG_CONFIG_BIAS_CORRECTION: u32 = 1;
+
+const RNG_TASKS_OFFSET: usize = RNG_BASE_ADDRESS + 0x0;
+#[repr(C)]
+pub struct RngPeripheralTasks {
+    /// TASKS_START in Nordic's datasheet.
+    trigger_start: volatile_register::WO<u32>,
+    /// TASKS_STOP in Nordic's datasheet.
+    trigger_stop: volatile_register::WO<u32>,
+}
+
+const RNG_EVENTS_OFFSET: usize = RNG_BASE_ADDRESS + 0x100;
+#[repr(C)]
+pub struct RngPeripheralEvents {
+    /// 1 if a new random byte was written to VALUE.
+    ///
+    /// EVENTS_VALRDY in Nordic's datasheet.
+    events_value_ready: volatile_register::RW<u32>,
+}
+
+const RNG_VALUE_OFFSET: usize = RNG_BASE_ADDRESS + 0x504;
+#[repr(C)]
+pub struct RngPeripheralValue {
+    /// CONFIG in Nordic's datasheet.
+    config: volatile_register::RW<u32>,
+    /// VALUE in Nordic's datasheet.
+    value: volatile_register::RO<u32>,
+}
@@ -0,0 +1,155 @@
+use core::cell::Cell;
+
+/// Free running microsecond counter on TIMER1, used as the stack's clock,
+/// with an alarm on its second compare register.
+pub struct MicrosecondTimer {
+    tasks: &'static mut TimerPeripheralTasks,
+    events: &'static mut TimerPeripheralEvents,
+    capture_compare: &'static mut TimerPeripheralCaptureCompare,
+    /// Counter value at the last reading, to detect when it wraps around.
+    last_microseconds: Cell<u32>,
+    elapsed_microseconds: Cell<u64>,
+    alarm_armed: Cell<bool>,
+    /// Whether the alarm time had already passed when it was armed, so the
+    /// compare event won't come until the counter wraps.
+    alarm_passed: Cell<bool>,
+}
+
+impl MicrosecondTimer {
+    pub fn new() -> Self {
+        let tasks = unsafe { &mut *(TIMER_TASKS_OFFSET as *mut TimerPeripheralTasks) };
+        let events = unsafe { &mut *(TIMER_EVENTS_OFFSET as *mut TimerPeripheralEvents) };
+        let config = unsafe { &mut *(TIMER_CONFIG_OFFSET as *mut TimerPeripheralConfiguration) };
+        let capture_compare =
+            unsafe { &mut *(TIMER_CAPTURE_COMPARE_OFFSET as *mut TimerPeripheralCaptureCompare) };
+
+        unsafe {
+            tasks.trigger_stop.write(1);
+            config.mode.write(TIMER_MODE_TIMER);
+            config.bit_mode.write(TIMER_BIT_MODE_32);
+            // 16 MHz / 2^4 gives one tick per microsecond.
+            config.prescaler.write(4);
+            tasks.trigger_clear.write(1);
+            tasks.trigger_start.write(1);
+        }
+
+        return Self {
+            tasks,
+            events,
+            capture_compare,
+            last_microseconds: Cell::new(0),
+            elapsed_microseconds: Cell::new(0),
+            alarm_armed: Cell::new(false),
+            alarm_passed: Cell::new(false),
+        };
+    }
+
+    /// Microseconds since the timer was started, wrapping around with the
+    /// hardware counter every 71 minutes.
+    pub fn now_us(&self) -> u32 {
+        unsafe {
+            self.tasks.trigger_capture_0.write(1);
+            self.capture_compare.capture_compare_0.read()
+        }
+    }
+
+    /// Milliseconds since the timer was started, wrapping around on
+    /// overflow. Needs to be called at least once every 71 minutes, which
+    /// is when the hardware counter wraps.
+    pub fn now_ms(&self) -> u32 {
+        let microseconds = self.now_us();
+        let delta = microseconds.wrapping_sub(self.last_microseconds.get());
+        self.last_microseconds.set(microseconds);
+        self.elapsed_microseconds
+            .set(self.elapsed_microseconds.get() + delta as u64);
+
+        (self.elapsed_microseconds.get() / 1000) as u32
+    }
+
+    /// Arm the alarm to go off when `now_us` reaches `at`, see
+    /// `Clock::set_alarm_us`.
+    pub fn set_alarm_us(&self, at: u32) {
+        unsafe {
+            self.capture_compare.capture_compare_1.write(at);
+            // Cleared after the compare register is written, so an event of
+            // the previous alarm isn't taken for this one.
+            self.events.events_compare_1.write(0);
+        }
+        self.alarm_passed
+            .set((self.now_us().wrapping_sub(at) as i32) >= 0);
+        self.alarm_armed.set(true);
+    }
+
+    pub fn alarm_expired(&self) -> bool {
+        self.alarm_armed.get()
+            && (self.alarm_passed.get() || self.events.events_compare_1.read() == 1)
+    }
+
+    pub fn cancel_alarm(&self) {
+        self.alarm_armed.set(false);
+    }
+}
+
+const TIMER_BASE_ADDRESS: usize = 0x40009000;
+
+const TIMER_MODE_TIMER: u32 = 0;
+const TIMER_BIT_MODE_32: u32 = 3;
+
+const TIMER_TASKS_OFFSET: usize = TIMER_BASE_ADDRESS + 0x0;
+#[allow(dead_code)]
+#[repr(C)]
+pub struct TimerPeripheralTasks {
+    /// TASKS_START in Nordic's datasheet.
+    trigger_start: volatile_register::WO<u32>,
+    /// TASKS_STOP in Nordic's datasheet.
+    trigger_stop: volatile_register::WO<u32>,
+    /// TASKS_COUNT in Nordic's datasheet, only used in counter mode.
+    trigger_count: volatile_register::WO<u32>,
+    /// TASKS_CLEAR in Nordic's datasheet.
+    trigger_clear: volatile_register::WO<u32>,
+    /// TASKS_SHUTDOWN in Nordic's datasheet, deprecated.
+    trigger_shutdown: volatile_register::WO<u32>,
+    _reserved: [u32; 11],
+    /// Copies the counter into the first capture/compare register.
+    ///
+    /// TASKS_CAPTURE[0] in Nordic's datasheet.
+    trigger_capture_0: volatile_register::WO<u32>,
+}
+
+const TIMER_EVENTS_OFFSET: usize = TIMER_BASE_ADDRESS + 0x140;
+#[repr(C)]
+pub struct TimerPeripheralEvents {
+    /// EVENTS_COMPARE[0] in Nordic's datasheet.
+    events_compare_0: volatile_register::RW<u32>,
+    /// Set when the counter reaches the alarm in the second capture/compare
+    /// register.
+    ///
+    /// EVENTS_COMPARE[1] in Nordic's datasheet.
+    events_compare_1: volatile_register::RW<u32>,
+}
+
+const TIMER_CONFIG_OFFSET: usize = TIMER_BASE_ADDRESS + 0x504;
+#[allow(dead_code)]
+#[repr(C)]
+pub struct TimerPeripheralConfiguration {
+    /// MODE in Nordic's datasheet.
+    mode: volatile_register::RW<u32>,
+    /// BITMODE in Nordic's datasheet.
+    bit_mode: volatile_register::RW<u32>,
+    _reserved: u32,
+    /// The timer runs at 16 MHz / 2^prescaler.
+    ///
+    /// PRESCALER in Nordic's datasheet.
+    prescaler: volatile_register::RW<u32>,
+}
+
+const TIMER_CAPTURE_COMPARE_OFFSET: usize = TIMER_BASE_ADDRESS + 0x540;
+#[repr(C)]
+pub struct TimerPeripheralCaptureCompare {
+    /// CC[0] in Nordic's datasheet.
+    capture_compare_0: volatile_register::RW<u32>,
+    /// Holds the alarm time.
+    ///
+    /// CC[1] in Nordic's datasheet.
+    capture_compare_1: volatile_register::RW<u32>,
+}
@@ -0,0 +1,277 @@
+use super::ApsFrame;
+use crate::hardware::Clock;
+use crate::network_layer::WriteError;
+use crate::table::Table;
+
+/// Number of times an acknowledged frame is sent again before giving up
+/// (apscMaxFrameRetries).
+pub const APSC_MAX_FRAME_RETRIES: u8 = 3;
+/// How long to wait for an acknowledgement before sending a frame again
+/// (apscAckWaitDuration), for a network of the maximum depth of 15.
+pub const APSC_ACK_WAIT_DURATION_MS: u32 = 1_500;
+/// How long a received frame is remembered to reject duplicates of it
+/// (apsDuplicateRejectionTimeout).
+pub const APS_DUPLICATE_REJECTION_TIMEOUT_MS: u32 = 3_000;
+
+#[derive(Debug, Clone, Copy)]
+struct ReceivedFrame {
+    source: u16,
+    counter: u8,
+    received_at: u32,
+}
+
+/// Sources and APS counters of recently received frames, used to drop
+/// frames that were received before, such as retransmissions whose
+/// acknowledgement got lost. Each frame is remembered for
+/// `APS_DUPLICATE_REJECTION_TIMEOUT_MS`, so a retransmission is caught even
+/// if later frames from the same source were received in between.
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateRejectionTable<const N: usize> {
+    frames: Table<ReceivedFrame, N>,
+}
+impl<const N: usize> DuplicateRejectionTable<N> {
+    pub const fn new() -> Self {
+        Self {
+            frames: Table::new(),
+        }
+    }
+
+    /// Record a frame with `counter` received from the network address
+    /// `source`, returning whether it is a duplicate that should be
+    /// dropped. Duplicates are still acknowledged by the caller.
+    pub fn is_duplicate(&mut self, hardware: &impl Clock, source: u16, counter: u8) -> bool {
+        let now = hardware.now_ms();
+        self.frames.remove_where(|frame| {
+            now.wrapping_sub(frame.received_at) >= APS_DUPLICATE_REJECTION_TIMEOUT_MS
+        });
+
+        if self
+            .frames
+            .find(|frame| frame.source == source && frame.counter == counter)
+            .is_some()
+        {
+            return true;
+        }
+
+        // When the table is full the frame can't be tracked, so it is
+        // accepted.
+        let _ = self.frames.insert(ReceivedFrame {
+            source,
+            counter,
+            received_at: now,
+        });
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingFrame<const LEN: usize> {
+    destination: u16,
+    counter: u8,
+    sent_at: u32,
+    retries: u8,
+    length: usize,
+    frame: [u8; LEN],
+}
+
+/// What to do with a frame that wasn't acknowledged in time.
+#[derive(Debug, PartialEq)]
+pub enum Retransmission<'a> {
+    /// Send `frame` to `destination` again.
+    Resend { destination: u16, frame: &'a [u8] },
+    /// The frame with `counter` was never acknowledged by `destination`,
+    /// and has been dropped.
+    Failed { destination: u16, counter: u8 },
+}
+
+/// Sent frames waiting for an acknowledgement, each stored in a buffer of
+/// `LEN` bytes so it can be sent again.
+#[derive(Debug, Clone)]
+pub struct RetransmissionQueue<const N: usize, const LEN: usize> {
+    frames: Table<PendingFrame<LEN>, N>,
+}
+impl<const N: usize, const LEN: usize> RetransmissionQueue<N, LEN> {
+    pub const fn new() -> Self {
+        Self {
+            frames: Table::new(),
+        }
+    }
+
+    /// Keep a copy of `frame`, which was just sent to the network address
+    /// `destination` and requests an acknowledgement.
+    pub fn push(
+        &mut self,
+        hardware: &impl Clock,
+        destination: u16,
+        frame: &ApsFrame,
+    ) -> Result<(), WriteError> {
+        let mut pending = PendingFrame {
+            destination,
+            counter: frame.counter.ok_or(WriteError)?,
+            sent_at: hardware.now_ms(),
+            retries: 0,
+            length: 0,
+            frame: [0; LEN],
+        };
+        pending.length = frame.write_into(&mut pending.frame)?;
+
+        self.frames.insert(pending).map_err(|_| WriteError)
+    }
+
+    /// Handle an acknowledgement received from `source`, returning whether
+    /// it matched a pending frame.
+    pub fn acknowledge(&mut self, source: u16, acknowledgement: &ApsFrame) -> bool {
+        let counter = match acknowledgement.counter {
+            Some(counter) => counter,
+            None => return false,
+        };
+        self.frames
+            .remove_where(|frame| frame.destination == source && frame.counter == counter)
+            > 0
+    }
+
+    /// Pass every frame whose acknowledgement timed out to `handle`, either
+    /// to be sent again or, after `APSC_MAX_FRAME_RETRIES` attempts, to be
+    /// reported as failed. Should be called regularly.
+    pub fn poll(&mut self, hardware: &impl Clock, mut handle: impl FnMut(Retransmission)) {
+        let now = hardware.now_ms();
+
+        for pending in self.frames.iter_mut() {
+            if now.wrapping_sub(pending.sent_at) < APSC_ACK_WAIT_DURATION_MS {
+                continue;
+            }
+            if pending.retries == APSC_MAX_FRAME_RETRIES {
+                handle(Retransmission::Failed {
+                    destination: pending.destination,
+                    counter: pending.counter,
+                });
+                continue;
+            }
+
+            pending.retries += 1;
+            pending.sent_at = now;
+            handle(Retransmission::Resend {
+                destination: pending.destination,
+                frame: &pending.frame[..pending.length],
+            });
+        }
+
+        self.frames.remove_where(|pending| {
+            pending.retries == APSC_MAX_FRAME_RETRIES
+                && now.wrapping_sub(pending.sent_at) >= APSC_ACK_WAIT_DURATION_MS
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+impl<const N: usize, const LEN: usize> Default for RetransmissionQueue<N, LEN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TestHardware;
+
+    #[test]
+    fn rejects_duplicates() {
+        let hardware = TestHardware::new();
+        let mut table = DuplicateRejectionTable::<1>::new();
+
+        assert!(!table.is_duplicate(&hardware, 0x1234, 7));
+        assert!(table.is_duplicate(&hardware, 0x1234, 7));
+        assert!(!table.is_duplicate(&hardware, 0x1234, 8));
+        // No room to track the second device.
+        assert!(!table.is_duplicate(&hardware, 0x5678, 1));
+        assert!(!table.is_duplicate(&hardware, 0x5678, 1));
+
+        hardware.advance_ms(APS_DUPLICATE_REJECTION_TIMEOUT_MS);
+        assert!(!table.is_duplicate(&hardware, 0x1234, 8));
+    }
+
+    #[test]
+    fn rejects_retransmissions_of_older_frames() {
+        let hardware = TestHardware::new();
+        let mut table = DuplicateRejectionTable::<4>::new();
+
+        // Two frames in flight, and the first one sent again because its
+        // acknowledgement got lost.
+        assert!(!table.is_duplicate(&hardware, 0x1234, 7));
+        assert!(!table.is_duplicate(&hardware, 0x1234, 8));
+        assert!(table.is_duplicate(&hardware, 0x1234, 7));
+        assert!(table.is_duplicate(&hardware, 0x1234, 8));
+        assert!(!table.is_duplicate(&hardware, 0x5678, 7));
+
+        hardware.advance_ms(APS_DUPLICATE_REJECTION_TIMEOUT_MS - 1);
+        assert!(!table.is_duplicate(&hardware, 0x1234, 9));
+        hardware.advance_ms(1);
+        // Only the frames received before the timeout are forgotten.
+        assert!(!table.is_duplicate(&hardware, 0x1234, 7));
+        assert!(table.is_duplicate(&hardware, 0x1234, 9));
+    }
+
+    #[test]
+    fn resends_until_acknowledged() {
+        let hardware = TestHardware::new();
+        let mut queue = RetransmissionQueue::<2, 32>::new();
+        let bytes = b"\x40\x0b\x06\x00\x04\x01\x01\x22\x01\x05\x02";
+        let frame = ApsFrame::try_parse_from(bytes).unwrap();
+
+        queue.push(&hardware, 0x1234, &frame).unwrap();
+
+        queue.poll(&hardware, |_| panic!("nothing timed out yet"));
+
+        hardware.advance_ms(APSC_ACK_WAIT_DURATION_MS);
+        let mut resent = 0;
+        queue.poll(&hardware, |retransmission| {
+            assert_eq!(
+                retransmission,
+                Retransmission::Resend {
+                    destination: 0x1234,
+                    frame: bytes,
+                }
+            );
+            resent += 1;
+        });
+        assert_eq!(resent, 1);
+
+        let ack = frame.acknowledgement().unwrap();
+        assert!(!queue.acknowledge(0x5678, &ack));
+        assert!(queue.acknowledge(0x1234, &ack));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let hardware = TestHardware::new();
+        let mut queue = RetransmissionQueue::<1, 32>::new();
+        let frame = ApsFrame::try_parse_from(b"\x40\x0b\x06\x00\x04\x01\x01\x22").unwrap();
+        queue.push(&hardware, 0x1234, &frame).unwrap();
+        assert!(queue.push(&hardware, 0x1234, &frame).is_err());
+
+        let mut resent = 0;
+        let mut failed = None;
+        for _ in 0..=APSC_MAX_FRAME_RETRIES {
+            hardware.advance_ms(APSC_ACK_WAIT_DURATION_MS);
+            queue.poll(&hardware, |retransmission| match retransmission {
+                Retransmission::Resend { .. } => resent += 1,
+                Retransmission::Failed {
+                    destination,
+                    counter,
+                } => failed = Some((destination, counter)),
+            });
+        }
+
+        assert_eq!(resent, APSC_MAX_FRAME_RETRIES);
+        assert_eq!(failed, Some((0x1234, 0x22)));
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,162 @@
+use super::{ApsFrame, DeliveryMode};
+use crate::table::{Table, TableFull};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BindingDestination {
+    Group(u16),
+    Device { ieee_address: u64, endpoint: u8 },
+}
+impl BindingDestination {
+    /// A copy of `frame`, sent in indirect delivery mode, addressed to this
+    /// destination. Frames to a group don't request an acknowledgement.
+    /// Frames to a device are unicast, the caller still needs to look up
+    /// the network address of `ieee_address`.
+    pub fn address<'a>(&self, frame: &ApsFrame<'a>) -> ApsFrame<'a> {
+        let mut frame = frame.clone();
+        match *self {
+            BindingDestination::Group(group_address) => {
+                frame.frame_control_field.delivery_mode = DeliveryMode::Group;
+                frame.frame_control_field.ack_request = false;
+                frame.destination_endpoint = None;
+                frame.group_address = Some(group_address);
+            }
+            BindingDestination::Device { endpoint, .. } => {
+                frame.frame_control_field.delivery_mode = DeliveryMode::Unicast;
+                frame.destination_endpoint = Some(endpoint);
+                frame.group_address = None;
+            }
+        }
+        frame
+    }
+}
+
+/// Sends frames for a cluster on a local endpoint to a remote device or
+/// group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binding {
+    pub source_address: u64,
+    pub source_endpoint: u8,
+    pub cluster_id: u16,
+    pub destination: BindingDestination,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BindingTable<const N: usize> {
+    bindings: Table<Binding, N>,
+}
+impl<const N: usize> BindingTable<N> {
+    pub const fn new() -> Self {
+        Self {
+            bindings: Table::new(),
+        }
+    }
+
+    /// Add a binding, doing nothing if it already exists.
+    pub fn bind(&mut self, binding: Binding) -> Result<(), TableFull> {
+        self.bindings
+            .upsert(binding, |existing| *existing == binding)
+    }
+
+    /// Remove a binding, returning whether it existed.
+    pub fn unbind(&mut self, binding: &Binding) -> bool {
+        self.bindings.remove_where(|existing| existing == binding) > 0
+    }
+
+    /// Where a data frame in indirect delivery mode from `source_endpoint`
+    /// for `cluster_id` should be sent. There is one frame to send per
+    /// destination.
+    pub fn destinations(
+        &self,
+        source_endpoint: u8,
+        cluster_id: u16,
+    ) -> impl Iterator<Item = BindingDestination> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |binding| {
+                binding.source_endpoint == source_endpoint && binding.cluster_id == cluster_id
+            })
+            .map(|binding| binding.destination)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binds_once() {
+        let binding = Binding {
+            source_address: 0x0017_8801_0881_c09e,
+            source_endpoint: 1,
+            cluster_id: 0x0006,
+            destination: BindingDestination::Group(0x0001),
+        };
+        let mut table = BindingTable::<2>::new();
+
+        table.bind(binding).unwrap();
+        table.bind(binding).unwrap();
+        assert_eq!(table.len(), 1);
+
+        assert!(table.unbind(&binding));
+        assert!(!table.unbind(&binding));
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn resolves_indirect_frames() {
+        let mut table = BindingTable::<4>::new();
+        let binding = |source_endpoint, cluster_id, destination| Binding {
+            source_address: 0x0017_8801_0881_c09e,
+            source_endpoint,
+            cluster_id,
+            destination,
+        };
+        let light = BindingDestination::Device {
+            ieee_address: 0x1111_1111_1111_1111,
+            endpoint: 11,
+        };
+        table
+            .bind(binding(1, 0x0006, BindingDestination::Group(0x0001)))
+            .unwrap();
+        table.bind(binding(1, 0x0006, light)).unwrap();
+        table.bind(binding(1, 0x0008, light)).unwrap();
+        table.bind(binding(2, 0x0006, light)).unwrap();
+
+        let mut destinations = table.destinations(1, 0x0006);
+        let frame = ApsFrame::try_parse_from(b"\x44\x06\x00\x04\x01\x01\x22\x01\x05\x02").unwrap();
+
+        let group_frame = destinations.next().unwrap().address(&frame);
+        let mut buffer = [0u8; 16];
+        let len = group_frame.write_into(&mut buffer).unwrap();
+        assert_eq!(
+            &buffer[..len],
+            b"\x0c\x01\x00\x06\x00\x04\x01\x01\x22\x01\x05\x02"
+        );
+
+        let device_frame = destinations.next().unwrap().address(&frame);
+        let len = device_frame.write_into(&mut buffer).unwrap();
+        assert_eq!(
+            &buffer[..len],
+            b"\x40\x0b\x06\x00\x04\x01\x01\x22\x01\x05\x02"
+        );
+        // The frame itself is left in indirect delivery mode.
+        assert_eq!(
+            frame.frame_control_field.delivery_mode,
+            DeliveryMode::Indirect
+        );
+
+        assert_eq!(destinations.next(), None);
+    }
+}
@@ -0,0 +1,532 @@
+use super::security::keyed_hash;
+use crate::network_layer::security::SecurityKey;
+use crate::network_layer::{ParseError, WriteError};
+use byte::ctx::Bytes;
+use byte::{BytesExt, LE};
+
+/// Payload of an APS command frame, i.e. a frame with
+/// `FrameType::Command`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApsCommand {
+    TransportKey(TransportKey),
+    UpdateDevice(UpdateDevice),
+    RemoveDevice(RemoveDevice),
+    RequestKey(RequestKey),
+    SwitchKey(SwitchKey),
+    VerifyKey(VerifyKey),
+    ConfirmKey(ConfirmKey),
+}
+impl ApsCommand {
+    pub fn try_parse_from(payload: &[u8]) -> Result<Self, ParseError> {
+        let offset = &mut 0;
+
+        let command_id = payload.read_with::<u8>(offset, LE)?;
+        let command = match command_id {
+            0x05 => ApsCommand::TransportKey(TransportKey::try_parse_from(payload, offset)?),
+            0x06 => ApsCommand::UpdateDevice(UpdateDevice::try_parse_from(payload, offset)?),
+            0x07 => ApsCommand::RemoveDevice(RemoveDevice::try_parse_from(payload, offset)?),
+            0x08 => ApsCommand::RequestKey(RequestKey::try_parse_from(payload, offset)?),
+            0x09 => ApsCommand::SwitchKey(SwitchKey::try_parse_from(payload, offset)?),
+            0x0f => ApsCommand::VerifyKey(VerifyKey::try_parse_from(payload, offset)?),
+            0x10 => ApsCommand::ConfirmKey(ConfirmKey::try_parse_from(payload, offset)?),
+            _ => return Err(ParseError),
+        };
+
+        Ok(command)
+    }
+
+    /// Serialize the command into `buffer` as the payload of an APS command
+    /// frame, returning the number of bytes written.
+    pub fn write_into(&self, buffer: &mut [u8]) -> Result<usize, WriteError> {
+        let offset = &mut 0;
+
+        buffer.write_with::<u8>(offset, self.command_id(), LE)?;
+        match self {
+            ApsCommand::TransportKey(command) => command.write_into(buffer, offset)?,
+            ApsCommand::UpdateDevice(command) => command.write_into(buffer, offset)?,
+            ApsCommand::RemoveDevice(command) => command.write_into(buffer, offset)?,
+            ApsCommand::RequestKey(command) => command.write_into(buffer, offset)?,
+            ApsCommand::SwitchKey(command) => command.write_into(buffer, offset)?,
+            ApsCommand::VerifyKey(command) => command.write_into(buffer, offset)?,
+            ApsCommand::ConfirmKey(command) => command.write_into(buffer, offset)?,
+        }
+
+        Ok(*offset)
+    }
+
+    pub fn command_id(&self) -> u8 {
+        match self {
+            ApsCommand::TransportKey(_) => 0x05,
+            ApsCommand::UpdateDevice(_) => 0x06,
+            ApsCommand::RemoveDevice(_) => 0x07,
+            ApsCommand::RequestKey(_) => 0x08,
+            ApsCommand::SwitchKey(_) => 0x09,
+            ApsCommand::VerifyKey(_) => 0x0f,
+            ApsCommand::ConfirmKey(_) => 0x10,
+        }
+    }
+}
+
+fn read_key(payload: &[u8], offset: &mut usize) -> Result<SecurityKey, ParseError> {
+    let key = payload.read_with::<&[u8]>(offset, Bytes::Len(16))?;
+    // Should never panic, exactly 16 bytes were read.
+    Ok(key.try_into().unwrap())
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum KeyType {
+    StandardNetworkKey,
+    ApplicationLinkKey,
+    TrustCenterLinkKey,
+}
+impl TryFrom<u8> for KeyType {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(KeyType::StandardNetworkKey),
+            0x03 => Ok(KeyType::ApplicationLinkKey),
+            0x04 => Ok(KeyType::TrustCenterLinkKey),
+            _ => Err(()),
+        }
+    }
+}
+impl From<&KeyType> for u8 {
+    fn from(key_type: &KeyType) -> Self {
+        match key_type {
+            KeyType::StandardNetworkKey => 0x01,
+            KeyType::ApplicationLinkKey => 0x03,
+            KeyType::TrustCenterLinkKey => 0x04,
+        }
+    }
+}
+
+/// Delivers a key to a device, usually from the trust center. Needs to be
+/// secured with a key derived from the link key, see
+/// `security::frame_key`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportKey {
+    StandardNetworkKey {
+        key: SecurityKey,
+        sequence_number: u8,
+        /// Extended address of the device the key is for, or 0 when it is
+        /// broadcast to all devices.
+        destination_address: u64,
+        source_address: u64,
+    },
+    TrustCenterLinkKey {
+        key: SecurityKey,
+        destination_address: u64,
+        source_address: u64,
+    },
+    ApplicationLinkKey {
+        key: SecurityKey,
+        /// Extended address of the device the key is shared with.
+        partner_address: u64,
+        /// Set on the copy of the key sent to the device that requested it.
+        initiator: bool,
+    },
+}
+impl TransportKey {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let key_type = KeyType::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let key = read_key(payload, offset)?;
+
+        let transport_key = match key_type {
+            KeyType::StandardNetworkKey => TransportKey::StandardNetworkKey {
+                key,
+                sequence_number: payload.read_with::<u8>(offset, LE)?,
+                destination_address: payload.read_with::<u64>(offset, LE)?,
+                source_address: payload.read_with::<u64>(offset, LE)?,
+            },
+            KeyType::TrustCenterLinkKey => TransportKey::TrustCenterLinkKey {
+                key,
+                destination_address: payload.read_with::<u64>(offset, LE)?,
+                source_address: payload.read_with::<u64>(offset, LE)?,
+            },
+            KeyType::ApplicationLinkKey => TransportKey::ApplicationLinkKey {
+                key,
+                partner_address: payload.read_with::<u64>(offset, LE)?,
+                initiator: payload.read_with::<u8>(offset, LE)? == 0x01,
+            },
+        };
+
+        Ok(transport_key)
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, u8::from(&self.key_type()), LE)?;
+        match self {
+            TransportKey::StandardNetworkKey {
+                key,
+                sequence_number,
+                destination_address,
+                source_address,
+            } => {
+                buffer.write(offset, &key[..])?;
+                buffer.write_with::<u8>(offset, *sequence_number, LE)?;
+                buffer.write_with::<u64>(offset, *destination_address, LE)?;
+                buffer.write_with::<u64>(offset, *source_address, LE)?;
+            }
+            TransportKey::TrustCenterLinkKey {
+                key,
+                destination_address,
+                source_address,
+            } => {
+                buffer.write(offset, &key[..])?;
+                buffer.write_with::<u64>(offset, *destination_address, LE)?;
+                buffer.write_with::<u64>(offset, *source_address, LE)?;
+            }
+            TransportKey::ApplicationLinkKey {
+                key,
+                partner_address,
+                initiator,
+            } => {
+                buffer.write(offset, &key[..])?;
+                buffer.write_with::<u64>(offset, *partner_address, LE)?;
+                buffer.write_with::<u8>(offset, *initiator as u8, LE)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        match self {
+            TransportKey::StandardNetworkKey { .. } => KeyType::StandardNetworkKey,
+            TransportKey::TrustCenterLinkKey { .. } => KeyType::TrustCenterLinkKey,
+            TransportKey::ApplicationLinkKey { .. } => KeyType::ApplicationLinkKey,
+        }
+    }
+
+    pub fn key(&self) -> &SecurityKey {
+        match self {
+            TransportKey::StandardNetworkKey { key, .. }
+            | TransportKey::TrustCenterLinkKey { key, .. }
+            | TransportKey::ApplicationLinkKey { key, .. } => key,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum UpdateDeviceStatus {
+    StandardDeviceSecuredRejoin,
+    StandardDeviceUnsecuredJoin,
+    DeviceLeft,
+    StandardDeviceTrustCenterRejoin,
+}
+impl TryFrom<u8> for UpdateDeviceStatus {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(UpdateDeviceStatus::StandardDeviceSecuredRejoin),
+            0x01 => Ok(UpdateDeviceStatus::StandardDeviceUnsecuredJoin),
+            0x02 => Ok(UpdateDeviceStatus::DeviceLeft),
+            0x03 => Ok(UpdateDeviceStatus::StandardDeviceTrustCenterRejoin),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Sent by a router to the trust center when a device joined, rejoined or
+/// left through it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateDevice {
+    pub device_address: u64,
+    pub device_network_address: u16,
+    pub status: UpdateDeviceStatus,
+}
+impl UpdateDevice {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let device_address = payload.read_with::<u64>(offset, LE)?;
+        let device_network_address = payload.read_with::<u16>(offset, LE)?;
+        let status = UpdateDeviceStatus::try_from(payload.read_with::<u8>(offset, LE)?)?;
+
+        Ok(Self {
+            device_address,
+            device_network_address,
+            status,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u64>(offset, self.device_address, LE)?;
+        buffer.write_with::<u16>(offset, self.device_network_address, LE)?;
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+
+        Ok(())
+    }
+}
+
+/// Sent by the trust center to a router, asking it to make one of its
+/// children leave the network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoveDevice {
+    pub target_address: u64,
+}
+impl RemoveDevice {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        Ok(Self {
+            target_address: payload.read_with::<u64>(offset, LE)?,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u64>(offset, self.target_address, LE)?;
+
+        Ok(())
+    }
+}
+
+/// Sent to the trust center to ask for a key, which is delivered with a
+/// Transport-Key command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestKey {
+    /// A link key shared with the device with the extended address
+    /// `partner_address`.
+    ApplicationLinkKey { partner_address: u64 },
+    /// A unique trust center link key, replacing the one used to join.
+    TrustCenterLinkKey,
+}
+impl RequestKey {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let request_key = match payload.read_with::<u8>(offset, LE)? {
+            0x02 => RequestKey::ApplicationLinkKey {
+                partner_address: payload.read_with::<u64>(offset, LE)?,
+            },
+            0x04 => RequestKey::TrustCenterLinkKey,
+            _ => return Err(ParseError),
+        };
+
+        Ok(request_key)
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        match self {
+            RequestKey::ApplicationLinkKey { partner_address } => {
+                buffer.write_with::<u8>(offset, 0x02, LE)?;
+                buffer.write_with::<u64>(offset, *partner_address, LE)?;
+            }
+            RequestKey::TrustCenterLinkKey => buffer.write_with::<u8>(offset, 0x04, LE)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Tells devices to start using the network key with `sequence_number`,
+/// which was delivered before with a Transport-Key command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchKey {
+    pub sequence_number: u8,
+}
+impl SwitchKey {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        Ok(Self {
+            sequence_number: payload.read_with::<u8>(offset, LE)?,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.sequence_number, LE)?;
+
+        Ok(())
+    }
+}
+
+/// Sent to the trust center after receiving a new trust center link key,
+/// proving the device has the key without sending it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyKey {
+    pub key_type: KeyType,
+    pub source_address: u64,
+    pub initiator_verify_key_hash: SecurityKey,
+}
+impl VerifyKey {
+    /// Verify `link_key`, sent from the device with the extended address
+    /// `source_address`.
+    pub fn new(source_address: u64, link_key: &SecurityKey) -> Self {
+        Self {
+            key_type: KeyType::TrustCenterLinkKey,
+            source_address,
+            initiator_verify_key_hash: keyed_hash(link_key, 0x03),
+        }
+    }
+
+    /// Whether the sender has the same `link_key` as this device.
+    pub fn matches(&self, link_key: &SecurityKey) -> bool {
+        self.initiator_verify_key_hash == keyed_hash(link_key, 0x03)
+    }
+
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let key_type = KeyType::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let source_address = payload.read_with::<u64>(offset, LE)?;
+        let initiator_verify_key_hash = read_key(payload, offset)?;
+
+        Ok(Self {
+            key_type,
+            source_address,
+            initiator_verify_key_hash,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, u8::from(&self.key_type), LE)?;
+        buffer.write_with::<u64>(offset, self.source_address, LE)?;
+        buffer.write(offset, &self.initiator_verify_key_hash[..])?;
+
+        Ok(())
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ConfirmKeyStatus {
+    Success = 0x00,
+    /// The hash in the Verify-Key command didn't match the trust center's
+    /// copy of the key.
+    SecurityFailure = 0xad,
+}
+impl TryFrom<u8> for ConfirmKeyStatus {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(ConfirmKeyStatus::Success),
+            0xad => Ok(ConfirmKeyStatus::SecurityFailure),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The trust center's answer to a Verify-Key command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmKey {
+    pub status: ConfirmKeyStatus,
+    pub key_type: KeyType,
+    pub destination_address: u64,
+}
+impl ConfirmKey {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = ConfirmKeyStatus::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let key_type = KeyType::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let destination_address = payload.read_with::<u64>(offset, LE)?;
+
+        Ok(Self {
+            status,
+            key_type,
+            destination_address,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        buffer.write_with::<u8>(offset, u8::from(&self.key_type), LE)?;
+        buffer.write_with::<u64>(offset, self.destination_address, LE)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aps::security::ZIGBEE_ALLIANCE_09_KEY;
+
+    fn assert_round_trip(bytes: &[u8], command: ApsCommand) {
+        assert_eq!(ApsCommand::try_parse_from(bytes).unwrap(), command);
+
+        let mut buffer = [0u8; 64];
+        let length = command.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..length], bytes);
+    }
+
+    #[test]
+    fn parses_transport_key() {
+        assert_round_trip(
+            b"\x05\x01\
+\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xaa\xbb\xcc\xdd\xee\xff\x00\
+\x9e\xc0\x81\x08\x01\x88\x17\x00\x04\x03\x02\x01\x00\x4b\x12\x00",
+            ApsCommand::TransportKey(TransportKey::StandardNetworkKey {
+                key: *b"\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xaa\xbb\xcc\xdd\xee\xff",
+                sequence_number: 0,
+                destination_address: 0x00_17_88_01_08_81_c0_9e,
+                source_address: 0x00_12_4b_00_01_02_03_04,
+            }),
+        );
+
+        assert_round_trip(
+            b"\x05\x03ZigBeeAlliance09\x04\x03\x02\x01\x00\x4b\x12\x00\x01",
+            ApsCommand::TransportKey(TransportKey::ApplicationLinkKey {
+                key: ZIGBEE_ALLIANCE_09_KEY,
+                partner_address: 0x00_12_4b_00_01_02_03_04,
+                initiator: true,
+            }),
+        );
+
+        // Master keys aren't supported.
+        assert!(ApsCommand::try_parse_from(b"\x05\x00ZigBeeAlliance09").is_err());
+    }
+
+    #[test]
+    fn parses_device_commands() {
+        assert_round_trip(
+            b"\x06\x9e\xc0\x81\x08\x01\x88\x17\x00\x34\x12\x01",
+            ApsCommand::UpdateDevice(UpdateDevice {
+                device_address: 0x00_17_88_01_08_81_c0_9e,
+                device_network_address: 0x1234,
+                status: UpdateDeviceStatus::StandardDeviceUnsecuredJoin,
+            }),
+        );
+        assert_round_trip(
+            b"\x07\x9e\xc0\x81\x08\x01\x88\x17\x00",
+            ApsCommand::RemoveDevice(RemoveDevice {
+                target_address: 0x00_17_88_01_08_81_c0_9e,
+            }),
+        );
+    }
+
+    #[test]
+    fn parses_key_requests() {
+        assert_round_trip(
+            b"\x08\x04",
+            ApsCommand::RequestKey(RequestKey::TrustCenterLinkKey),
+        );
+        assert_round_trip(
+            b"\x08\x02\x9e\xc0\x81\x08\x01\x88\x17\x00",
+            ApsCommand::RequestKey(RequestKey::ApplicationLinkKey {
+                partner_address: 0x00_17_88_01_08_81_c0_9e,
+            }),
+        );
+        assert_round_trip(
+            b"\x09\x01",
+            ApsCommand::SwitchKey(SwitchKey { sequence_number: 1 }),
+        );
+        assert_round_trip(
+            b"\x10\xad\x04\x9e\xc0\x81\x08\x01\x88\x17\x00",
+            ApsCommand::ConfirmKey(ConfirmKey {
+                status: ConfirmKeyStatus::SecurityFailure,
+                key_type: KeyType::TrustCenterLinkKey,
+                destination_address: 0x00_17_88_01_08_81_c0_9e,
+            }),
+        );
+    }
+
+    #[test]
+    fn verifies_link_key() {
+        let link_key = *b"\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10";
+        let command = ApsCommand::VerifyKey(VerifyKey::new(0x00_17_88_01_08_81_c0_9e, &link_key));
+
+        let mut buffer = [0u8; 32];
+        let length = command.write_into(&mut buffer).unwrap();
+        assert_eq!(length, 26);
+        assert_eq!(&buffer[..10], b"\x0f\x04\x9e\xc0\x81\x08\x01\x88\x17\x00");
+
+        let verify_key = match ApsCommand::try_parse_from(&buffer[..length]).unwrap() {
+            ApsCommand::VerifyKey(verify_key) => verify_key,
+            command => panic!("unexpected command {command:?}"),
+        };
+        assert!(verify_key.matches(&link_key));
+        assert!(!verify_key.matches(&ZIGBEE_ALLIANCE_09_KEY));
+    }
+}
@@ -0,0 +1,591 @@
+use super::acknowledgement::{APSC_ACK_WAIT_DURATION_MS, APSC_MAX_FRAME_RETRIES};
+use super::{ApsFrame, DeliveryMode, ExtendedHeader, Fragmentation, FrameControlField, FrameType};
+use crate::hardware::Clock;
+use crate::network_layer::WriteError;
+
+/// Largest number of blocks sent before waiting for an acknowledgement, as
+/// limited by the size of the acknowledgement bitfield.
+pub const MAX_WINDOW_SIZE: u8 = 8;
+/// How long to keep a partly reassembled payload after the last block was
+/// received, which is as long as the sender keeps retrying a window.
+pub const APS_REASSEMBLY_TIMEOUT_MS: u32 =
+    APSC_ACK_WAIT_DURATION_MS * (APSC_MAX_FRAME_RETRIES as u32 + 1);
+
+/// Bits of the acknowledgement bitfield for the blocks in the window
+/// starting at `window_start`. Bits for blocks past the window or past the
+/// last block are set in every acknowledgement.
+fn window_mask(window_start: u8, window_size: u8, total_blocks: u8) -> u8 {
+    let blocks = window_size.min(total_blocks - window_start);
+    (0xffu16 >> (8 - blocks)) as u8
+}
+
+/// Header fields shared by the blocks of a fragmented frame.
+#[derive(Debug, Clone)]
+struct Header {
+    frame_control_field: FrameControlField,
+    destination_endpoint: Option<u8>,
+    cluster_id: Option<u16>,
+    profile_id: Option<u16>,
+    source_endpoint: Option<u8>,
+    counter: u8,
+    /// Network address of the sender of the blocks being reassembled,
+    /// `None` for the blocks we send.
+    source: Option<u16>,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TransmissionStatus {
+    InProgress,
+    Complete,
+    /// A window was never acknowledged, even after retrying.
+    Failed,
+}
+
+/// A payload too large for one frame, sent as blocks of up to
+/// `block_size` bytes. After each window of blocks the receiver
+/// acknowledges which blocks it got, and missing ones are sent again.
+#[derive(Debug, Clone)]
+pub struct FragmentedTransmission<'a> {
+    header: Header,
+    payload: &'a [u8],
+    block_size: usize,
+    total_blocks: u8,
+    window_size: u8,
+    window_start: u8,
+    /// Acknowledgement bitfield of the current window.
+    acknowledged: u8,
+    /// When the current window was last sent, `None` if it hasn't been yet.
+    sent_at: Option<u32>,
+    retries: u8,
+}
+impl<'a> FragmentedTransmission<'a> {
+    /// Split the payload of `frame`, a unicast data frame, into blocks.
+    /// The blocks are sent unsecured and request an acknowledgement.
+    pub fn new(
+        frame: &ApsFrame<'a>,
+        block_size: usize,
+        window_size: u8,
+    ) -> Result<Self, WriteError> {
+        let fcf = &frame.frame_control_field;
+        if fcf.frame_type != FrameType::Data
+            || fcf.delivery_mode != DeliveryMode::Unicast
+            || block_size == 0
+            || !(1..=MAX_WINDOW_SIZE).contains(&window_size)
+        {
+            return Err(WriteError);
+        }
+        let total_blocks = frame.payload.len().div_ceil(block_size);
+        let total_blocks = match u8::try_from(total_blocks) {
+            Ok(0) | Err(_) => return Err(WriteError),
+            Ok(total_blocks) => total_blocks,
+        };
+
+        let frame_control_field = FrameControlField {
+            security: false,
+            ack_request: true,
+            extended_header_present: true,
+            ..fcf.clone()
+        };
+
+        Ok(Self {
+            header: Header {
+                frame_control_field,
+                destination_endpoint: frame.destination_endpoint,
+                cluster_id: frame.cluster_id,
+                profile_id: frame.profile_id,
+                source_endpoint: frame.source_endpoint,
+                counter: frame.counter.ok_or(WriteError)?,
+                source: None,
+            },
+            payload: frame.payload,
+            block_size,
+            total_blocks,
+            window_size,
+            window_start: 0,
+            acknowledged: !window_mask(0, window_size, total_blocks),
+            sent_at: None,
+            retries: 0,
+        })
+    }
+
+    pub fn total_blocks(&self) -> u8 {
+        self.total_blocks
+    }
+
+    /// The frame carrying block `index`.
+    pub fn block(&self, index: u8) -> ApsFrame<'a> {
+        let start = index as usize * self.block_size;
+        let end = (start + self.block_size).min(self.payload.len());
+        let extended_header = match index {
+            0 => ExtendedHeader {
+                fragmentation: Fragmentation::FirstFragment,
+                block_number: Some(self.total_blocks),
+                ack_bitfield: None,
+            },
+            _ => ExtendedHeader {
+                fragmentation: Fragmentation::Fragment,
+                block_number: Some(index),
+                ack_bitfield: None,
+            },
+        };
+
+        ApsFrame {
+            frame_control_field: self.header.frame_control_field.clone(),
+            destination_endpoint: self.header.destination_endpoint,
+            group_address: None,
+            cluster_id: self.header.cluster_id,
+            profile_id: self.header.profile_id,
+            source_endpoint: self.header.source_endpoint,
+            counter: Some(self.header.counter),
+            extended_header: Some(extended_header),
+            security_header: None,
+            payload: &self.payload[start..end],
+        }
+    }
+
+    /// Send the blocks of the current window that haven't been
+    /// acknowledged, if the window hasn't been sent yet or its
+    /// acknowledgement timed out. Should be called regularly.
+    pub fn poll(
+        &mut self,
+        hardware: &impl Clock,
+        mut send: impl FnMut(&ApsFrame),
+    ) -> TransmissionStatus {
+        if self.window_start >= self.total_blocks {
+            return TransmissionStatus::Complete;
+        }
+
+        let now = hardware.now_ms();
+        match self.sent_at {
+            None => {}
+            Some(sent_at) if now.wrapping_sub(sent_at) < APSC_ACK_WAIT_DURATION_MS => {
+                return TransmissionStatus::InProgress;
+            }
+            Some(_) if self.retries == APSC_MAX_FRAME_RETRIES => {
+                return TransmissionStatus::Failed;
+            }
+            Some(_) => self.retries += 1,
+        }
+
+        for bit in 0..self.window_size {
+            if (self.acknowledged >> bit) & 1 == 0 {
+                send(&self.block(self.window_start + bit));
+            }
+        }
+        self.sent_at = Some(now);
+
+        TransmissionStatus::InProgress
+    }
+
+    /// Handle an acknowledgement from the receiver, moving on to the next
+    /// window once every block of the current one was received. Returns
+    /// whether the acknowledgement was for this transmission.
+    pub fn acknowledge(&mut self, acknowledgement: &ApsFrame) -> bool {
+        let header = match &acknowledgement.extended_header {
+            Some(header) => header,
+            None => return false,
+        };
+        if acknowledgement.counter != Some(self.header.counter)
+            || header.block_number != Some(self.window_start)
+        {
+            return false;
+        }
+
+        self.acknowledged |= header.ack_bitfield.unwrap_or(0);
+        if self.acknowledged == 0xff {
+            self.window_start = self.window_start.saturating_add(self.window_size);
+            if self.window_start < self.total_blocks {
+                self.acknowledged =
+                    !window_mask(self.window_start, self.window_size, self.total_blocks);
+            }
+            self.sent_at = None;
+            self.retries = 0;
+        }
+        true
+    }
+}
+
+/// Reassembles the blocks of a fragmented frame into a caller provided
+/// buffer. Blocks that don't fit in the buffer are dropped, so the transfer
+/// never completes.
+#[derive(Debug)]
+pub struct Reassembly<'b> {
+    buffer: &'b mut [u8],
+    window_size: u8,
+    /// Header of the first block, `None` when no transfer is in progress.
+    header: Option<Header>,
+    block_size: usize,
+    total_blocks: u8,
+    length: usize,
+    window_start: u8,
+    /// Acknowledgement bitfield of the current window.
+    received: u8,
+    last_received_at: u32,
+    partial_acknowledgement_sent: bool,
+}
+impl<'b> Reassembly<'b> {
+    /// `window_size` needs to match the one the sender uses.
+    pub fn new(buffer: &'b mut [u8], window_size: u8) -> Self {
+        Self {
+            buffer,
+            window_size: window_size.clamp(1, MAX_WINDOW_SIZE),
+            header: None,
+            block_size: 0,
+            total_blocks: 0,
+            length: 0,
+            window_start: 0,
+            received: 0,
+            last_received_at: 0,
+            partial_acknowledgement_sent: false,
+        }
+    }
+
+    pub fn is_in_progress(&self) -> bool {
+        self.header.is_some() && !self.is_complete()
+    }
+
+    fn is_complete(&self) -> bool {
+        self.header.is_some() && self.window_start >= self.total_blocks
+    }
+
+    /// The reassembled payload, once every block was received.
+    pub fn payload(&self) -> Option<&[u8]> {
+        match self.is_complete() {
+            true => Some(&self.buffer[..self.length]),
+            false => None,
+        }
+    }
+
+    /// Drop the current transfer, to be ready for the next one.
+    pub fn reset(&mut self) {
+        self.header = None;
+    }
+
+    /// Store a block received from the network address `source`,
+    /// returning the acknowledgement to send back once its window is
+    /// complete. Blocks of other transfers than the one in progress are
+    /// ignored, while the first block of a new transfer replaces a
+    /// completed one.
+    pub fn receive(
+        &mut self,
+        hardware: &impl Clock,
+        source: u16,
+        frame: &ApsFrame,
+    ) -> Option<ApsFrame<'static>> {
+        let extended_header = frame.extended_header.as_ref()?;
+        let index = match (extended_header.fragmentation, extended_header.block_number) {
+            (Fragmentation::FirstFragment, Some(total_blocks)) if total_blocks > 0 => {
+                if self.header.is_none() || (self.is_complete() && !self.is_from(source, frame)) {
+                    self.start(source, frame, total_blocks)?;
+                }
+                0
+            }
+            (Fragmentation::Fragment, Some(index)) => index,
+            _ => return None,
+        };
+        if !self.is_from(source, frame) {
+            return None;
+        }
+
+        if index < self.window_start {
+            // The acknowledgement of an earlier window got lost.
+            let window_start = index - index % self.window_size;
+            return Some(self.acknowledgement(window_start, 0xff));
+        }
+        let bit = index - self.window_start;
+        if bit >= self.window_size || index >= self.total_blocks {
+            return None;
+        }
+
+        let is_last = index == self.total_blocks - 1;
+        let length = frame.payload.len();
+        if (is_last && length > self.block_size) || (!is_last && length != self.block_size) {
+            return None;
+        }
+        let start = index as usize * self.block_size;
+        self.buffer
+            .get_mut(start..start + length)?
+            .copy_from_slice(frame.payload);
+        if is_last {
+            self.length = start + length;
+        }
+
+        self.received |= 1 << bit;
+        self.last_received_at = hardware.now_ms();
+        self.partial_acknowledgement_sent = false;
+
+        if self.received != 0xff {
+            return None;
+        }
+        let acknowledgement = self.acknowledgement(self.window_start, 0xff);
+        self.window_start = self.window_start.saturating_add(self.window_size);
+        if self.window_start < self.total_blocks {
+            self.received = !window_mask(self.window_start, self.window_size, self.total_blocks);
+        }
+        Some(acknowledgement)
+    }
+
+    /// Acknowledge the blocks received so far when the rest of the window
+    /// is taking too long, so the sender only sends the missing ones again.
+    /// Transfers that stopped are dropped. Should be called regularly.
+    pub fn poll(&mut self, hardware: &impl Clock) -> Option<ApsFrame<'static>> {
+        if !self.is_in_progress() {
+            return None;
+        }
+
+        let elapsed = hardware.now_ms().wrapping_sub(self.last_received_at);
+        if elapsed >= APS_REASSEMBLY_TIMEOUT_MS {
+            self.reset();
+            return None;
+        }
+        let window_mask = window_mask(self.window_start, self.window_size, self.total_blocks);
+        if elapsed < APSC_ACK_WAIT_DURATION_MS
+            || self.partial_acknowledgement_sent
+            || self.received & window_mask == 0
+        {
+            return None;
+        }
+
+        self.partial_acknowledgement_sent = true;
+        Some(self.acknowledgement(self.window_start, self.received))
+    }
+
+    /// Whether `frame` from `source` belongs to the current transfer.
+    fn is_from(&self, source: u16, frame: &ApsFrame) -> bool {
+        self.header.as_ref().is_some_and(|header| {
+            header.source == Some(source)
+                && Some(header.counter) == frame.counter
+                && header.source_endpoint == frame.source_endpoint
+        })
+    }
+
+    fn start(&mut self, source: u16, frame: &ApsFrame, total_blocks: u8) -> Option<()> {
+        let fcf = &frame.frame_control_field;
+        if fcf.frame_type != FrameType::Data {
+            return None;
+        }
+
+        self.header = Some(Header {
+            frame_control_field: fcf.clone(),
+            destination_endpoint: frame.destination_endpoint,
+            cluster_id: frame.cluster_id,
+            profile_id: frame.profile_id,
+            source_endpoint: frame.source_endpoint,
+            counter: frame.counter?,
+            source: Some(source),
+        });
+        self.block_size = frame.payload.len();
+        self.total_blocks = total_blocks;
+        self.length = 0;
+        self.window_start = 0;
+        self.received = !window_mask(0, self.window_size, total_blocks);
+        Some(())
+    }
+
+    fn acknowledgement(&self, window_start: u8, ack_bitfield: u8) -> ApsFrame<'static> {
+        // Only called while a transfer is in progress.
+        let header = self.header.as_ref().unwrap();
+        let fragmentation = match window_start {
+            0 => Fragmentation::FirstFragment,
+            _ => Fragmentation::Fragment,
+        };
+
+        ApsFrame {
+            frame_control_field: FrameControlField {
+                frame_type: FrameType::Acknowledgement,
+                delivery_mode: DeliveryMode::Unicast,
+                ack_format: false,
+                security: false,
+                ack_request: false,
+                extended_header_present: true,
+            },
+            destination_endpoint: header.source_endpoint,
+            group_address: None,
+            cluster_id: header.cluster_id,
+            profile_id: header.profile_id,
+            source_endpoint: header.destination_endpoint,
+            counter: Some(header.counter),
+            extended_header: Some(ExtendedHeader {
+                fragmentation,
+                block_number: Some(window_start),
+                ack_bitfield: Some(ack_bitfield),
+            }),
+            security_header: None,
+            payload: &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TestHardware;
+
+    const PAYLOAD: &[u8] = b"0123456789abcdefghij";
+
+    fn frame() -> ApsFrame<'static> {
+        let mut frame = ApsFrame::try_parse_from(b"\x40\x0b\x19\x00\x04\x01\x01\x22").unwrap();
+        frame.payload = PAYLOAD;
+        frame
+    }
+
+    #[test]
+    fn writes_blocks() {
+        let transmission = FragmentedTransmission::new(&frame(), 8, 2).unwrap();
+        assert_eq!(transmission.total_blocks(), 3);
+
+        let mut buffer = [0u8; 32];
+        let len = transmission.block(0).write_into(&mut buffer).unwrap();
+        assert_eq!(
+            &buffer[..len],
+            b"\xc0\x0b\x19\x00\x04\x01\x01\x22\x01\x03\x30\x31\x32\x33\x34\x35\x36\x37"
+        );
+        let len = transmission.block(2).write_into(&mut buffer).unwrap();
+        assert_eq!(
+            &buffer[..len],
+            b"\xc0\x0b\x19\x00\x04\x01\x01\x22\x02\x02ghij"
+        );
+
+        assert!(FragmentedTransmission::new(&frame(), 8, 9).is_err());
+        assert!(FragmentedTransmission::new(&frame(), 0, 1).is_err());
+    }
+
+    #[test]
+    fn transfers_payload_over_lossy_link() {
+        let hardware = TestHardware::new();
+        let mut transmission = FragmentedTransmission::new(&frame(), 8, 2).unwrap();
+        let mut buffer = [0u8; 32];
+        let mut reassembly = Reassembly::new(&mut buffer, 2);
+
+        let status = transmission.poll(&hardware, |block| {
+            // Block 1 of the first window is lost.
+            if block.extended_header.as_ref().unwrap().fragmentation == Fragmentation::FirstFragment
+            {
+                assert!(reassembly.receive(&hardware, 0x1234, block).is_none());
+            }
+        });
+        assert_eq!(status, TransmissionStatus::InProgress);
+
+        hardware.advance_ms(APSC_ACK_WAIT_DURATION_MS);
+        let ack = reassembly.poll(&hardware).unwrap();
+        assert_eq!(
+            ack.extended_header.as_ref().unwrap().ack_bitfield,
+            Some(0xfd)
+        );
+        assert!(transmission.acknowledge(&ack));
+        assert!(reassembly.poll(&hardware).is_none());
+
+        // Only the missing block is sent again.
+        let mut resent = 0;
+        let status = transmission.poll(&hardware, |block| {
+            assert_eq!(
+                block.extended_header.as_ref().unwrap().block_number,
+                Some(1)
+            );
+            assert_eq!(block.payload, &PAYLOAD[8..16]);
+            resent += 1;
+        });
+        assert_eq!(status, TransmissionStatus::InProgress);
+        assert_eq!(resent, 1);
+
+        let ack = reassembly
+            .receive(&hardware, 0x1234, &transmission.block(1))
+            .unwrap();
+        assert!(transmission.acknowledge(&ack));
+
+        let ack = reassembly
+            .receive(&hardware, 0x1234, &transmission.block(2))
+            .unwrap();
+        assert_eq!(ack.extended_header.as_ref().unwrap().block_number, Some(2));
+        assert!(transmission.acknowledge(&ack));
+
+        assert_eq!(
+            transmission.poll(&hardware, |_| panic!("everything was acknowledged")),
+            TransmissionStatus::Complete
+        );
+        assert_eq!(reassembly.payload(), Some(PAYLOAD));
+    }
+
+    #[test]
+    fn fails_without_acknowledgements() {
+        let hardware = TestHardware::new();
+        let mut transmission = FragmentedTransmission::new(&frame(), 16, 2).unwrap();
+
+        let mut sent = 0;
+        for _ in 0..=APSC_MAX_FRAME_RETRIES {
+            transmission.poll(&hardware, |_| sent += 1);
+            hardware.advance_ms(APSC_ACK_WAIT_DURATION_MS);
+        }
+        assert_eq!(sent, 2 * (APSC_MAX_FRAME_RETRIES as usize + 1));
+        assert_eq!(
+            transmission.poll(&hardware, |_| sent += 1),
+            TransmissionStatus::Failed
+        );
+    }
+
+    #[test]
+    fn drops_stalled_reassembly() {
+        let hardware = TestHardware::new();
+        let transmission = FragmentedTransmission::new(&frame(), 8, 2).unwrap();
+        let mut buffer = [0u8; 16];
+        let mut reassembly = Reassembly::new(&mut buffer, 2);
+
+        // Blocks before the first one are dropped, the sender will retry
+        // them.
+        assert!(reassembly
+            .receive(&hardware, 0x1234, &transmission.block(1))
+            .is_none());
+        assert!(!reassembly.is_in_progress());
+
+        reassembly.receive(&hardware, 0x1234, &transmission.block(0));
+        assert!(reassembly.is_in_progress());
+        // The last block doesn't fit in the buffer.
+        reassembly.receive(&hardware, 0x1234, &transmission.block(1));
+        assert!(reassembly
+            .receive(&hardware, 0x1234, &transmission.block(2))
+            .is_none());
+
+        hardware.advance_ms(APS_REASSEMBLY_TIMEOUT_MS);
+        assert!(reassembly.poll(&hardware).is_none());
+        assert!(!reassembly.is_in_progress());
+        assert_eq!(reassembly.payload(), None);
+    }
+
+    #[test]
+    fn keeps_transfers_of_other_senders_apart() {
+        let hardware = TestHardware::new();
+        let transmission = FragmentedTransmission::new(&frame(), 8, 2).unwrap();
+        let mut buffer = [0u8; 32];
+        let mut reassembly = Reassembly::new(&mut buffer, 2);
+
+        reassembly.receive(&hardware, 0x1234, &transmission.block(0));
+        // Another device that happens to use the same APS counter.
+        assert!(reassembly
+            .receive(&hardware, 0x5678, &transmission.block(0))
+            .is_none());
+        assert!(reassembly
+            .receive(&hardware, 0x5678, &transmission.block(1))
+            .is_none());
+        assert!(reassembly
+            .receive(&hardware, 0x1234, &transmission.block(1))
+            .is_some());
+        assert!(reassembly
+            .receive(&hardware, 0x1234, &transmission.block(2))
+            .is_some());
+        assert_eq!(reassembly.payload(), Some(PAYLOAD));
+
+        // The first window again, as its acknowledgement got lost.
+        let ack = reassembly
+            .receive(&hardware, 0x1234, &transmission.block(0))
+            .unwrap();
+        assert_eq!(ack.extended_header.as_ref().unwrap().block_number, Some(0));
+        assert_eq!(reassembly.payload(), Some(PAYLOAD));
+
+        // A new transfer starts without a reset.
+        assert!(reassembly
+            .receive(&hardware, 0x5678, &transmission.block(0))
+            .is_none());
+        assert!(reassembly.is_in_progress());
+        assert_eq!(reassembly.payload(), None);
+    }
+}
@@ -0,0 +1,128 @@
+use super::{ApsFrame, DeliveryMode};
+use crate::network_layer::ZigbeePacket;
+use crate::table::{Table, TableFull};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Membership {
+    group_id: u16,
+    endpoint: u8,
+}
+
+/// Groups the endpoints of this device are members of.
+#[derive(Debug, Clone, Default)]
+pub struct GroupTable<const N: usize> {
+    memberships: Table<Membership, N>,
+}
+impl<const N: usize> GroupTable<N> {
+    pub const fn new() -> Self {
+        Self {
+            memberships: Table::new(),
+        }
+    }
+
+    /// Add `endpoint` to a group, doing nothing if it already is a member.
+    pub fn add(&mut self, group_id: u16, endpoint: u8) -> Result<(), TableFull> {
+        let membership = Membership { group_id, endpoint };
+        self.memberships
+            .upsert(membership, |existing| *existing == membership)
+    }
+
+    /// Remove `endpoint` from a group, returning whether it was a member.
+    pub fn remove(&mut self, group_id: u16, endpoint: u8) -> bool {
+        let membership = Membership { group_id, endpoint };
+        self.memberships
+            .remove_where(|existing| *existing == membership)
+            > 0
+    }
+
+    /// Remove `endpoint` from all groups, returning how many it was a
+    /// member of.
+    pub fn remove_all(&mut self, endpoint: u8) -> usize {
+        self.memberships
+            .remove_where(|membership| membership.endpoint == endpoint)
+    }
+
+    pub fn is_member(&self, group_id: u16) -> bool {
+        self.memberships
+            .find(|membership| membership.group_id == group_id)
+            .is_some()
+    }
+
+    /// Endpoints of this device that are members of `group_id`.
+    pub fn endpoints(&self, group_id: u16) -> impl Iterator<Item = u8> + '_ {
+        self.memberships
+            .iter()
+            .filter(move |membership| membership.group_id == group_id)
+            .map(|membership| membership.endpoint)
+    }
+
+    /// Whether a received NWK frame should be passed up to the APS layer.
+    /// Multicast frames are only accepted when this device is a member of
+    /// the group they were sent to.
+    pub fn accepts(&self, packet: &ZigbeePacket) -> bool {
+        match packet.frame_control_field.multicast_present {
+            true => self.is_member(packet.destination),
+            false => true,
+        }
+    }
+
+    /// The endpoints a group addressed APS frame is delivered to. Frames
+    /// for groups without members on this device have none, and should be
+    /// dropped. Frames that aren't group addressed have none either.
+    pub fn member_endpoints(&self, frame: &ApsFrame) -> impl Iterator<Item = u8> + '_ {
+        let group_id = match frame.frame_control_field.delivery_mode {
+            DeliveryMode::Group => frame.group_address,
+            _ => None,
+        };
+        self.memberships
+            .iter()
+            .filter(move |membership| Some(membership.group_id) == group_id)
+            .map(|membership| membership.endpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_memberships() {
+        let mut table = GroupTable::<3>::new();
+
+        table.add(0x0001, 1).unwrap();
+        table.add(0x0001, 1).unwrap();
+        table.add(0x0001, 2).unwrap();
+        table.add(0x0002, 2).unwrap();
+        assert_eq!(table.add(0x0003, 1), Err(TableFull));
+
+        assert_eq!(table.endpoints(0x0001).collect::<Vec<_>>(), [1, 2]);
+        assert!(table.remove(0x0001, 1));
+        assert!(!table.remove(0x0001, 1));
+        assert_eq!(table.remove_all(2), 2);
+        assert!(!table.is_member(0x0001));
+    }
+
+    #[test]
+    fn filters_group_frames() {
+        let mut table = GroupTable::<2>::new();
+        table.add(0x0001, 1).unwrap();
+        table.add(0x0001, 3).unwrap();
+
+        let member = ZigbeePacket::try_parse_from(b"\x08\x01\x01\x00\x34\x12\x1e\x05\xed").unwrap();
+        assert!(table.accepts(&member));
+        let other = ZigbeePacket::try_parse_from(b"\x08\x01\x02\x00\x34\x12\x1e\x05\xed").unwrap();
+        assert!(!table.accepts(&other));
+
+        let frame =
+            ApsFrame::try_parse_from(b"\x0c\x01\x00\x06\x00\x04\x01\x01\x22\x01\x05\x02").unwrap();
+        assert_eq!(table.member_endpoints(&frame).collect::<Vec<_>>(), [1, 3]);
+
+        let frame =
+            ApsFrame::try_parse_from(b"\x0c\x02\x00\x06\x00\x04\x01\x01\x22\x01\x05\x02").unwrap();
+        assert_eq!(table.member_endpoints(&frame).count(), 0);
+
+        let unicast =
+            ApsFrame::try_parse_from(b"\x00\x01\x06\x00\x04\x01\x01\x22\x01\x05\x02").unwrap();
+        assert_eq!(table.member_endpoints(&unicast).count(), 0);
+    }
+}
@@ -0,0 +1,632 @@
+use self::commands::ApsCommand;
+use crate::network_layer::security::{SecurityHeader, DEFAULT_SECURITY_LEVEL};
+use crate::network_layer::{ParseError, WriteError};
+use byte::{BytesExt, LE};
+
+pub mod acknowledgement;
+pub mod binding;
+pub mod commands;
+pub mod fragmentation;
+pub mod group;
+pub mod security;
+
+#[derive(Debug, Clone)]
+pub struct ApsFrame<'a> {
+    pub frame_control_field: FrameControlField,
+    /// Present for unicast and broadcast data frames and their
+    /// acknowledgements.
+    pub destination_endpoint: Option<u8>,
+    /// Present when the delivery mode is `DeliveryMode::Group`.
+    pub group_address: Option<u16>,
+    /// Present for data, inter-PAN and data acknowledgement frames.
+    pub cluster_id: Option<u16>,
+    /// Present for data, inter-PAN and data acknowledgement frames.
+    pub profile_id: Option<u16>,
+    /// Present for data frames and data acknowledgements.
+    pub source_endpoint: Option<u8>,
+    /// Present in all frames except inter-PAN ones.
+    pub counter: Option<u8>,
+    pub extended_header: Option<ExtendedHeader>,
+    pub security_header: Option<SecurityHeader<'a>>,
+    pub payload: &'a [u8],
+}
+impl<'a> ApsFrame<'a> {
+    pub fn try_parse_from(frame: &'a [u8]) -> Result<Self, ParseError> {
+        Self::try_parse_with_security_level(frame, DEFAULT_SECURITY_LEVEL)
+    }
+
+    /// Parse an APS frame, using `security_level` for secured frames that
+    /// were sent with a security level of 0, as is done over the air.
+    pub fn try_parse_with_security_level(
+        frame: &'a [u8],
+        security_level: u8,
+    ) -> Result<Self, ParseError> {
+        let offset = &mut 0;
+
+        let fcf = frame.read_with::<u8>(offset, LE)?;
+        let fcf = FrameControlField::from(fcf);
+
+        let destination_endpoint = match fcf.destination_endpoint_present() {
+            true => Some(frame.read_with::<u8>(offset, LE)?),
+            false => None,
+        };
+        let group_address = match fcf.group_address_present() {
+            true => Some(frame.read_with::<u16>(offset, LE)?),
+            false => None,
+        };
+        let (cluster_id, profile_id) = match fcf.cluster_and_profile_present() {
+            true => (
+                Some(frame.read_with::<u16>(offset, LE)?),
+                Some(frame.read_with::<u16>(offset, LE)?),
+            ),
+            false => (None, None),
+        };
+        let source_endpoint = match fcf.source_endpoint_present() {
+            true => Some(frame.read_with::<u8>(offset, LE)?),
+            false => None,
+        };
+        let counter = match fcf.frame_type {
+            FrameType::InterPAN => None,
+            _ => Some(frame.read_with::<u8>(offset, LE)?),
+        };
+
+        let extended_header = match fcf.extended_header_present {
+            true => Some(ExtendedHeader::try_parse_from(
+                frame,
+                offset,
+                fcf.frame_type == FrameType::Acknowledgement,
+            )?),
+            false => None,
+        };
+
+        let security_header = match fcf.security {
+            true => Some(SecurityHeader::try_parse_with_security_level(
+                frame,
+                offset,
+                security_level,
+            )?),
+            false => None,
+        };
+
+        // If there is a MIC, don't include it in the payload.
+        let end_index = match security_header {
+            Some(ref header) => frame.len() - header.message_integrity_code.len(),
+            None => frame.len(),
+        };
+
+        let payload = &frame[*offset..end_index];
+
+        Ok(ApsFrame {
+            frame_control_field: fcf,
+            destination_endpoint,
+            group_address,
+            cluster_id,
+            profile_id,
+            source_endpoint,
+            counter,
+            extended_header,
+            security_header,
+            payload,
+        })
+    }
+
+    /// Serialize the frame into `buffer`, returning the number of bytes
+    /// written.
+    ///
+    /// The payload is written as-is, so for secured frames it should already
+    /// be encrypted. The message integrity code from the security header is
+    /// appended after the payload.
+    pub fn write_into(&self, buffer: &mut [u8]) -> Result<usize, WriteError> {
+        let offset = &mut 0;
+        let fcf = &self.frame_control_field;
+
+        buffer.write_with::<u8>(offset, u8::from(fcf), LE)?;
+
+        if fcf.destination_endpoint_present() {
+            let destination_endpoint = self.destination_endpoint.ok_or(WriteError)?;
+            buffer.write_with::<u8>(offset, destination_endpoint, LE)?;
+        }
+        if fcf.group_address_present() {
+            let group_address = self.group_address.ok_or(WriteError)?;
+            buffer.write_with::<u16>(offset, group_address, LE)?;
+        }
+        if fcf.cluster_and_profile_present() {
+            let cluster_id = self.cluster_id.ok_or(WriteError)?;
+            let profile_id = self.profile_id.ok_or(WriteError)?;
+            buffer.write_with::<u16>(offset, cluster_id, LE)?;
+            buffer.write_with::<u16>(offset, profile_id, LE)?;
+        }
+        if fcf.source_endpoint_present() {
+            let source_endpoint = self.source_endpoint.ok_or(WriteError)?;
+            buffer.write_with::<u8>(offset, source_endpoint, LE)?;
+        }
+        if fcf.frame_type != FrameType::InterPAN {
+            let counter = self.counter.ok_or(WriteError)?;
+            buffer.write_with::<u8>(offset, counter, LE)?;
+        }
+
+        if fcf.extended_header_present {
+            let extended_header = self.extended_header.as_ref().ok_or(WriteError)?;
+            extended_header.write_into(
+                buffer,
+                offset,
+                fcf.frame_type == FrameType::Acknowledgement,
+            )?;
+        }
+
+        let security_header = match fcf.security {
+            true => Some(self.security_header.as_ref().ok_or(WriteError)?),
+            false => None,
+        };
+        if let Some(header) = security_header {
+            header.write_into(buffer, offset)?;
+        }
+
+        buffer.write(offset, self.payload)?;
+
+        if let Some(header) = security_header {
+            buffer.write(offset, header.message_integrity_code)?;
+        }
+
+        Ok(*offset)
+    }
+
+    /// Parse the payload of a command frame. The payload of secured frames
+    /// needs to be decrypted first, see `security::decrypt_frame`.
+    pub fn command(&self) -> Result<ApsCommand, ParseError> {
+        if self.frame_control_field.frame_type != FrameType::Command {
+            return Err(ParseError);
+        }
+        ApsCommand::try_parse_from(self.payload)
+    }
+
+    /// The acknowledgement to send back for a frame that requests one, or
+    /// `None` if it doesn't. Endpoints are swapped, so the acknowledgement
+    /// of a data frame goes to its source endpoint.
+    ///
+    /// Only unicast frames are acknowledged, group and broadcast frames get
+    /// `None` even if they request an acknowledgement. Fragmented frames
+    /// are acknowledged per window, not per frame, so they get `None` as
+    /// well.
+    pub fn acknowledgement(&self) -> Option<ApsFrame<'static>> {
+        let fcf = &self.frame_control_field;
+        let fragmented = self
+            .extended_header
+            .as_ref()
+            .is_some_and(|header| header.fragmentation != Fragmentation::NotFragmented);
+        if !fcf.ack_request || fcf.delivery_mode != DeliveryMode::Unicast || fragmented {
+            return None;
+        }
+
+        let ack_format = match fcf.frame_type {
+            FrameType::Data => false,
+            FrameType::Command => true,
+            _ => return None,
+        };
+        let frame_control_field = FrameControlField {
+            frame_type: FrameType::Acknowledgement,
+            delivery_mode: DeliveryMode::Unicast,
+            ack_format,
+            security: false,
+            ack_request: false,
+            extended_header_present: false,
+        };
+
+        Some(ApsFrame {
+            destination_endpoint: match ack_format {
+                true => None,
+                false => self.source_endpoint,
+            },
+            group_address: None,
+            cluster_id: self.cluster_id,
+            profile_id: self.profile_id,
+            source_endpoint: match ack_format {
+                true => None,
+                false => self.destination_endpoint,
+            },
+            counter: self.counter,
+            extended_header: None,
+            security_header: None,
+            payload: &[],
+            frame_control_field,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameControlField {
+    pub frame_type: FrameType,
+    pub delivery_mode: DeliveryMode,
+    /// Set on acknowledgements of command frames, which don't carry the
+    /// endpoint, cluster and profile fields.
+    pub ack_format: bool,
+    pub security: bool,
+    pub ack_request: bool,
+    pub extended_header_present: bool,
+}
+impl FrameControlField {
+    fn is_data_or_data_ack(&self) -> bool {
+        match self.frame_type {
+            FrameType::Data => true,
+            FrameType::Acknowledgement => !self.ack_format,
+            _ => false,
+        }
+    }
+
+    pub fn destination_endpoint_present(&self) -> bool {
+        self.is_data_or_data_ack()
+            && matches!(
+                self.delivery_mode,
+                DeliveryMode::Unicast | DeliveryMode::Broadcast
+            )
+    }
+
+    pub fn group_address_present(&self) -> bool {
+        self.delivery_mode == DeliveryMode::Group
+            && matches!(self.frame_type, FrameType::Data | FrameType::InterPAN)
+    }
+
+    pub fn cluster_and_profile_present(&self) -> bool {
+        self.is_data_or_data_ack() || self.frame_type == FrameType::InterPAN
+    }
+
+    pub fn source_endpoint_present(&self) -> bool {
+        self.is_data_or_data_ack()
+    }
+}
+impl From<u8> for FrameControlField {
+    fn from(field: u8) -> Self {
+        // Should never panic, all possible values covered by FrameType.
+        let frame_type = FrameType::try_from(field & 0b11).unwrap();
+        // Should never panic, all possible values covered by DeliveryMode.
+        let delivery_mode = DeliveryMode::try_from((field >> 2) & 0b11).unwrap();
+        let ack_format = ((field >> 4) & 1) == 1;
+        let security = ((field >> 5) & 1) == 1;
+        let ack_request = ((field >> 6) & 1) == 1;
+        let extended_header_present = ((field >> 7) & 1) == 1;
+
+        Self {
+            frame_type,
+            delivery_mode,
+            ack_format,
+            security,
+            ack_request,
+            extended_header_present,
+        }
+    }
+}
+impl From<&FrameControlField> for u8 {
+    fn from(fcf: &FrameControlField) -> Self {
+        let frame_type: u8 = match fcf.frame_type {
+            FrameType::Data => 0b00,
+            FrameType::Command => 0b01,
+            FrameType::Acknowledgement => 0b10,
+            FrameType::InterPAN => 0b11,
+        };
+        let delivery_mode: u8 = match fcf.delivery_mode {
+            DeliveryMode::Unicast => 0b00,
+            DeliveryMode::Indirect => 0b01,
+            DeliveryMode::Broadcast => 0b10,
+            DeliveryMode::Group => 0b11,
+        };
+
+        frame_type
+            | (delivery_mode << 2)
+            | ((fcf.ack_format as u8) << 4)
+            | ((fcf.security as u8) << 5)
+            | ((fcf.ack_request as u8) << 6)
+            | ((fcf.extended_header_present as u8) << 7)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum FrameType {
+    Data,
+    Command,
+    Acknowledgement,
+    InterPAN,
+}
+impl TryFrom<u8> for FrameType {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(FrameType::Data),
+            0b01 => Ok(FrameType::Command),
+            0b10 => Ok(FrameType::Acknowledgement),
+            0b11 => Ok(FrameType::InterPAN),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DeliveryMode {
+    Unicast,
+    /// Destination is looked up in the binding table. Only used by pre-2007
+    /// stacks over the air, where the destination endpoint is left out.
+    Indirect,
+    Broadcast,
+    Group,
+}
+impl TryFrom<u8> for DeliveryMode {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(DeliveryMode::Unicast),
+            0b01 => Ok(DeliveryMode::Indirect),
+            0b10 => Ok(DeliveryMode::Broadcast),
+            0b11 => Ok(DeliveryMode::Group),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Fragmentation {
+    NotFragmented,
+    FirstFragment,
+    /// Any fragment after the first one.
+    Fragment,
+    Reserved,
+}
+impl TryFrom<u8> for Fragmentation {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(Fragmentation::NotFragmented),
+            0b01 => Ok(Fragmentation::FirstFragment),
+            0b10 => Ok(Fragmentation::Fragment),
+            0b11 => Ok(Fragmentation::Reserved),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Extended header, used for fragmented frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedHeader {
+    pub fragmentation: Fragmentation,
+    /// For the first fragment this is the total number of blocks, otherwise
+    /// it is the number of this block. Present for fragmented frames.
+    pub block_number: Option<u8>,
+    /// Which blocks in the window are being acknowledged. Present for
+    /// acknowledgements of fragmented frames.
+    pub ack_bitfield: Option<u8>,
+}
+impl ExtendedHeader {
+    pub fn try_parse_from(
+        frame: &[u8],
+        offset: &mut usize,
+        is_acknowledgement: bool,
+    ) -> Result<Self, ParseError> {
+        let extended_fcf = frame.read_with::<u8>(offset, LE)?;
+        // Should never panic, all possible values covered by Fragmentation.
+        let fragmentation = Fragmentation::try_from(extended_fcf & 0b11).unwrap();
+
+        let block_number = match fragmentation {
+            Fragmentation::NotFragmented => None,
+            _ => Some(frame.read_with::<u8>(offset, LE)?),
+        };
+        let ack_bitfield = match (block_number, is_acknowledgement) {
+            (Some(_), true) => Some(frame.read_with::<u8>(offset, LE)?),
+            _ => None,
+        };
+
+        Ok(Self {
+            fragmentation,
+            block_number,
+            ack_bitfield,
+        })
+    }
+
+    pub fn write_into(
+        &self,
+        buffer: &mut [u8],
+        offset: &mut usize,
+        is_acknowledgement: bool,
+    ) -> Result<(), WriteError> {
+        let extended_fcf: u8 = match self.fragmentation {
+            Fragmentation::NotFragmented => 0b00,
+            Fragmentation::FirstFragment => 0b01,
+            Fragmentation::Fragment => 0b10,
+            Fragmentation::Reserved => 0b11,
+        };
+        buffer.write_with::<u8>(offset, extended_fcf, LE)?;
+
+        if self.fragmentation != Fragmentation::NotFragmented {
+            let block_number = self.block_number.ok_or(WriteError)?;
+            buffer.write_with::<u8>(offset, block_number, LE)?;
+
+            if is_acknowledgement {
+                let ack_bitfield = self.ack_bitfield.ok_or(WriteError)?;
+                buffer.write_with::<u8>(offset, ack_bitfield, LE)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_layer::security::KeyIdentifier;
+
+    fn assert_round_trips(bytes: &[u8]) -> ApsFrame<'_> {
+        let frame = ApsFrame::try_parse_from(bytes).unwrap();
+
+        let mut buffer = [0u8; 128];
+        let len = frame.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], bytes);
+
+        frame
+    }
+
+    #[test]
+    fn parses_broadcast_data_frame() {
+        // A ZDO device announcement.
+        let frame = assert_round_trips(
+            b"\x08\x00\x13\x00\x00\x00\x00\x81\
+\x81\x34\x12\x9e\xc0\x81\x08\x01\x88\x17\x00\x8e",
+        );
+
+        assert_eq!(frame.frame_control_field.frame_type, FrameType::Data);
+        assert_eq!(
+            frame.frame_control_field.delivery_mode,
+            DeliveryMode::Broadcast
+        );
+        assert_eq!(frame.destination_endpoint, Some(0x00));
+        assert_eq!(frame.group_address, None);
+        assert_eq!(frame.cluster_id, Some(0x0013));
+        assert_eq!(frame.profile_id, Some(0x0000));
+        assert_eq!(frame.source_endpoint, Some(0x00));
+        assert_eq!(frame.counter, Some(0x81));
+        assert_eq!(frame.payload.len(), 12);
+    }
+
+    #[test]
+    fn parses_group_data_frame() {
+        let frame = assert_round_trips(b"\x0c\x01\x00\x06\x00\x04\x01\x01\x22\x01\x05\x02");
+
+        assert_eq!(frame.frame_control_field.delivery_mode, DeliveryMode::Group);
+        assert_eq!(frame.destination_endpoint, None);
+        assert_eq!(frame.group_address, Some(0x0001));
+        assert_eq!(frame.cluster_id, Some(0x0006));
+        assert_eq!(frame.profile_id, Some(0x0104));
+        assert_eq!(frame.source_endpoint, Some(0x01));
+        assert_eq!(frame.counter, Some(0x22));
+        assert_eq!(frame.payload, b"\x01\x05\x02");
+    }
+
+    #[test]
+    fn parses_indirect_data_frame() {
+        let frame = assert_round_trips(b"\x04\x06\x00\x04\x01\x01\x22\x01\x05\x02");
+
+        assert_eq!(
+            frame.frame_control_field.delivery_mode,
+            DeliveryMode::Indirect
+        );
+        assert_eq!(frame.destination_endpoint, None);
+        assert_eq!(frame.source_endpoint, Some(0x01));
+    }
+
+    #[test]
+    fn parses_unicast_data_frame_and_ack() {
+        let frame = assert_round_trips(b"\x40\x01\x06\x00\x04\x01\x01\x22\x01\x05\x02");
+        assert!(frame.frame_control_field.ack_request);
+        assert_eq!(frame.destination_endpoint, Some(0x01));
+
+        let ack = assert_round_trips(b"\x02\x01\x06\x00\x04\x01\x01\x22");
+        assert_eq!(
+            ack.frame_control_field.frame_type,
+            FrameType::Acknowledgement
+        );
+        assert_eq!(ack.destination_endpoint, Some(0x01));
+        assert_eq!(ack.cluster_id, Some(0x0006));
+        assert_eq!(ack.counter, Some(0x22));
+        assert!(ack.payload.is_empty());
+    }
+
+    #[test]
+    fn builds_acknowledgements() {
+        let mut buffer = [0u8; 16];
+
+        let frame =
+            ApsFrame::try_parse_from(b"\x40\x0b\x06\x00\x04\x01\x01\x22\x01\x05\x02").unwrap();
+        let ack = frame.acknowledgement().unwrap();
+        let len = ack.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"\x02\x01\x06\x00\x04\x01\x0b\x22");
+
+        let command = ApsFrame::try_parse_from(b"\x41\x42\x08").unwrap();
+        let ack = command.acknowledgement().unwrap();
+        let len = ack.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"\x12\x42");
+
+        let unacknowledged = ApsFrame::try_parse_from(b"\x00\x0b\x06\x00\x04\x01\x01\x22").unwrap();
+        assert!(unacknowledged.acknowledgement().is_none());
+
+        // Requesting an acknowledgement, but sent to a group or broadcast.
+        let group =
+            ApsFrame::try_parse_from(b"\x4c\x01\x00\x06\x00\x04\x01\x01\x22\x01\x05\x02").unwrap();
+        assert_eq!(group.group_address, Some(0x0001));
+        assert!(group.acknowledgement().is_none());
+        let broadcast = ApsFrame::try_parse_from(b"\x48\x0b\x06\x00\x04\x01\x01\x22").unwrap();
+        assert!(broadcast.acknowledgement().is_none());
+    }
+
+    #[test]
+    fn parses_command_ack() {
+        let ack = assert_round_trips(b"\x12\x42");
+
+        assert!(ack.frame_control_field.ack_format);
+        assert_eq!(ack.destination_endpoint, None);
+        assert_eq!(ack.cluster_id, None);
+        assert_eq!(ack.source_endpoint, None);
+        assert_eq!(ack.counter, Some(0x42));
+    }
+
+    #[test]
+    fn parses_fragmented_frame_and_ack() {
+        let frame = assert_round_trips(b"\xc0\x01\x19\x00\x04\x01\x01\x22\x01\x04\xaa\xbb");
+        assert_eq!(
+            frame.extended_header,
+            Some(ExtendedHeader {
+                fragmentation: Fragmentation::FirstFragment,
+                block_number: Some(4),
+                ack_bitfield: None,
+            })
+        );
+        assert_eq!(frame.payload, b"\xaa\xbb");
+
+        let ack = assert_round_trips(b"\x82\x01\x19\x00\x04\x01\x01\x22\x02\x02\x07");
+        assert_eq!(
+            ack.extended_header,
+            Some(ExtendedHeader {
+                fragmentation: Fragmentation::Fragment,
+                block_number: Some(2),
+                ack_bitfield: Some(0x07),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_inter_pan_frame() {
+        let frame = assert_round_trips(b"\x0b\x00\x10\x5e\xc0\x01\x02");
+
+        assert_eq!(frame.frame_control_field.frame_type, FrameType::InterPAN);
+        assert_eq!(frame.cluster_id, Some(0x1000));
+        assert_eq!(frame.profile_id, Some(0xc05e));
+        assert_eq!(frame.counter, None);
+        assert_eq!(frame.payload, b"\x01\x02");
+    }
+
+    #[test]
+    fn parses_secured_command_frame() {
+        let frame = assert_round_trips(
+            b"\x21\x10\x30\x02\x00\x00\x00\x9e\xc0\x81\x08\x01\x88\x17\x00\
+\x05\x01\x02\x03\xaa\xbb\xcc\xdd",
+        );
+
+        assert_eq!(frame.frame_control_field.frame_type, FrameType::Command);
+        assert_eq!(frame.counter, Some(0x10));
+
+        let header = frame.security_header.unwrap();
+        assert_eq!(
+            header.security_control_field.key_identifier,
+            KeyIdentifier::KeyTransport
+        );
+        assert_eq!(header.frame_counter, 2);
+        assert_eq!(header.message_integrity_code, [0xaa, 0xbb, 0xcc, 0xdd]);
+        assert_eq!(frame.payload, b"\x05\x01\x02\x03");
+    }
+
+    #[test]
+    fn rejects_truncated_frames() {
+        assert!(ApsFrame::try_parse_from(b"").is_err());
+        assert!(ApsFrame::try_parse_from(b"\x40\x01\x06\x00\x04").is_err());
+        assert!(ApsFrame::try_parse_from(b"\xc0\x01\x19\x00\x04\x01\x01\x22\x01").is_err());
+    }
+}
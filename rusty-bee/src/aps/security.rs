@@ -0,0 +1,310 @@
+use super::{ApsFrame, FrameControlField};
+use crate::network_layer::security::{
+    build_nonce, ccm_star_decrypt, ccm_star_encrypt, mmo_hash, KeyIdentifier, MessageIntegritySize,
+    SecurityContext, SecurityControlField, SecurityError, SecurityHeader, SecurityKey,
+};
+
+/// Default trust center link key, used by devices joining without an
+/// install code.
+pub const ZIGBEE_ALLIANCE_09_KEY: SecurityKey = *b"ZigBeeAlliance09";
+
+/// HMAC built on the MMO hash, used to derive keys from a link key.
+pub fn keyed_hash(key: &SecurityKey, input: u8) -> SecurityKey {
+    // The key is as long as a hash block, so it is used as-is.
+    let mut inner = [0u8; 16 + 1];
+    for (byte, key_byte) in inner.iter_mut().zip(key) {
+        *byte = key_byte ^ 0x36;
+    }
+    inner[16] = input;
+    let inner_hash = mmo_hash(&inner);
+
+    let mut outer = [0u8; 16 + 16];
+    for (byte, key_byte) in outer.iter_mut().zip(key) {
+        *byte = key_byte ^ 0x5c;
+    }
+    outer[16..].copy_from_slice(&inner_hash);
+    mmo_hash(&outer)
+}
+
+/// The key that secures APS frames with `key_identifier`. Transport-Key
+/// commands are secured with keys derived from the link key rather than
+/// the link key itself.
+pub fn frame_key(link_key: &SecurityKey, key_identifier: &KeyIdentifier) -> SecurityKey {
+    match key_identifier {
+        KeyIdentifier::Data | KeyIdentifier::Network => *link_key,
+        KeyIdentifier::KeyTransport => keyed_hash(link_key, 0x00),
+        KeyIdentifier::KeyLoad => keyed_hash(link_key, 0x02),
+    }
+}
+
+/// Secure `frame` with a key derived from `link_key` for `key_identifier`
+/// and write it into `buffer`, returning the number of bytes written.
+///
+/// The frame's payload is the plaintext, any security header on it is
+/// replaced with one using the next outgoing frame counter of `context`,
+/// which should be the one kept for `link_key`.
+pub fn encrypt_frame(
+    frame: &ApsFrame,
+    key_identifier: KeyIdentifier,
+    link_key: &SecurityKey,
+    context: &mut SecurityContext,
+    buffer: &mut [u8],
+) -> Result<usize, SecurityError> {
+    // A frame counter of 0xffffffff must never be sent.
+    if context.outgoing_frame_counter == u32::MAX {
+        return Err(SecurityError::FrameCounterExhausted);
+    }
+
+    let key = frame_key(link_key, &key_identifier);
+    let mut security_control_field = SecurityControlField {
+        using_encryption: false,
+        message_integrity_size: MessageIntegritySize::None,
+        key_identifier,
+        using_extended_nonce: true,
+    };
+    security_control_field.set_security_level(context.security_level);
+    let security_level = security_control_field.security_level();
+    let mic_length = security_control_field.message_integrity_size.num_bytes();
+
+    let security_header = SecurityHeader {
+        security_control_field,
+        frame_counter: context.outgoing_frame_counter,
+        extended_source: Some(context.extended_source),
+        key_message_number: 0,
+        // Placeholder until the frame is encrypted.
+        message_integrity_code: &[0; 16][..mic_length],
+    };
+    let auxiliary_header_length = security_header.header_length();
+
+    let secured = ApsFrame {
+        frame_control_field: FrameControlField {
+            security: true,
+            ..frame.frame_control_field.clone()
+        },
+        destination_endpoint: frame.destination_endpoint,
+        group_address: frame.group_address,
+        cluster_id: frame.cluster_id,
+        profile_id: frame.profile_id,
+        source_endpoint: frame.source_endpoint,
+        counter: frame.counter,
+        extended_header: frame.extended_header.clone(),
+        security_header: Some(security_header),
+        payload: frame.payload,
+    };
+    let length = secured.write_into(buffer)?;
+
+    let header_length = length - mic_length - frame.payload.len();
+    let control_offset = header_length - auxiliary_header_length;
+
+    let sent_control = buffer[control_offset];
+    buffer[control_offset] |= security_level;
+    let nonce = build_nonce(
+        context.extended_source,
+        context.outgoing_frame_counter,
+        buffer[control_offset],
+    );
+
+    let (data, message_integrity_code) = buffer[..length].split_at_mut(length - mic_length);
+    ccm_star_encrypt(
+        &key,
+        &nonce,
+        security_level,
+        data,
+        header_length,
+        message_integrity_code,
+    )?;
+
+    // The security level is never sent over the air.
+    buffer[control_offset] = sent_control;
+    context.outgoing_frame_counter += 1;
+
+    Ok(length)
+}
+
+/// Decrypt and authenticate a secured APS frame in place, with the key
+/// derived from `link_key` for the key identifier in its security header.
+///
+/// Frames without the sender's address in their security header need
+/// `extended_source`, e.g. taken from the NWK header. On success the
+/// returned frame's `payload` is the plaintext.
+pub fn decrypt_frame<'a>(
+    frame: &'a mut [u8],
+    link_key: &SecurityKey,
+    security_level: u8,
+    extended_source: Option<u64>,
+) -> Result<ApsFrame<'a>, SecurityError> {
+    let (header_length, payload_length, control_offset, security_level, source, frame_counter, key) = {
+        let parsed = ApsFrame::try_parse_with_security_level(frame, security_level)?;
+        let header = parsed
+            .security_header
+            .as_ref()
+            .ok_or(SecurityError::MalformedFrame)?;
+
+        let mic_length = header.message_integrity_code.len();
+        let payload_length = parsed.payload.len();
+        let header_length = frame.len() - mic_length - payload_length;
+        let control_offset = header_length - header.header_length();
+
+        let source = header
+            .extended_source
+            .or(extended_source)
+            .ok_or(SecurityError::MalformedFrame)?;
+
+        (
+            header_length,
+            payload_length,
+            control_offset,
+            header.security_control_field.security_level(),
+            source,
+            header.frame_counter,
+            frame_key(link_key, &header.security_control_field.key_identifier),
+        )
+    };
+
+    let received_control = frame[control_offset];
+    frame[control_offset] = (received_control & !0b111) | security_level;
+    let nonce = build_nonce(source, frame_counter, frame[control_offset]);
+
+    let (authenticated, message_integrity_code) =
+        frame.split_at_mut(header_length + payload_length);
+    let result = ccm_star_decrypt(
+        &key,
+        &nonce,
+        security_level,
+        authenticated,
+        header_length,
+        message_integrity_code,
+    );
+
+    // Put the frame back the way it was received.
+    frame[control_offset] = received_control;
+    result?;
+
+    let frame: &'a [u8] = frame;
+    Ok(ApsFrame::try_parse_with_security_level(
+        frame,
+        security_level,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aps::commands::{ApsCommand, KeyType};
+    use crate::network_layer::security::DEFAULT_SECURITY_LEVEL;
+
+    const TRUST_CENTER_ADDRESS: u64 = 0x00_12_4b_00_01_02_03_04;
+
+    // Transport-Key command carrying a standard network key.
+    const TRANSPORT_KEY: &[u8] = b"\x01\x2a\x05\x01\
+\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xaa\xbb\xcc\xdd\xee\xff\x00\
+\x9e\xc0\x81\x08\x01\x88\x17\x00\x04\x03\x02\x01\x00\x4b\x12\x00";
+
+    #[test]
+    fn computes_keyed_hash() {
+        let key = *b"\x40\x41\x42\x43\x44\x45\x46\x47\x48\x49\x4a\x4b\x4c\x4d\x4e\x4f";
+
+        assert_eq!(
+            keyed_hash(&key, 0xc0),
+            *b"\x45\x12\x80\x7b\xf9\x4c\xb3\x40\x0f\x0e\x2c\x25\xfb\x76\xe9\x99"
+        );
+    }
+
+    #[test]
+    fn derives_transport_keys_from_link_key() {
+        let key = ZIGBEE_ALLIANCE_09_KEY;
+
+        assert_eq!(frame_key(&key, &KeyIdentifier::Data), key);
+        assert_eq!(
+            frame_key(&key, &KeyIdentifier::KeyTransport),
+            keyed_hash(&key, 0x00)
+        );
+        assert_ne!(
+            frame_key(&key, &KeyIdentifier::KeyTransport),
+            frame_key(&key, &KeyIdentifier::KeyLoad)
+        );
+    }
+
+    #[test]
+    fn decrypts_transport_key_command() {
+        let command = ApsFrame::try_parse_from(TRANSPORT_KEY).unwrap();
+        let mut context = SecurityContext::new(TRUST_CENTER_ADDRESS);
+        let mut buffer = [0u8; 64];
+        let length = encrypt_frame(
+            &command,
+            KeyIdentifier::KeyTransport,
+            &ZIGBEE_ALLIANCE_09_KEY,
+            &mut context,
+            &mut buffer,
+        )
+        .unwrap();
+        assert_eq!(context.outgoing_frame_counter, 1);
+        // Frame control with the security bit, counter, then the auxiliary
+        // header with the key identifier and the extended nonce.
+        assert_eq!(&buffer[..5], b"\x21\x2a\x30\x00\x00");
+        assert_ne!(&buffer[15..length - 4], &TRANSPORT_KEY[2..]);
+
+        let mut wrong_key = ZIGBEE_ALLIANCE_09_KEY;
+        wrong_key[0] ^= 0xff;
+        let mut frame = buffer;
+        assert_eq!(
+            decrypt_frame(
+                &mut frame[..length],
+                &wrong_key,
+                DEFAULT_SECURITY_LEVEL,
+                None
+            )
+            .unwrap_err(),
+            SecurityError::AuthenticationFailed
+        );
+
+        let decrypted = decrypt_frame(
+            &mut buffer[..length],
+            &ZIGBEE_ALLIANCE_09_KEY,
+            DEFAULT_SECURITY_LEVEL,
+            None,
+        )
+        .unwrap();
+        let header = decrypted.security_header.as_ref().unwrap();
+        assert_eq!(
+            header.security_control_field.key_identifier,
+            KeyIdentifier::KeyTransport
+        );
+        assert_eq!(header.extended_source, Some(TRUST_CENTER_ADDRESS));
+        assert_eq!(decrypted.payload, &TRANSPORT_KEY[2..]);
+        match decrypted.command().unwrap() {
+            ApsCommand::TransportKey(transport_key) => {
+                assert_eq!(transport_key.key_type(), KeyType::StandardNetworkKey);
+                assert_eq!(transport_key.key(), &TRANSPORT_KEY[4..20]);
+            }
+            command => panic!("unexpected command {command:?}"),
+        }
+    }
+
+    #[test]
+    fn encrypts_data_with_link_key() {
+        let link_key = *b"\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10";
+        let frame =
+            ApsFrame::try_parse_from(b"\x40\x0b\x06\x00\x04\x01\x01\x22\x01\x05\x02").unwrap();
+        let mut context = SecurityContext::new(TRUST_CENTER_ADDRESS);
+        let mut buffer = [0u8; 64];
+        let length = encrypt_frame(
+            &frame,
+            KeyIdentifier::Data,
+            &link_key,
+            &mut context,
+            &mut buffer,
+        )
+        .unwrap();
+
+        let decrypted = decrypt_frame(
+            &mut buffer[..length],
+            &link_key,
+            DEFAULT_SECURITY_LEVEL,
+            None,
+        )
+        .unwrap();
+        assert_eq!(decrypted.destination_endpoint, Some(0x0b));
+        assert_eq!(decrypted.payload, b"\x01\x05\x02");
+    }
+}
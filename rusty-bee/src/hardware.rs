@@ -0,0 +1,113 @@
+/// Details of a received frame, measured by the radio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReceiveInfo {
+    /// Length of the frame, without its frame check sequence.
+    pub length: usize,
+    /// Received signal strength in dBm.
+    pub rssi: i8,
+    /// Link quality indicator, from 0 for the worst links to 255 for the
+    /// best ones.
+    pub lqi: u8,
+    /// Value of `Clock::now_us` when the frame was received.
+    pub timestamp_us: u32,
+}
+
+/// Lowest IEEE 802.15.4 channel in the 2.4 GHz band.
+pub const MIN_CHANNEL: u8 = 11;
+/// Highest IEEE 802.15.4 channel in the 2.4 GHz band.
+pub const MAX_CHANNEL: u8 = 26;
+
+/// An IEEE 802.15.4 radio in the 2.4 GHz band.
+pub trait Radio {
+    /// Send a raw IEEE 802.15.4 frame, without the frame check sequence
+    /// which the radio appends. Returns once the frame was sent.
+    fn transmit(&self, frame: &[u8]);
+
+    /// Copy the next received IEEE 802.15.4 frame into `buffer` without its
+    /// frame check sequence, or return `None` if no frame was received.
+    /// Frames with an invalid frame check sequence are dropped. Must not
+    /// block.
+    fn receive(&self, buffer: &mut [u8]) -> Option<ReceiveInfo>;
+
+    /// Switch to `channel`, from `MIN_CHANNEL` to `MAX_CHANNEL`, returning
+    /// false for channels the radio doesn't support.
+    fn set_channel(&self, channel: u8) -> bool;
+
+    /// Set the transmit power to the closest supported value at or below
+    /// `dbm`.
+    fn set_tx_power(&self, dbm: i8);
+
+    /// Clear channel assessment, whether the current channel is idle and a
+    /// frame can be sent.
+    fn clear_channel_assessment(&self) -> bool;
+
+    /// Energy measured on the current channel, from 0 for the lowest level
+    /// the radio can detect to 255 for the highest.
+    fn energy_detect(&self) -> u8;
+}
+
+/// A monotonic clock.
+pub trait Clock {
+    /// Milliseconds elapsed since some fixed point in time, wrapping around
+    /// on overflow. Timeouts in the stack are measured with this clock.
+    fn now_ms(&self) -> u32;
+
+    /// Microseconds elapsed since some fixed point in time, wrapping around
+    /// on overflow. Used for the short timeouts of the MAC layer.
+    fn now_us(&self) -> u32;
+
+    /// Wait for `duration` microseconds. Platforms with a timer that can
+    /// wake them up should override this to sleep instead.
+    fn delay_us(&self, duration: u32) {
+        let start = self.now_us();
+        while self.now_us().wrapping_sub(start) < duration {}
+    }
+
+    /// Arm the alarm to go off when `now_us` reaches `at`, which must be
+    /// less than half a wraparound of `now_us` away, about 35 minutes.
+    /// Replaces the alarm armed before, there is only one.
+    fn set_alarm_us(&self, at: u32);
+
+    /// Whether the alarm went off. Stays true until the alarm is armed
+    /// again or cancelled, and is false while no alarm is armed.
+    fn alarm_expired(&self) -> bool;
+
+    fn cancel_alarm(&self);
+}
+
+/// A source of random numbers, e.g. for sequence numbers and backoffs.
+pub trait Random {
+    fn random_u32(&self) -> u32;
+}
+
+/// Values kept across reboots by `Storage`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageKey {
+    /// The outgoing NWK frame counter, see
+    /// `network_layer::frame_counter::OutgoingFrameCounter`.
+    OutgoingFrameCounter,
+    /// PAN, short address and channel of the network this device joined.
+    NetworkParameters,
+    /// The active network key and its sequence number.
+    NetworkKey,
+    /// The link key shared with the trust center.
+    TrustCenterLinkKey,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StorageError {
+    /// The value is too long to be stored.
+    TooLong,
+    /// The underlying storage failed, e.g. a flash write error.
+    Failed,
+}
+
+/// Persistent storage for small values.
+pub trait Storage {
+    /// Copy the value stored for `key` into `buffer`, returning its length,
+    /// or `None` if nothing is stored for it.
+    fn read(&self, key: StorageKey, buffer: &mut [u8]) -> Option<usize>;
+
+    /// Store `value` for `key`, replacing any previous value.
+    fn write(&self, key: StorageKey, value: &[u8]) -> Result<(), StorageError>;
+}
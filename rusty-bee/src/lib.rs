@@ -1,33 +1,812 @@
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "simulation")), no_std)]
 
+use aps::acknowledgement::{DuplicateRejectionTable, Retransmission, RetransmissionQueue};
+use aps::binding::{BindingDestination, BindingTable};
+use aps::commands::ApsCommand;
+use aps::{ApsFrame, DeliveryMode, FrameControlField as ApsFrameControlField};
+use hardware::{Clock, Radio, Random, ReceiveInfo, Storage, StorageError, StorageKey};
+use ieee802154::mac::{Address, FrameContent, PanId, ShortAddress};
+use mac::{Mac, MacError, MAX_FRAME_LENGTH};
+use network_layer::address_map::AddressMap;
+use network_layer::commands::{Leave, NwkCommand};
+use network_layer::frame_counter::{IncomingFrameCounters, OutgoingFrameCounter};
+use network_layer::key_store::KeyStore;
+use network_layer::neighbor_table::{DeviceType, Neighbor, NeighborTable, Relationship};
+use network_layer::routing_table::{Route, RouteStatus, RoutingTable};
+use network_layer::security::{SecurityContext, SecurityError};
+use network_layer::{DiscoverRoute, FrameControlField, FrameType, WriteError, ZigbeePacket};
+use zdo::management::ManagementTables;
+use zdo::{Status, ZdoCommand, ZdoFrame, ZDO_ENDPOINT, ZDO_PROFILE_ID};
+
+pub mod aps;
+pub mod hardware;
+pub mod mac;
 pub mod network_layer;
+#[cfg(any(test, feature = "simulation"))]
+pub mod simulation;
+pub mod table;
+pub mod zcl;
+pub mod zdo;
+
+/// How many link keys the stack keeps.
+pub const LINK_KEY_TABLE_SIZE: usize = 4;
+/// How many devices the stack tracks incoming frame counters of.
+pub const FRAME_COUNTER_TABLE_SIZE: usize = 16;
+/// How many received APS frames are remembered to drop duplicates.
+pub const DUPLICATE_REJECTION_TABLE_SIZE: usize = 8;
+/// How many sent APS frames can wait for an acknowledgement at once.
+pub const RETRANSMISSION_QUEUE_SIZE: usize = 4;
+pub const NEIGHBOR_TABLE_SIZE: usize = 8;
+pub const ROUTING_TABLE_SIZE: usize = 8;
+/// How many network addresses of devices that aren't neighbors are kept.
+pub const ADDRESS_MAP_SIZE: usize = 16;
+
+/// Radius of the NWK frames we send, twice the default maximum depth of a
+/// network.
+const DEFAULT_RADIUS: u8 = 30;
+/// Room for the table entries of a management response: a frame less the
+/// MAC, NWK, auxiliary security, APS and ZDO headers and the message
+/// integrity code.
+const MANAGEMENT_RESPONSE_ENTRIES_LENGTH: usize = MAX_FRAME_LENGTH - 9 - 8 - 14 - 8 - 5 - 4;
+
+/// Why a frame could not be sent.
+#[derive(Debug, PartialEq)]
+pub enum SendError {
+    /// The frame could not be serialized, e.g. because it is too long, or
+    /// no more frames can wait for an acknowledgement.
+    InvalidFrame,
+    /// The device has no short address to send from, it hasn't joined a
+    /// network yet.
+    NotJoined,
+    /// An indirect frame was sent, but nothing is bound to its source
+    /// endpoint and cluster.
+    NoBinding,
+    /// The network address of a bound device isn't known yet, e.g. because
+    /// it hasn't announced itself.
+    UnknownAddress,
+    Security(SecurityError),
+    Mac(MacError),
+    /// The frame was sent, but the outgoing frame counter could not be
+    /// persisted, so no more secured frames should be sent.
+    Storage(StorageError),
+}
+impl From<WriteError> for SendError {
+    fn from(_: WriteError) -> Self {
+        Self::InvalidFrame
+    }
+}
+impl From<SecurityError> for SendError {
+    fn from(error: SecurityError) -> Self {
+        Self::Security(error)
+    }
+}
+impl From<MacError> for SendError {
+    fn from(error: MacError) -> Self {
+        Self::Mac(error)
+    }
+}
+impl From<StorageError> for SendError {
+    fn from(error: StorageError) -> Self {
+        Self::Storage(error)
+    }
+}
 
-/// Initialize the Zigbee stack for specific hardware.
-pub fn initialize_zigbee_stack<T: ZigbeeHardware>(hardware: &T) -> bool {
-    hardware.connect()
+/// A Zigbee stack running on `hardware`.
+pub struct ZigbeeStack<'h, T: ZigbeeHardware> {
+    pub hardware: &'h T,
+    pub mac: Mac,
+    /// Security material for the NWK frames we send.
+    pub security_context: SecurityContext,
+    pub key_store: KeyStore<LINK_KEY_TABLE_SIZE>,
+    /// Frame counters of the secured NWK frames received, to drop replayed
+    /// ones.
+    pub incoming_frame_counters: IncomingFrameCounters<FRAME_COUNTER_TABLE_SIZE>,
+    outgoing_frame_counter: OutgoingFrameCounter,
+    /// Sequence number of the next NWK frame sent.
+    pub nwk_sequence_number: u8,
+    /// APS counter of the next APS frame sent.
+    pub aps_counter: u8,
+    pub duplicate_rejection: DuplicateRejectionTable<DUPLICATE_REJECTION_TABLE_SIZE>,
+    /// APS frames sent that are waiting for an acknowledgement.
+    pub retransmissions: RetransmissionQueue<RETRANSMISSION_QUEUE_SIZE, MAX_FRAME_LENGTH>,
+    /// Devices frames were received from directly.
+    pub neighbors: NeighborTable<NEIGHBOR_TABLE_SIZE>,
+    /// Routes back to devices whose frames were relayed to us.
+    pub routes: RoutingTable<ROUTING_TABLE_SIZE>,
+    /// Network addresses of devices we heard from, to send frames to bound
+    /// devices.
+    pub addresses: AddressMap<ADDRESS_MAP_SIZE>,
+}
+impl<T: ZigbeeHardware> ZigbeeStack<'_, T> {
+    pub fn next_nwk_sequence_number(&mut self) -> u8 {
+        let sequence_number = self.nwk_sequence_number;
+        self.nwk_sequence_number = self.nwk_sequence_number.wrapping_add(1);
+        sequence_number
+    }
+
+    pub fn next_aps_counter(&mut self) -> u8 {
+        let counter = self.aps_counter;
+        self.aps_counter = self.aps_counter.wrapping_add(1);
+        counter
+    }
+
+    /// Send `frame` to `destination` with the next APS counter, see
+    /// `send_data`. If it requests an acknowledgement, it is sent again by
+    /// `poll_retransmissions` until one is received.
+    pub fn send_aps_frame(
+        &mut self,
+        destination: u16,
+        frame: &mut ApsFrame,
+    ) -> Result<(), SendError> {
+        frame.counter = Some(self.next_aps_counter());
+        if frame.frame_control_field.ack_request {
+            self.retransmissions
+                .push(self.hardware, destination, frame)?;
+        }
+
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+        let length = frame.write_into(&mut buffer)?;
+        self.send_data(destination, &buffer[..length])
+    }
+
+    /// Send again the APS frames whose acknowledgement timed out. `failed`
+    /// is called with the destination and APS counter of the frames that
+    /// were never acknowledged. Should be called regularly.
+    pub fn poll_retransmissions(&mut self, mut failed: impl FnMut(u16, u8)) {
+        // Taken out while polling, as frames are sent again through `self`.
+        let mut retransmissions = core::mem::take(&mut self.retransmissions);
+        retransmissions.poll(self.hardware, |retransmission| match retransmission {
+            // Tried again at the next timeout if this fails.
+            Retransmission::Resend { destination, frame } => {
+                let _ = self.send_data(destination, frame);
+            }
+            Retransmission::Failed {
+                destination,
+                counter,
+            } => failed(destination, counter),
+        });
+        self.retransmissions = retransmissions;
+    }
+
+    /// Handle the APS frame in a received NWK data frame: send the
+    /// acknowledgement it requests, drop it if it was received before, and
+    /// match acknowledgements to the frames waiting for them. Returns the
+    /// frame if it needs to be handled further.
+    pub fn handle_aps_frame<'a>(&mut self, packet: &ZigbeePacket<'a>) -> Option<ApsFrame<'a>> {
+        if packet.frame_control_field.frame_type != FrameType::Data {
+            return None;
+        }
+        let frame = ApsFrame::try_parse_with_security_level(
+            packet.payload,
+            self.security_context.security_level,
+        )
+        .ok()?;
+
+        if frame.frame_control_field.frame_type == aps::FrameType::Acknowledgement {
+            self.retransmissions.acknowledge(packet.source, &frame);
+            return None;
+        }
+        // Duplicates are acknowledged as well, as they are usually sent
+        // because the first acknowledgement got lost.
+        if let Some(acknowledgement) = frame.acknowledgement() {
+            let mut buffer = [0u8; MAX_FRAME_LENGTH];
+            if let Ok(length) = acknowledgement.write_into(&mut buffer) {
+                let _ = self.send_data(packet.source, &buffer[..length]);
+            }
+        }
+        if let Some(counter) = frame.counter {
+            if self
+                .duplicate_rejection
+                .is_duplicate(self.hardware, packet.source, counter)
+            {
+                return None;
+            }
+        }
+
+        Some(frame)
+    }
+
+    /// Send `frame` from our ZDO to the one of `destination`.
+    pub fn send_zdo_frame(&mut self, destination: u16, frame: &ZdoFrame) -> Result<(), SendError> {
+        let mut payload = [0u8; MAX_FRAME_LENGTH];
+        let length = frame.write_into(&mut payload)?;
+
+        self.send_aps_frame(
+            destination,
+            &mut ApsFrame {
+                frame_control_field: ApsFrameControlField {
+                    frame_type: aps::FrameType::Data,
+                    delivery_mode: DeliveryMode::Unicast,
+                    ack_format: false,
+                    security: false,
+                    ack_request: false,
+                    extended_header_present: false,
+                },
+                destination_endpoint: Some(ZDO_ENDPOINT),
+                group_address: None,
+                cluster_id: Some(frame.cluster_id()),
+                profile_id: Some(ZDO_PROFILE_ID),
+                source_endpoint: Some(ZDO_ENDPOINT),
+                // Filled in when sent.
+                counter: None,
+                extended_header: None,
+                security_header: None,
+                payload: &payload[..length],
+            },
+        )
+    }
+
+    /// Send a data frame in indirect delivery mode: one copy of `frame` to
+    /// each destination its source endpoint and cluster are bound to in
+    /// `bindings`. Group destinations are broadcast without an
+    /// acknowledgement. The other destinations are tried even if one of
+    /// them fails, and the first error is returned.
+    pub fn send_indirect<const BINDINGS: usize>(
+        &mut self,
+        bindings: &BindingTable<BINDINGS>,
+        frame: &ApsFrame,
+    ) -> Result<(), SendError> {
+        let (source_endpoint, cluster_id) = match (frame.source_endpoint, frame.cluster_id) {
+            (Some(source_endpoint), Some(cluster_id)) => (source_endpoint, cluster_id),
+            _ => return Err(SendError::InvalidFrame),
+        };
+        let mut bound = false;
+        let mut result = Ok(());
+        for destination in bindings.destinations(source_endpoint, cluster_id) {
+            bound = true;
+            let mut addressed = destination.address(frame);
+            let sent = match destination {
+                // To all the devices whose receiver is on, which filter on
+                // their group table.
+                BindingDestination::Group(_) => self.send_aps_frame(0xfffd, &mut addressed),
+                BindingDestination::Device { ieee_address, .. } => {
+                    match self.nwk_address(ieee_address) {
+                        Some(nwk_address) => self.send_aps_frame(nwk_address, &mut addressed),
+                        None => Err(SendError::UnknownAddress),
+                    }
+                }
+            };
+            result = result.and(sent);
+        }
+        match bound {
+            true => result,
+            false => Err(SendError::NoBinding),
+        }
+    }
+
+    /// The network address of the device with `extended_address`, if it is
+    /// a neighbor or we heard from it.
+    pub fn nwk_address(&self, extended_address: u64) -> Option<u16> {
+        self.neighbors
+            .by_extended_address(extended_address)
+            .map(|neighbor| neighbor.nwk_address)
+            .or_else(|| self.addresses.nwk_address(extended_address))
+    }
+
+    /// Answer a management request from `source` with the stack's tables and
+    /// `bindings`. A leave request for this device is answered before
+    /// leaving the network, other frames are ignored.
+    pub fn answer_management_request<const BINDINGS: usize>(
+        &mut self,
+        source: u16,
+        request: &ZdoFrame,
+        bindings: &BindingTable<BINDINGS>,
+    ) -> Result<(), SendError> {
+        let rejoin = match &request.command {
+            ZdoCommand::MgmtLeaveRequest(leave)
+                if leave.device_address == 0
+                    || leave.device_address == self.mac.extended_address =>
+            {
+                Some(leave.rejoin)
+            }
+            _ => None,
+        };
+
+        let mut scratch = [0u8; MANAGEMENT_RESPONSE_ENTRIES_LENGTH];
+        let response = match rejoin {
+            Some(_) => Some(ZdoFrame {
+                transaction_sequence_number: request.transaction_sequence_number,
+                command: ZdoCommand::MgmtLeaveResponse(Status::Success),
+            }),
+            None => ManagementTables {
+                neighbors: &self.neighbors,
+                routes: &self.routes,
+                bindings,
+            }
+            .respond(request, &mut scratch)?,
+        };
+        let answered = match response {
+            Some(response) => self.send_zdo_frame(source, &response),
+            None => Ok(()),
+        };
+
+        match rejoin {
+            // Even if the response got lost.
+            Some(rejoin) => {
+                let left = self.leave(rejoin);
+                answered.and(left)
+            }
+            None => answered,
+        }
+    }
+
+    /// Announce that this device leaves the network, then forget about it:
+    /// the addresses, neighbors, routes and frame counters. Keys are kept,
+    /// so it can rejoin.
+    pub fn leave(&mut self, rejoin: bool) -> Result<(), SendError> {
+        let mut payload = [0u8; 2];
+        let length = NwkCommand::Leave(Leave {
+            rejoin,
+            request: false,
+            remove_children: false,
+        })
+        .write_into(&mut payload)?;
+        // Only for the devices in range.
+        let mut packet = self.nwk_packet(FrameType::Command, 0xfffd, &payload[..length])?;
+        packet.radius = 1;
+        let sent = self.send_secured(ShortAddress::broadcast(), &packet);
+
+        self.mac.pan_id = PanId::broadcast();
+        self.mac.short_address = None;
+        self.mac.coordinator = None;
+        self.neighbors = NeighborTable::new();
+        self.routes = RoutingTable::new();
+        self.addresses = AddressMap::new();
+        self.incoming_frame_counters.clear();
+        self.duplicate_rejection = DuplicateRejectionTable::new();
+        self.retransmissions = RetransmissionQueue::new();
+        sent
+    }
+
+    /// Send `payload`, e.g. an APS frame, to `destination` in a secured NWK
+    /// data frame, see `next_hop`.
+    pub fn send_data(&mut self, destination: u16, payload: &[u8]) -> Result<(), SendError> {
+        let packet = self.nwk_packet(FrameType::Data, destination, payload)?;
+        self.send_secured(self.next_hop(destination), &packet)
+    }
+
+    /// The device to send a frame for `destination` to: the destination
+    /// itself if it is a neighbor, the next hop of a route to it, or else
+    /// the coordinator we associated with.
+    pub fn next_hop(&self, destination: u16) -> ShortAddress {
+        if let 0xfff8..=0xffff = destination {
+            return ShortAddress::broadcast();
+        }
+        if self.neighbors.by_nwk_address(destination).is_some() {
+            return ShortAddress(destination);
+        }
+        match (self.routes.next_hop(destination), self.mac.coordinator) {
+            (Some(next_hop), _) => ShortAddress(next_hop),
+            (None, Some(Address::Short(_, coordinator))) => coordinator,
+            _ => ShortAddress(destination),
+        }
+    }
+
+    /// An NWK frame from this device with the next sequence number.
+    fn nwk_packet<'p>(
+        &mut self,
+        frame_type: FrameType,
+        destination: u16,
+        payload: &'p [u8],
+    ) -> Result<ZigbeePacket<'p>, SendError> {
+        let ShortAddress(source) = self.mac.short_address.ok_or(SendError::NotJoined)?;
+
+        Ok(ZigbeePacket {
+            frame_control_field: FrameControlField {
+                frame_type,
+                protocol_version: 2,
+                discover_route: DiscoverRoute::EnableRouteDiscovery,
+                multicast_present: false,
+                // Set when the frame is secured.
+                security_present: false,
+                source_route_present: false,
+                destination_present: false,
+                source_address_present: false,
+                end_device_initiator: false,
+            },
+            destination,
+            source,
+            radius: DEFAULT_RADIUS,
+            sequence_number: self.next_nwk_sequence_number(),
+            extended_destination: None,
+            extended_source: None,
+            multicast_control: None,
+            source_route: None,
+            security_header: None,
+            payload,
+        })
+    }
+
+    /// Secure `packet` with the active network key and send it to
+    /// `next_hop`, persisting the outgoing frame counter if it is due.
+    pub fn send_secured(
+        &mut self,
+        next_hop: ShortAddress,
+        packet: &ZigbeePacket,
+    ) -> Result<(), SendError> {
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+        let length =
+            self.key_store
+                .encrypt_frame(&mut self.security_context, packet, &mut buffer)?;
+
+        let sent = self
+            .mac
+            .send_data(self.hardware, next_hop, &buffer[..length]);
+        // The frame counter is used up even if the frame wasn't
+        // acknowledged.
+        self.persist_frame_counter()?;
+        Ok(sent?)
+    }
+
+    /// Receive the next NWK frame addressed to this device into `buffer`,
+    /// along with how it was received.
+    ///
+    /// Secured frames are decrypted, and dropped if they don't authenticate
+    /// with a known network key or were replayed. The neighbor and routing
+    /// tables are updated from them. Unsecured frames are dropped, except
+    /// for the APS Transport-Key command until a network key is held, as
+    /// the trust center sends the network key to joining devices without
+    /// NWK security.
+    pub fn receive<'b>(&mut self, buffer: &'b mut [u8]) -> Option<(ZigbeePacket<'b>, ReceiveInfo)> {
+        let mut frame_buffer = [0u8; MAX_FRAME_LENGTH];
+        let (frame, info) = self.mac.receive(self.hardware, &mut frame_buffer)?;
+        if !matches!(frame.content, FrameContent::Data) {
+            return None;
+        }
+        let previous_hop = match frame.header.source {
+            Some(Address::Short(_, ShortAddress(address))) => Some(address),
+            _ => None,
+        };
+        let buffer = buffer.get_mut(..frame.payload.len())?;
+        buffer.copy_from_slice(frame.payload);
+
+        let security_level = self.security_context.security_level;
+        let secured = ZigbeePacket::try_parse_with_security_level(buffer, security_level)
+            .ok()?
+            .frame_control_field
+            .security_present;
+        if !secured {
+            let packet =
+                ZigbeePacket::try_parse_with_security_level(buffer, security_level).ok()?;
+            if self.key_store.active_network_key().is_some()
+                || !is_key_transport(&packet, security_level)
+            {
+                return None;
+            }
+            return Some((packet, info));
+        }
+
+        let packet = self.key_store.decrypt_frame(buffer, security_level).ok()?;
+        self.incoming_frame_counters.accept_packet(&packet).ok()?;
+        if let Some(previous_hop) = previous_hop {
+            self.learn_from(previous_hop, &packet, info.lqi);
+        }
+        Some((packet, info))
+    }
+
+    /// Update the neighbor `previous_hop` a frame was received from, and
+    /// learn the route back to the frame's originator if it was relayed,
+    /// along with its extended address if the frame has it.
+    fn learn_from(&mut self, previous_hop: u16, packet: &ZigbeePacket, lqi: u8) {
+        if let Some(extended_source) = packet.extended_source {
+            let _ = self.addresses.update(extended_source, packet.source);
+        }
+        if previous_hop != packet.source {
+            let _ = self.routes.update(Route {
+                destination: packet.source,
+                status: RouteStatus::Active,
+                memory_constrained: false,
+                many_to_one: false,
+                route_record_required: false,
+                next_hop: previous_hop,
+            });
+        }
+
+        // NWK security is applied hop by hop, so the auxiliary header holds
+        // the extended address of the previous hop, while the one in the NWK
+        // header is the originator's.
+        let extended_source = packet
+            .security_header
+            .as_ref()
+            .and_then(|header| header.extended_source)
+            .or(packet
+                .extended_source
+                .filter(|_| previous_hop == packet.source));
+        let mut neighbor = match (self.neighbors.by_nwk_address(previous_hop), extended_source) {
+            (Some(neighbor), _) => *neighbor,
+            (None, Some(extended_address)) => Neighbor {
+                extended_pan_id: 0,
+                extended_address,
+                nwk_address: previous_hop,
+                device_type: DeviceType::Unknown,
+                receiver_on_when_idle: None,
+                relationship: Relationship::None,
+                permit_joining: None,
+                depth: 0,
+                lqi,
+            },
+            (None, None) => return,
+        };
+
+        neighbor.lqi = lqi;
+        if previous_hop == 0x0000 {
+            neighbor.device_type = DeviceType::Coordinator;
+        } else if let Ok(NwkCommand::LinkStatus(_)) = packet.command() {
+            // Only routers send their link status.
+            neighbor.device_type = DeviceType::Router;
+        }
+        if matches!(self.mac.coordinator, Some(Address::Short(_, ShortAddress(parent))) if parent == previous_hop)
+        {
+            neighbor.relationship = Relationship::Parent;
+        }
+        let _ = self.neighbors.update(neighbor);
+    }
+
+    /// Store the outgoing frame counter if it is due, see
+    /// `OutgoingFrameCounter`. Needs to be called after every secured frame
+    /// sent.
+    pub fn persist_frame_counter(&mut self) -> Result<(), StorageError> {
+        if let Some(frame_counter) = self.outgoing_frame_counter.poll(&self.security_context) {
+            self.hardware.write(
+                StorageKey::OutgoingFrameCounter,
+                &frame_counter.to_le_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Initialize the Zigbee stack for specific hardware, returning `None` if
+/// the hardware could not be set up.
+pub fn initialize_zigbee_stack<T: ZigbeeHardware>(hardware: &T) -> Option<ZigbeeStack<'_, T>> {
+    if !hardware.connect() {
+        return None;
+    }
+
+    let extended_address = hardware.extended_address();
+    let mut mac = Mac::new(extended_address);
+    // Start from random sequence numbers, so frames sent right after a
+    // reboot aren't mistaken for duplicates of ones sent before it.
+    mac.sequence_number = hardware.random_u32() as u8;
+
+    let mut security_context = SecurityContext::new(extended_address);
+    let mut buffer = [0u8; 4];
+    let persisted = match hardware.read(StorageKey::OutgoingFrameCounter, &mut buffer) {
+        Some(4) => Some(u32::from_le_bytes(buffer)),
+        _ => None,
+    };
+    let outgoing_frame_counter = OutgoingFrameCounter::restore(&mut security_context, persisted);
+
+    let mut stack = ZigbeeStack {
+        hardware,
+        mac,
+        security_context,
+        key_store: KeyStore::new(),
+        incoming_frame_counters: IncomingFrameCounters::new(),
+        outgoing_frame_counter,
+        nwk_sequence_number: hardware.random_u32() as u8,
+        aps_counter: hardware.random_u32() as u8,
+        duplicate_rejection: DuplicateRejectionTable::new(),
+        retransmissions: RetransmissionQueue::new(),
+        neighbors: NeighborTable::new(),
+        routes: RoutingTable::new(),
+        addresses: AddressMap::new(),
+    };
+    // Without storage the frame counter could go back after a reboot.
+    stack.persist_frame_counter().ok()?;
+    Some(stack)
+}
+
+/// Whether `packet` carries an APS Transport-Key command, either in the
+/// clear or secured at the APS layer, where the command can't be read until
+/// it is decrypted.
+fn is_key_transport(packet: &ZigbeePacket, security_level: u8) -> bool {
+    if packet.frame_control_field.frame_type != FrameType::Data {
+        return false;
+    }
+    match ApsFrame::try_parse_with_security_level(packet.payload, security_level) {
+        Ok(frame) if frame.frame_control_field.frame_type == aps::FrameType::Command => {
+            frame.frame_control_field.security
+                || matches!(frame.command(), Ok(ApsCommand::TransportKey(_)))
+        }
+        _ => false,
+    }
 }
 
 /// All the hardware specific functions to implement for this library.
-pub trait ZigbeeHardware {
+pub trait ZigbeeHardware: Radio + Clock + Random + Storage {
     /// Connect and set up the radio hardware, returning true on success.
     fn connect(&self) -> bool;
+
+    /// The IEEE 802.15.4 extended address (EUI-64) of this device.
+    fn extended_address(&self) -> u64;
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ZigbeeHardware;
+    use crate::aps::acknowledgement::APSC_ACK_WAIT_DURATION_MS;
+    use crate::aps::binding::{Binding, BindingDestination, BindingTable};
+    use crate::aps::commands::{ApsCommand, TransportKey};
+    use crate::aps::{
+        ApsFrame, DeliveryMode, FrameControlField as ApsFrameControlField, FrameType,
+    };
+    use crate::hardware::{
+        Clock, Radio, Random, ReceiveInfo, Storage, StorageError, StorageKey, MAX_CHANNEL,
+        MIN_CHANNEL,
+    };
+    use crate::mac::{MacError, MAX_FRAME_LENGTH};
+    use crate::network_layer::commands::{Leave, LinkStatus, NwkCommand};
+    use crate::network_layer::frame_counter::FRAME_COUNTER_PERSIST_INTERVAL;
+    use crate::network_layer::key_store::NetworkKey;
+    use crate::network_layer::neighbor_table::{DeviceType, Relationship};
+    use crate::network_layer::security::SecurityError;
+    use crate::network_layer::FrameType as NwkFrameType;
+    use crate::network_layer::{FrameControlField, ZigbeePacket};
+    use crate::zdo::{ZdoCommand, ZdoFrame};
+    use crate::{SendError, ZigbeeHardware};
+    use byte::TryRead;
+    use core::cell::{Cell, RefCell};
+    use ieee802154::mac::{Address, FooterMode, Frame, PanId, ShortAddress};
+    use std::collections::VecDeque;
 
-    pub struct TestHardware {}
+    pub struct TestHardware {
+        /// Whether `connect` succeeds.
+        pub connects: Cell<bool>,
+        pub now_us: Cell<u64>,
+        /// When the alarm goes off, on the `now_us` timeline.
+        pub alarm_us: Cell<Option<u64>>,
+        /// Frames sent with `transmit`, oldest first.
+        pub transmitted: RefCell<Vec<Vec<u8>>>,
+        /// Frames handed out by `receive`, oldest first.
+        pub received: RefCell<VecDeque<Vec<u8>>>,
+        pub channel: Cell<u8>,
+        pub tx_power: Cell<i8>,
+        /// Whether clear channel assessments find the channel busy.
+        pub channel_busy: Cell<bool>,
+        pub energy: Cell<u8>,
+        /// State of the xorshift generator behind `random_u32`.
+        pub random_state: Cell<u32>,
+        pub storage: RefCell<Vec<(StorageKey, Vec<u8>)>>,
+    }
 
     impl TestHardware {
         pub fn new() -> Self {
-            Self {}
+            Self {
+                connects: Cell::new(false),
+                now_us: Cell::new(0),
+                alarm_us: Cell::new(None),
+                transmitted: RefCell::new(Vec::new()),
+                received: RefCell::new(VecDeque::new()),
+                channel: Cell::new(MIN_CHANNEL),
+                tx_power: Cell::new(0),
+                channel_busy: Cell::new(false),
+                energy: Cell::new(0),
+                random_state: Cell::new(0x2545_f491),
+                storage: RefCell::new(Vec::new()),
+            }
+        }
+
+        pub fn queue_received(&self, frame: &[u8]) {
+            self.received.borrow_mut().push_back(frame.to_vec());
+        }
+
+        pub fn advance_ms(&self, duration: u32) {
+            self.advance_us(duration as u64 * 1000);
+        }
+
+        pub fn advance_us(&self, duration: u64) {
+            self.now_us.set(self.now_us.get() + duration);
         }
     }
 
     impl ZigbeeHardware for TestHardware {
         fn connect(&self) -> bool {
-            false
+            self.connects.get()
+        }
+
+        fn extended_address(&self) -> u64 {
+            0x00_12_4b_00_01_02_03_04
+        }
+    }
+
+    impl Radio for TestHardware {
+        fn transmit(&self, frame: &[u8]) {
+            self.transmitted.borrow_mut().push(frame.to_vec());
+        }
+
+        fn receive(&self, buffer: &mut [u8]) -> Option<ReceiveInfo> {
+            let frame = match self.received.borrow_mut().pop_front() {
+                Some(frame) => frame,
+                None => {
+                    // Let time pass while nothing is received, so loops
+                    // waiting for a frame eventually time out.
+                    self.advance_ms(1);
+                    return None;
+                }
+            };
+            buffer[..frame.len()].copy_from_slice(&frame);
+            Some(ReceiveInfo {
+                length: frame.len(),
+                rssi: -40,
+                lqi: 255,
+                timestamp_us: self.now_us(),
+            })
+        }
+
+        fn set_channel(&self, channel: u8) -> bool {
+            if !(MIN_CHANNEL..=MAX_CHANNEL).contains(&channel) {
+                return false;
+            }
+            self.channel.set(channel);
+            true
+        }
+
+        fn set_tx_power(&self, dbm: i8) {
+            self.tx_power.set(dbm);
+        }
+
+        fn clear_channel_assessment(&self) -> bool {
+            !self.channel_busy.get()
+        }
+
+        fn energy_detect(&self) -> u8 {
+            self.energy.get()
+        }
+    }
+
+    impl Clock for TestHardware {
+        fn now_ms(&self) -> u32 {
+            (self.now_us.get() / 1000) as u32
+        }
+
+        fn now_us(&self) -> u32 {
+            self.now_us.get() as u32
+        }
+
+        fn delay_us(&self, duration: u32) {
+            self.advance_us(duration as u64);
+        }
+
+        fn set_alarm_us(&self, at: u32) {
+            let delay = at.wrapping_sub(self.now_us()) as u64;
+            self.alarm_us.set(Some(self.now_us.get() + delay));
+        }
+
+        fn alarm_expired(&self) -> bool {
+            self.alarm_us
+                .get()
+                .is_some_and(|alarm_us| self.now_us.get() >= alarm_us)
+        }
+
+        fn cancel_alarm(&self) {
+            self.alarm_us.set(None);
+        }
+    }
+
+    impl Random for TestHardware {
+        fn random_u32(&self) -> u32 {
+            let mut x = self.random_state.get();
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            self.random_state.set(x);
+            x
+        }
+    }
+
+    impl Storage for TestHardware {
+        fn read(&self, key: StorageKey, buffer: &mut [u8]) -> Option<usize> {
+            let storage = self.storage.borrow();
+            let (_, value) = storage.iter().find(|(stored, _)| *stored == key)?;
+            buffer.get_mut(..value.len())?.copy_from_slice(value);
+            Some(value.len())
+        }
+
+        fn write(&self, key: StorageKey, value: &[u8]) -> Result<(), StorageError> {
+            let mut storage = self.storage.borrow_mut();
+            storage.retain(|(stored, _)| *stored != key);
+            storage.push((key, value.to_vec()));
+            Ok(())
         }
     }
 
@@ -36,6 +815,532 @@ mod tests {
         let hardware = TestHardware::new();
 
         let result = super::initialize_zigbee_stack(&hardware);
-        assert_eq!(result, false);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn restores_outgoing_frame_counter() {
+        let hardware = TestHardware::new();
+        hardware.connects.set(true);
+
+        let stack = super::initialize_zigbee_stack(&hardware).unwrap();
+        assert_eq!(stack.mac.extended_address, 0x00_12_4b_00_01_02_03_04);
+        assert_eq!(stack.security_context.outgoing_frame_counter, 0);
+
+        let mut stack = super::initialize_zigbee_stack(&hardware).unwrap();
+        assert_eq!(
+            stack.security_context.outgoing_frame_counter,
+            FRAME_COUNTER_PERSIST_INTERVAL
+        );
+
+        stack.security_context.outgoing_frame_counter = 2 * FRAME_COUNTER_PERSIST_INTERVAL;
+        stack.persist_frame_counter().unwrap();
+        let stack = super::initialize_zigbee_stack(&hardware).unwrap();
+        assert_eq!(
+            stack.security_context.outgoing_frame_counter,
+            3 * FRAME_COUNTER_PERSIST_INTERVAL
+        );
+    }
+
+    const NETWORK_KEY: NetworkKey = NetworkKey {
+        sequence_number: 1,
+        key: *b"\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10",
+    };
+
+    fn data_packet(payload: &[u8]) -> ZigbeePacket<'_> {
+        ZigbeePacket {
+            frame_control_field: FrameControlField::from(0x0008),
+            destination: 0x0000,
+            source: 0x1234,
+            radius: 0x1e,
+            sequence_number: 0x2a,
+            extended_destination: None,
+            extended_source: None,
+            multicast_control: None,
+            source_route: None,
+            security_header: None,
+            payload,
+        }
+    }
+
+    #[test]
+    fn secures_sent_and_received_frames() {
+        let sender = TestHardware::new();
+        sender.connects.set(true);
+        let mut sending = super::initialize_zigbee_stack(&sender).unwrap();
+        sending.key_store.add_network_key(NETWORK_KEY);
+        let receiver = TestHardware::new();
+        receiver.connects.set(true);
+        let mut receiving = super::initialize_zigbee_stack(&receiver).unwrap();
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+
+        let packet = data_packet(b"\x01\x02\x03");
+        sending
+            .send_secured(ShortAddress::broadcast(), &packet)
+            .unwrap();
+        sending
+            .send_secured(ShortAddress::broadcast(), &packet)
+            .unwrap();
+        let sent = sender.transmitted.borrow().clone();
+        assert!(!sent[0].windows(3).any(|bytes| bytes == b"\x01\x02\x03"));
+
+        // Dropped without the network key.
+        receiver.queue_received(&sent[0]);
+        assert!(receiving.receive(&mut buffer).is_none());
+
+        receiving.key_store.add_network_key(NETWORK_KEY);
+        receiver.queue_received(&sent[0]);
+        let (received, info) = receiving.receive(&mut buffer).unwrap();
+        assert_eq!(received.payload, b"\x01\x02\x03");
+        assert_eq!(received.source, 0x1234);
+        assert_eq!(info.lqi, 255);
+
+        // A replayed frame is dropped, but not the next one.
+        receiver.queue_received(&sent[0]);
+        assert!(receiving.receive(&mut buffer).is_none());
+        receiver.queue_received(&sent[1]);
+        let (received, _) = receiving.receive(&mut buffer).unwrap();
+        assert_eq!(received.security_header.unwrap().frame_counter, 1);
+
+        // Clearing the security bit doesn't get a replay past the frame
+        // counter check.
+        let mut replayed = sent[1].clone();
+        let (frame, _) = Frame::try_read(&sent[1], FooterMode::None).unwrap();
+        let nwk_offset = sent[1].len() - frame.payload.len();
+        replayed[nwk_offset + 1] &= !0x02;
+        receiver.queue_received(&replayed);
+        assert!(receiving.receive(&mut buffer).is_none());
+    }
+
+    #[test]
+    fn persists_frame_counter_when_sending() {
+        let hardware = TestHardware::new();
+        hardware.connects.set(true);
+        let mut stack = super::initialize_zigbee_stack(&hardware).unwrap();
+        let packet = data_packet(b"\x01");
+        let persisted = || {
+            let mut buffer = [0u8; 4];
+            hardware.read(StorageKey::OutgoingFrameCounter, &mut buffer);
+            u32::from_le_bytes(buffer)
+        };
+
+        assert_eq!(
+            stack.send_secured(ShortAddress::broadcast(), &packet),
+            Err(SendError::Security(SecurityError::UnknownKey))
+        );
+        stack.key_store.add_network_key(NETWORK_KEY);
+        stack.security_context.outgoing_frame_counter = FRAME_COUNTER_PERSIST_INTERVAL - 2;
+        stack
+            .send_secured(ShortAddress::broadcast(), &packet)
+            .unwrap();
+        assert_eq!(persisted(), 0);
+
+        // Persisted even though the frame wasn't acknowledged.
+        assert_eq!(
+            stack.send_secured(ShortAddress(0x0000), &packet),
+            Err(SendError::Mac(MacError::NoAck))
+        );
+        assert_eq!(persisted(), FRAME_COUNTER_PERSIST_INTERVAL);
+    }
+
+    #[test]
+    fn sends_data_through_coordinator() {
+        let device = TestHardware::new();
+        device.connects.set(true);
+        let mut stack = super::initialize_zigbee_stack(&device).unwrap();
+        stack.key_store.add_network_key(NETWORK_KEY);
+        let coordinator = TestHardware::new();
+        coordinator.connects.set(true);
+        let mut coordinator_stack = super::initialize_zigbee_stack(&coordinator).unwrap();
+        coordinator_stack.key_store.add_network_key(NETWORK_KEY);
+        coordinator_stack.mac.short_address = Some(ShortAddress(0x0000));
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+
+        assert_eq!(stack.send_data(0x0000, b"\x01"), Err(SendError::NotJoined));
+        stack.mac.short_address = Some(ShortAddress(0x1234));
+        stack.mac.coordinator = Some(Address::Short(PanId::broadcast(), ShortAddress(0x0000)));
+
+        // Not acknowledged, as nothing answers in this test.
+        let sequence_number = stack.nwk_sequence_number;
+        assert_eq!(
+            stack.send_data(0x5678, b"\x01"),
+            Err(SendError::Mac(MacError::NoAck))
+        );
+        coordinator.queue_received(&device.transmitted.borrow()[0]);
+        let (received, _) = coordinator_stack.receive(&mut buffer).unwrap();
+        assert_eq!(received.destination, 0x5678);
+        assert_eq!(received.source, 0x1234);
+        assert_eq!(received.sequence_number, sequence_number);
+        assert!(received.frame_control_field.security_present);
+        assert_eq!(received.payload, b"\x01");
+    }
+
+    #[test]
+    fn acknowledges_aps_frames() {
+        let device = TestHardware::new();
+        device.connects.set(true);
+        let mut stack = super::initialize_zigbee_stack(&device).unwrap();
+        stack.key_store.add_network_key(NETWORK_KEY);
+        stack.mac.short_address = Some(ShortAddress(0x1234));
+        stack.mac.coordinator = Some(Address::Short(PanId::broadcast(), ShortAddress(0x0000)));
+        let coordinator = TestHardware::new();
+        coordinator.connects.set(true);
+        let mut coordinator_stack = super::initialize_zigbee_stack(&coordinator).unwrap();
+        coordinator_stack.key_store.add_network_key(NETWORK_KEY);
+        coordinator_stack.mac.short_address = Some(ShortAddress(0x0000));
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+
+        let mut frame = ApsFrame {
+            frame_control_field: ApsFrameControlField {
+                frame_type: FrameType::Data,
+                delivery_mode: DeliveryMode::Unicast,
+                ack_format: false,
+                security: false,
+                ack_request: true,
+                extended_header_present: false,
+            },
+            destination_endpoint: Some(1),
+            group_address: None,
+            cluster_id: Some(0x0006),
+            profile_id: Some(0x0104),
+            source_endpoint: Some(1),
+            counter: None,
+            extended_header: None,
+            security_header: None,
+            payload: b"\x01\x2a\x02",
+        };
+        // MAC acknowledgements never come in this test.
+        let _ = coordinator_stack.send_aps_frame(0x1234, &mut frame);
+        assert_eq!(coordinator_stack.retransmissions.len(), 1);
+
+        // The acknowledgement got lost, so the frame is sent again.
+        let sent = coordinator.transmitted.borrow().last().unwrap().clone();
+        device.queue_received(&sent);
+        let (packet, _) = stack.receive(&mut buffer).unwrap();
+        let received = stack.handle_aps_frame(&packet).unwrap();
+        assert_eq!(received.payload, b"\x01\x2a\x02");
+
+        coordinator.advance_ms(APSC_ACK_WAIT_DURATION_MS);
+        coordinator_stack.poll_retransmissions(|_, _| panic!("not failed yet"));
+        let sent = coordinator.transmitted.borrow().last().unwrap().clone();
+        device.queue_received(&sent);
+        let (packet, _) = stack.receive(&mut buffer).unwrap();
+        assert!(stack.handle_aps_frame(&packet).is_none());
+
+        // Both were acknowledged.
+        let acknowledgement = device.transmitted.borrow().last().unwrap().clone();
+        coordinator.queue_received(&acknowledgement);
+        let (packet, _) = coordinator_stack.receive(&mut buffer).unwrap();
+        assert!(coordinator_stack.handle_aps_frame(&packet).is_none());
+        assert!(coordinator_stack.retransmissions.is_empty());
+    }
+
+    #[test]
+    fn reports_unacknowledged_aps_frames() {
+        let hardware = TestHardware::new();
+        hardware.connects.set(true);
+        let mut stack = super::initialize_zigbee_stack(&hardware).unwrap();
+        stack.key_store.add_network_key(NETWORK_KEY);
+        stack.mac.short_address = Some(ShortAddress(0x1234));
+
+        let mut frame = ApsFrame {
+            frame_control_field: ApsFrameControlField::from(0x40),
+            destination_endpoint: Some(1),
+            group_address: None,
+            cluster_id: Some(0x0006),
+            profile_id: Some(0x0104),
+            source_endpoint: Some(1),
+            counter: None,
+            extended_header: None,
+            security_header: None,
+            payload: b"\x01\x2a\x02",
+        };
+        let counter = stack.aps_counter;
+        let _ = stack.send_aps_frame(0x0000, &mut frame);
+
+        let mut failed = Vec::new();
+        for _ in 0..=3 {
+            hardware.advance_ms(APSC_ACK_WAIT_DURATION_MS);
+            stack.poll_retransmissions(|destination, counter| failed.push((destination, counter)));
+        }
+        assert_eq!(failed, [(0x0000, counter)]);
+        assert!(stack.retransmissions.is_empty());
+    }
+
+    #[test]
+    fn learns_neighbors_and_routes() {
+        let router = TestHardware::new();
+        router.connects.set(true);
+        let mut router_stack = super::initialize_zigbee_stack(&router).unwrap();
+        router_stack.key_store.add_network_key(NETWORK_KEY);
+        router_stack.mac.short_address = Some(ShortAddress(0x0001));
+        let coordinator = TestHardware::new();
+        coordinator.connects.set(true);
+        let mut coordinator_stack = super::initialize_zigbee_stack(&coordinator).unwrap();
+        coordinator_stack.key_store.add_network_key(NETWORK_KEY);
+        coordinator_stack.mac.short_address = Some(ShortAddress(0x0000));
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+
+        // Relayed by the router, from a device further away.
+        let _ = router_stack.send_secured(ShortAddress(0x0000), &data_packet(b"\x01"));
+        coordinator.queue_received(&router.transmitted.borrow()[0]);
+        coordinator_stack.receive(&mut buffer).unwrap();
+        assert_eq!(coordinator_stack.routes.next_hop(0x1234), Some(0x0001));
+        let neighbor = coordinator_stack.neighbors.by_nwk_address(0x0001).unwrap();
+        assert_eq!(neighbor.extended_address, router.extended_address());
+        assert_eq!(neighbor.device_type, DeviceType::Unknown);
+
+        let mut payload = [0u8; 2];
+        let length = NwkCommand::LinkStatus(LinkStatus {
+            first_frame: true,
+            last_frame: true,
+            entry_list: &[],
+        })
+        .write_into(&mut payload)
+        .unwrap();
+        let packet = router_stack
+            .nwk_packet(NwkFrameType::Command, 0xfffc, &payload[..length])
+            .unwrap();
+        router_stack
+            .send_secured(ShortAddress::broadcast(), &packet)
+            .unwrap();
+        coordinator.queue_received(router.transmitted.borrow().last().unwrap());
+        coordinator_stack.receive(&mut buffer).unwrap();
+        let neighbor = coordinator_stack.neighbors.by_nwk_address(0x0001).unwrap();
+        assert_eq!(neighbor.device_type, DeviceType::Router);
+        assert_eq!(neighbor.lqi, 255);
+
+        assert_eq!(coordinator_stack.next_hop(0x0001), ShortAddress(0x0001));
+        assert_eq!(coordinator_stack.next_hop(0x1234), ShortAddress(0x0001));
+        assert_eq!(coordinator_stack.next_hop(0x5678), ShortAddress(0x5678));
+        assert_eq!(
+            coordinator_stack.next_hop(0xfffd),
+            ShortAddress::broadcast()
+        );
+    }
+
+    #[test]
+    fn answers_management_requests() {
+        let device = TestHardware::new();
+        device.connects.set(true);
+        let mut stack = super::initialize_zigbee_stack(&device).unwrap();
+        stack.key_store.add_network_key(NETWORK_KEY);
+        stack.mac.short_address = Some(ShortAddress(0x1234));
+        stack.mac.coordinator = Some(Address::Short(PanId::broadcast(), ShortAddress(0x0000)));
+        let coordinator = TestHardware::new();
+        coordinator.connects.set(true);
+        let mut coordinator_stack = super::initialize_zigbee_stack(&coordinator).unwrap();
+        coordinator_stack.key_store.add_network_key(NETWORK_KEY);
+        coordinator_stack.mac.short_address = Some(ShortAddress(0x0000));
+        let bindings = BindingTable::<1>::new();
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+
+        // MAC acknowledgements never come in this test.
+        let _ = coordinator_stack.send_data(0x1234, b"\x00");
+        device.queue_received(coordinator.transmitted.borrow().last().unwrap());
+        stack.receive(&mut buffer).unwrap();
+
+        let request = ZdoFrame::try_parse_from(0x0031, b"\x07\x00").unwrap();
+        let _ = stack.answer_management_request(0x0000, &request, &bindings);
+        coordinator.queue_received(device.transmitted.borrow().last().unwrap());
+        let (packet, _) = coordinator_stack.receive(&mut buffer).unwrap();
+        let aps = coordinator_stack.handle_aps_frame(&packet).unwrap();
+        let response = ZdoFrame::try_parse_from(aps.cluster_id.unwrap(), aps.payload).unwrap();
+        match response.command {
+            ZdoCommand::MgmtLqiResponse(response) => {
+                assert_eq!(response.total_entries, 1);
+                let neighbor = response.entries().next().unwrap();
+                assert_eq!(neighbor.nwk_address, 0x0000);
+                assert_eq!(neighbor.device_type, DeviceType::Coordinator);
+                assert_eq!(neighbor.relationship, Relationship::Parent);
+            }
+            _ => panic!("Expected Mgmt_Lqi_rsp"),
+        }
+
+        // Answered, then announced.
+        let request =
+            ZdoFrame::try_parse_from(0x0034, b"\x08\x00\x00\x00\x00\x00\x00\x00\x00\x00").unwrap();
+        let _ = stack.answer_management_request(0x0000, &request, &bindings);
+        assert_eq!(stack.mac.short_address, None);
+        assert!(stack.neighbors.is_empty());
+        coordinator.queue_received(device.transmitted.borrow().last().unwrap());
+        let (packet, _) = coordinator_stack.receive(&mut buffer).unwrap();
+        assert_eq!(packet.source, 0x1234);
+        assert_eq!(
+            packet.command().unwrap(),
+            NwkCommand::Leave(Leave {
+                rejoin: false,
+                request: false,
+                remove_children: false,
+            })
+        );
+    }
+
+    #[test]
+    fn sends_indirect_frames() {
+        let device = TestHardware::new();
+        device.connects.set(true);
+        let mut stack = super::initialize_zigbee_stack(&device).unwrap();
+        stack.key_store.add_network_key(NETWORK_KEY);
+        stack.mac.short_address = Some(ShortAddress(0x1234));
+        let coordinator = TestHardware::new();
+        coordinator.connects.set(true);
+        let mut coordinator_stack = super::initialize_zigbee_stack(&coordinator).unwrap();
+        coordinator_stack.key_store.add_network_key(NETWORK_KEY);
+        coordinator_stack.mac.short_address = Some(ShortAddress(0x0000));
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+
+        // The coordinator becomes a neighbor, so its address is known.
+        let _ = coordinator_stack.send_data(0x1234, b"\x00");
+        device.queue_received(coordinator.transmitted.borrow().last().unwrap());
+        stack.receive(&mut buffer).unwrap();
+        assert_eq!(
+            stack.nwk_address(coordinator.extended_address()),
+            Some(0x0000)
+        );
+
+        let mut bindings = BindingTable::<3>::new();
+        let binding = |cluster_id, destination| Binding {
+            source_address: device.extended_address(),
+            source_endpoint: 1,
+            cluster_id,
+            destination,
+        };
+        bindings
+            .bind(binding(0x0006, BindingDestination::Group(0x0001)))
+            .unwrap();
+        bindings
+            .bind(binding(
+                0x0006,
+                BindingDestination::Device {
+                    ieee_address: coordinator.extended_address(),
+                    endpoint: 2,
+                },
+            ))
+            .unwrap();
+        bindings
+            .bind(binding(
+                0x0008,
+                BindingDestination::Device {
+                    ieee_address: 0x0011_2233_4455_6677,
+                    endpoint: 1,
+                },
+            ))
+            .unwrap();
+
+        let mut frame = ApsFrame {
+            frame_control_field: ApsFrameControlField {
+                frame_type: FrameType::Data,
+                delivery_mode: DeliveryMode::Indirect,
+                ack_format: false,
+                security: false,
+                ack_request: true,
+                extended_header_present: false,
+            },
+            destination_endpoint: None,
+            group_address: None,
+            cluster_id: Some(0x0006),
+            profile_id: Some(0x0104),
+            source_endpoint: Some(1),
+            counter: None,
+            extended_header: None,
+            security_header: None,
+            payload: b"\x01\x2a\x02",
+        };
+        let sent = device.transmitted.borrow().len();
+        // MAC acknowledgements never come in this test.
+        let _ = stack.send_indirect(&bindings, &frame);
+        assert_eq!(
+            frame.frame_control_field.delivery_mode,
+            DeliveryMode::Indirect
+        );
+        // The broadcast first, then the unicast and its MAC retries.
+        let transmitted = device.transmitted.borrow()[sent..].to_vec();
+        // Only the frame to the device waits for an acknowledgement.
+        assert_eq!(stack.retransmissions.len(), 1);
+
+        coordinator.queue_received(&transmitted[0]);
+        let (packet, _) = coordinator_stack.receive(&mut buffer).unwrap();
+        assert_eq!(packet.destination, 0xfffd);
+        let aps = coordinator_stack.handle_aps_frame(&packet).unwrap();
+        assert_eq!(aps.frame_control_field.delivery_mode, DeliveryMode::Group);
+        assert_eq!(aps.group_address, Some(0x0001));
+        assert!(!aps.frame_control_field.ack_request);
+
+        coordinator.queue_received(transmitted.last().unwrap());
+        let (packet, _) = coordinator_stack.receive(&mut buffer).unwrap();
+        assert_eq!(packet.destination, 0x0000);
+        let aps = coordinator_stack.handle_aps_frame(&packet).unwrap();
+        assert_eq!(aps.frame_control_field.delivery_mode, DeliveryMode::Unicast);
+        assert_eq!(aps.destination_endpoint, Some(2));
+        assert!(aps.frame_control_field.ack_request);
+
+        frame.cluster_id = Some(0x0008);
+        assert_eq!(
+            stack.send_indirect(&bindings, &frame),
+            Err(SendError::UnknownAddress)
+        );
+        frame.cluster_id = Some(0x0300);
+        assert_eq!(
+            stack.send_indirect(&bindings, &frame),
+            Err(SendError::NoBinding)
+        );
+    }
+
+    #[test]
+    fn drops_unsecured_frames_once_joined() {
+        let device = TestHardware::new();
+        device.connects.set(true);
+        let mut stack = super::initialize_zigbee_stack(&device).unwrap();
+        stack.mac.short_address = Some(ShortAddress(0x1234));
+        let coordinator = TestHardware::new();
+        coordinator.connects.set(true);
+        let mut coordinator_stack = super::initialize_zigbee_stack(&coordinator).unwrap();
+        coordinator_stack.mac.short_address = Some(ShortAddress(0x0000));
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+        let mut send_unsecured = |aps: &[u8]| {
+            let packet = coordinator_stack
+                .nwk_packet(NwkFrameType::Data, 0x1234, aps)
+                .unwrap();
+            let mut frame = [0u8; MAX_FRAME_LENGTH];
+            let length = packet.write_into(&mut frame).unwrap();
+            // MAC acknowledgements never come in this test.
+            let _ = coordinator_stack.mac.send_data(
+                &coordinator,
+                ShortAddress(0x1234),
+                &frame[..length],
+            );
+            device.queue_received(coordinator.transmitted.borrow().last().unwrap());
+        };
+
+        let transport_key = ApsCommand::TransportKey(TransportKey::StandardNetworkKey {
+            key: NETWORK_KEY.key,
+            sequence_number: NETWORK_KEY.sequence_number,
+            destination_address: 0,
+            source_address: coordinator.extended_address(),
+        });
+        let mut key_command = vec![0x01, 0x01];
+        let mut command = [0u8; MAX_FRAME_LENGTH];
+        let length = transport_key.write_into(&mut command).unwrap();
+        key_command.extend_from_slice(&command[..length]);
+        let leave_request =
+            b"\x00\x00\x34\x00\x00\x00\x00\x02\x08\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+        // Only the network key is accepted without NWK security.
+        send_unsecured(leave_request);
+        assert!(stack.receive(&mut buffer).is_none());
+        send_unsecured(&key_command);
+        let (packet, _) = stack.receive(&mut buffer).unwrap();
+        let aps = ApsFrame::try_parse_from(packet.payload).unwrap();
+        stack
+            .key_store
+            .handle_command(&aps.command().unwrap())
+            .unwrap();
+
+        send_unsecured(leave_request);
+        assert!(stack.receive(&mut buffer).is_none());
+        send_unsecured(&key_command);
+        assert!(stack.receive(&mut buffer).is_none());
+        assert_eq!(stack.mac.short_address, Some(ShortAddress(0x1234)));
     }
 }
@@ -0,0 +1,613 @@
+use crate::hardware::ReceiveInfo;
+use crate::ZigbeeHardware;
+use byte::{TryRead, TryWrite};
+use ieee802154::mac::command::{AssociationStatus, CapabilityInformation, Command};
+use ieee802154::mac::{
+    Address, AddressMode, ExtendedAddress, FooterMode, Frame, FrameContent, FrameSerDesContext,
+    FrameType, FrameVersion, Header, PanId, ShortAddress,
+};
+
+/// Longest frame the radio can send (aMaxPhyPacketSize), without the two
+/// byte frame check sequence.
+pub const MAX_FRAME_LENGTH: usize = 127 - 2;
+/// How long to wait for the acknowledgement of a frame that requested one
+/// (macAckWaitDuration), 54 symbols.
+pub const ACK_WAIT_DURATION_US: u32 = 54 * 16;
+/// Length of one CSMA-CA backoff period (aUnitBackoffPeriod), 20 symbols.
+pub const UNIT_BACKOFF_PERIOD_US: u32 = 20 * 16;
+
+#[derive(Debug, PartialEq)]
+pub enum MacError {
+    /// The frame could not be serialized, e.g. because it is too long.
+    InvalidFrame,
+    /// No acknowledgement was received for a frame that requested one,
+    /// even after retransmitting it.
+    NoAck,
+    /// The channel stayed busy, so the frame was not sent.
+    ChannelAccessFailure,
+}
+
+/// Parameters of the unslotted CSMA-CA used to send frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CsmaParameters {
+    /// Backoff exponent of the first attempt (macMinBE), from 0 to
+    /// `max_backoff_exponent`.
+    pub min_backoff_exponent: u8,
+    /// Highest backoff exponent (macMaxBE), from 3 to 8.
+    pub max_backoff_exponent: u8,
+    /// How many more times to back off after finding the channel busy,
+    /// before giving up (macMaxCSMABackoffs), from 0 to 5.
+    pub max_backoffs: u8,
+}
+impl Default for CsmaParameters {
+    fn default() -> Self {
+        Self {
+            min_backoff_exponent: 3,
+            max_backoff_exponent: 5,
+            max_backoffs: 4,
+        }
+    }
+}
+
+/// The IEEE 802.15.4 MAC layer, sending and receiving frames through the
+/// `Radio` of the `ZigbeeHardware`.
+///
+/// Received frames are filtered by address and acknowledged, and commands
+/// that change the MAC's own state, such as association responses, are
+/// handled before the frame is passed on.
+#[derive(Debug)]
+pub struct Mac {
+    pub extended_address: u64,
+    /// The PAN this device is a member of, the broadcast PAN until it
+    /// associates with one.
+    pub pan_id: PanId,
+    /// Short address assigned by the coordinator when associating.
+    pub short_address: Option<ShortAddress>,
+    /// The coordinator this device associated, or is associating, with.
+    pub coordinator: Option<Address>,
+    /// Sequence number of the next frame sent (macDSN).
+    pub sequence_number: u8,
+    pub csma: CsmaParameters,
+    /// How many times to retransmit a frame that wasn't acknowledged
+    /// (macMaxFrameRetries), from 0 to 7.
+    pub max_frame_retries: u8,
+}
+impl Mac {
+    pub fn new(extended_address: u64) -> Self {
+        Self {
+            extended_address,
+            pan_id: PanId::broadcast(),
+            short_address: None,
+            coordinator: None,
+            sequence_number: 0,
+            csma: CsmaParameters::default(),
+            max_frame_retries: 3,
+        }
+    }
+
+    /// The sequence number for the next frame sent (macDSN).
+    pub fn next_sequence_number(&mut self) -> u8 {
+        let sequence_number = self.sequence_number;
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+        sequence_number
+    }
+
+    /// Our own address, the short one once we have one.
+    pub fn source_address(&self) -> Address {
+        match self.short_address {
+            Some(short_address) => Address::Short(self.pan_id, short_address),
+            None => Address::Extended(self.pan_id, ExtendedAddress(self.extended_address)),
+        }
+    }
+
+    /// Send `frame` with the next sequence number once the channel is
+    /// clear. If it requests an acknowledgement, it is retransmitted up to
+    /// `max_frame_retries` times until one is received.
+    pub fn send(
+        &mut self,
+        hardware: &impl ZigbeeHardware,
+        mut frame: Frame,
+    ) -> Result<(), MacError> {
+        frame.header.seq = self.next_sequence_number();
+
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+        let length = frame
+            .try_write(
+                &mut buffer,
+                &mut FrameSerDesContext::no_security(FooterMode::None),
+            )
+            .map_err(|_| MacError::InvalidFrame)?;
+
+        for _ in 0..=self.max_frame_retries {
+            self.transmit(hardware, &buffer[..length])?;
+            if !frame.header.ack_request || self.wait_for_ack(hardware, frame.header.seq) {
+                return Ok(());
+            }
+        }
+
+        Err(MacError::NoAck)
+    }
+
+    /// Transmit `frame` following unslotted CSMA-CA: wait a random number
+    /// of backoff periods, then send it if the channel is clear, or try
+    /// again with a longer backoff if it is busy.
+    fn transmit(&self, hardware: &impl ZigbeeHardware, frame: &[u8]) -> Result<(), MacError> {
+        let mut backoff_exponent = self.csma.min_backoff_exponent;
+
+        for _ in 0..=self.csma.max_backoffs {
+            let backoff_periods = hardware.random_u32() % (1 << backoff_exponent);
+            hardware.delay_us(backoff_periods * UNIT_BACKOFF_PERIOD_US);
+
+            if hardware.clear_channel_assessment() {
+                hardware.transmit(frame);
+                return Ok(());
+            }
+            backoff_exponent = (backoff_exponent + 1).min(self.csma.max_backoff_exponent);
+        }
+
+        Err(MacError::ChannelAccessFailure)
+    }
+
+    /// Wait up to `ACK_WAIT_DURATION_US` for the acknowledgement of the
+    /// frame with `sequence_number`, returning whether it was received.
+    /// Other frames received in the meantime are dropped.
+    fn wait_for_ack(&self, hardware: &impl ZigbeeHardware, sequence_number: u8) -> bool {
+        let sent_at = hardware.now_us();
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+
+        while hardware.now_us().wrapping_sub(sent_at) < ACK_WAIT_DURATION_US {
+            let length = match hardware.receive(&mut buffer) {
+                Some(info) => info.length,
+                None => continue,
+            };
+            if let Ok((frame, _)) = Frame::try_read(&buffer[..length], FooterMode::None) {
+                if frame.header.frame_type == FrameType::Acknowledgement
+                    && frame.header.seq == sequence_number
+                {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Receive the next frame addressed to this device, acknowledging it if
+    /// requested, along with how it was received. Returns `None` if no frame
+    /// was received, or if it was dropped.
+    pub fn receive<'b>(
+        &mut self,
+        hardware: &impl ZigbeeHardware,
+        buffer: &'b mut [u8],
+    ) -> Option<(Frame<'b>, ReceiveInfo)> {
+        let info = hardware.receive(buffer)?;
+        let buffer: &'b [u8] = buffer;
+        let (frame, _) = Frame::try_read(buffer.get(..info.length)?, FooterMode::None).ok()?;
+
+        if !self.accepts(&frame) {
+            return None;
+        }
+        if frame.header.ack_request {
+            self.acknowledge(hardware, frame.header.seq);
+        }
+        if let FrameContent::Command(command) = &frame.content {
+            self.handle_command(command);
+        }
+
+        Some((frame, info))
+    }
+
+    /// Whether a received frame is addressed to this device, following the
+    /// third level of filtering of IEEE 802.15.4. Acknowledgements are only
+    /// expected while waiting for one, so they are never accepted here.
+    pub fn accepts(&self, frame: &Frame) -> bool {
+        let header = &frame.header;
+        let pan_matches = |pan_id: PanId| {
+            pan_id == PanId::broadcast()
+                || self.pan_id == PanId::broadcast()
+                || pan_id == self.pan_id
+        };
+
+        match header.frame_type {
+            FrameType::Acknowledgement => return false,
+            FrameType::Beacon => {
+                return header
+                    .source
+                    .is_some_and(|source| pan_matches(source.pan_id()))
+            }
+            _ => {}
+        }
+
+        // Frames without a destination are for the PAN coordinator, which
+        // this device never is.
+        let destination = match header.destination {
+            Some(destination) => destination,
+            None => return false,
+        };
+        if !pan_matches(destination.pan_id()) {
+            return false;
+        }
+        match destination {
+            Address::Short(_, address) => {
+                address == ShortAddress::broadcast() || Some(address) == self.short_address
+            }
+            Address::Extended(_, ExtendedAddress(address)) => address == self.extended_address,
+        }
+    }
+
+    fn acknowledge(&self, hardware: &impl ZigbeeHardware, sequence_number: u8) {
+        let mut header = header(FrameType::Acknowledgement, false, None, None);
+        header.seq = sequence_number;
+        let frame = Frame {
+            header,
+            content: FrameContent::Acknowledgement,
+            payload: &[],
+            footer: [0, 0],
+        };
+
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+        if let Ok(length) = frame.try_write(
+            &mut buffer,
+            &mut FrameSerDesContext::no_security(FooterMode::None),
+        ) {
+            hardware.transmit(&buffer[..length]);
+        }
+    }
+
+    fn handle_command(&mut self, command: &Command) {
+        match command {
+            Command::AssociationResponse(address, AssociationStatus::Successful) => {
+                self.short_address = Some(*address);
+            }
+            Command::DisassociationNotification(_) => {
+                self.short_address = None;
+                self.coordinator = None;
+                self.pan_id = PanId::broadcast();
+            }
+            _ => {}
+        }
+    }
+
+    /// Ask all coordinators in range to send a beacon, to find networks to
+    /// join.
+    pub fn send_beacon_request(&mut self, hardware: &impl ZigbeeHardware) -> Result<(), MacError> {
+        self.send(
+            hardware,
+            Frame {
+                header: header(
+                    FrameType::MacCommand,
+                    false,
+                    Address::broadcast(&AddressMode::Short),
+                    None,
+                ),
+                content: FrameContent::Command(Command::BeaconRequest),
+                payload: &[],
+                footer: [0, 0],
+            },
+        )
+    }
+
+    /// Ask the coordinator with `coordinator_address` to let this device
+    /// join its PAN. The coordinator's answer has to be polled for with
+    /// `send_data_request`.
+    pub fn send_association_request(
+        &mut self,
+        hardware: &impl ZigbeeHardware,
+        pan_id: PanId,
+        coordinator_address: ShortAddress,
+        capability_information: CapabilityInformation,
+    ) -> Result<(), MacError> {
+        self.pan_id = pan_id;
+        self.coordinator = Some(Address::Short(pan_id, coordinator_address));
+
+        self.send(
+            hardware,
+            Frame {
+                header: header(
+                    FrameType::MacCommand,
+                    true,
+                    self.coordinator,
+                    Some(Address::Extended(
+                        PanId::broadcast(),
+                        ExtendedAddress(self.extended_address),
+                    )),
+                ),
+                content: FrameContent::Command(Command::AssociationRequest(capability_information)),
+                payload: &[],
+                footer: [0, 0],
+            },
+        )
+    }
+
+    /// Poll the coordinator for frames it holds for this device.
+    pub fn send_data_request(&mut self, hardware: &impl ZigbeeHardware) -> Result<(), MacError> {
+        let coordinator = self.coordinator.ok_or(MacError::InvalidFrame)?;
+
+        self.send(
+            hardware,
+            Frame {
+                header: header(
+                    FrameType::MacCommand,
+                    true,
+                    Some(coordinator),
+                    Some(self.source_address()),
+                ),
+                content: FrameContent::Command(Command::DataRequest),
+                payload: &[],
+                footer: [0, 0],
+            },
+        )
+    }
+
+    /// Send `payload`, e.g. an NWK frame, to `destination` in our PAN.
+    /// Unicast frames are acknowledged.
+    pub fn send_data(
+        &mut self,
+        hardware: &impl ZigbeeHardware,
+        destination: ShortAddress,
+        payload: &[u8],
+    ) -> Result<(), MacError> {
+        let ack_request = destination != ShortAddress::broadcast();
+
+        self.send(
+            hardware,
+            Frame {
+                header: header(
+                    FrameType::Data,
+                    ack_request,
+                    Some(Address::Short(self.pan_id, destination)),
+                    Some(self.source_address()),
+                ),
+                content: FrameContent::Data,
+                payload,
+                footer: [0, 0],
+            },
+        )
+    }
+}
+
+/// Header of a frame we send, the sequence number is filled in when it is
+/// sent.
+fn header(
+    frame_type: FrameType,
+    ack_request: bool,
+    destination: Option<Address>,
+    source: Option<Address>,
+) -> Header {
+    let pan_id_compress = match (destination, source) {
+        (Some(destination), Some(source)) => destination.pan_id() == source.pan_id(),
+        _ => false,
+    };
+
+    Header {
+        frame_type,
+        frame_pending: false,
+        ack_request,
+        pan_id_compress,
+        seq_no_suppress: false,
+        ie_present: false,
+        version: FrameVersion::Ieee802154_2003,
+        seq: 0,
+        destination,
+        source,
+        auxiliary_security_header: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::{Clock, Radio};
+    use crate::simulation::{Link, Medium};
+    use crate::tests::TestHardware;
+
+    const EXTENDED_ADDRESS: u64 = 0x42_42_42_42_42_42_42_42;
+
+    #[test]
+    fn sends_beacon_request() {
+        let hardware = TestHardware::new();
+        let mut mac = Mac::new(EXTENDED_ADDRESS);
+
+        mac.send_beacon_request(&hardware).unwrap();
+        mac.send_beacon_request(&hardware).unwrap();
+
+        let transmitted = hardware.transmitted.borrow();
+        assert_eq!(transmitted[0], b"\x03\x08\x00\xff\xff\xff\xff\x07");
+        assert_eq!(transmitted[1], b"\x03\x08\x01\xff\xff\xff\xff\x07");
+    }
+
+    #[test]
+    fn backs_off_while_channel_is_busy() {
+        let hardware = TestHardware::new();
+        let mut mac = Mac::new(EXTENDED_ADDRESS);
+
+        hardware.channel_busy.set(true);
+        assert_eq!(
+            mac.send_beacon_request(&hardware),
+            Err(MacError::ChannelAccessFailure)
+        );
+        assert!(hardware.transmitted.borrow().is_empty());
+        // At most 7 + 15 + 3 * 31 backoff periods with the default
+        // exponents from 3 to 5.
+        assert!(hardware.now_us.get() <= 115 * UNIT_BACKOFF_PERIOD_US as u64);
+
+        hardware.channel_busy.set(false);
+        mac.send_beacon_request(&hardware).unwrap();
+        assert_eq!(hardware.transmitted.borrow().len(), 1);
+
+        // Without backoffs, a busy channel fails right away.
+        mac.csma = CsmaParameters {
+            min_backoff_exponent: 0,
+            max_backoff_exponent: 3,
+            max_backoffs: 0,
+        };
+        hardware.channel_busy.set(true);
+        let now_us = hardware.now_us.get();
+        assert_eq!(
+            mac.send_beacon_request(&hardware),
+            Err(MacError::ChannelAccessFailure)
+        );
+        assert_eq!(hardware.now_us.get(), now_us);
+    }
+
+    #[test]
+    fn associates_with_coordinator() {
+        let hardware = TestHardware::new();
+        let mut mac = Mac::new(EXTENDED_ADDRESS);
+        let capability_information = CapabilityInformation {
+            full_function_device: true,
+            mains_power: true,
+            idle_receive: true,
+            frame_protection: false,
+            allocate_address: true,
+        };
+
+        assert_eq!(
+            mac.send_association_request(
+                &hardware,
+                PanId(0xd721),
+                ShortAddress(0x0000),
+                capability_information
+            ),
+            Err(MacError::NoAck)
+        );
+        // Sent once, then retransmitted with the same sequence number.
+        {
+            let transmitted = hardware.transmitted.borrow();
+            assert_eq!(transmitted.len(), 4);
+            assert!(transmitted.iter().all(|frame| *frame == transmitted[0]));
+        }
+
+        // The acknowledgement of the next frame.
+        hardware.queue_received(b"\x02\x00\x01");
+        mac.send_association_request(
+            &hardware,
+            PanId(0xd721),
+            ShortAddress(0x0000),
+            capability_information,
+        )
+        .unwrap();
+        assert_eq!(
+            hardware.transmitted.borrow()[4],
+            b"\x23\xc8\x01\x21\xd7\x00\x00\xff\xff\x42\x42\x42\x42\x42\x42\x42\x42\x01\x8e"
+        );
+
+        // Association response from the coordinator, with short address
+        // 0x1234.
+        hardware.queue_received(
+            b"\x63\xcc\x07\x21\xd7\x42\x42\x42\x42\x42\x42\x42\x42\
+\x01\x00\x00\x00\x00\x4b\x12\x00\x02\x34\x12\x00",
+        );
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+        let (frame, info) = mac.receive(&hardware, &mut buffer).unwrap();
+        assert_eq!(info.length, 25);
+        assert_eq!(
+            frame.content,
+            FrameContent::Command(Command::AssociationResponse(
+                ShortAddress(0x1234),
+                AssociationStatus::Successful
+            ))
+        );
+        assert_eq!(mac.short_address, Some(ShortAddress(0x1234)));
+        // The response was acknowledged.
+        assert_eq!(
+            hardware.transmitted.borrow().last().unwrap(),
+            b"\x02\x00\x07"
+        );
+    }
+
+    #[test]
+    fn retransmits_until_acknowledged() {
+        let medium = Medium::new(1);
+        let device = medium.add_node(EXTENDED_ADDRESS);
+        let coordinator = medium.add_node(0x00_12_4b_00_00_00_00_01);
+        medium.connect(&device, &coordinator, Link::default());
+
+        // The coordinator misses the first frame, and acknowledges the
+        // retransmission.
+        let mut coordinator_mac = Mac::new(coordinator.extended_address());
+        coordinator_mac.pan_id = PanId(0xd721);
+        coordinator_mac.short_address = Some(ShortAddress(0x0000));
+        let mut missed = false;
+        medium.set_idle_handler(&coordinator, move |hardware| {
+            let mut buffer = [0u8; MAX_FRAME_LENGTH];
+            if !missed {
+                missed = hardware.receive(&mut buffer).is_some();
+                return;
+            }
+            coordinator_mac.receive(hardware, &mut buffer);
+        });
+
+        let mut mac = Mac::new(EXTENDED_ADDRESS);
+        mac.pan_id = PanId(0xd721);
+        mac.short_address = Some(ShortAddress(0x1234));
+        mac.send_data(&device, ShortAddress(0x0000), b"\x01")
+            .unwrap();
+        assert_eq!(device.transmitted().len(), 2);
+        assert_eq!(coordinator.transmitted(), [b"\x02\x00\x00".to_vec()]);
+
+        // Without retries, a single miss fails the frame, after waiting
+        // for the acknowledgement.
+        mac.max_frame_retries = 0;
+        let mut missed = false;
+        medium.set_idle_handler(&coordinator, move |hardware| {
+            let mut buffer = [0u8; MAX_FRAME_LENGTH];
+            if !missed {
+                missed = hardware.receive(&mut buffer).is_some();
+            }
+        });
+        let sent_at = device.now_us();
+        assert_eq!(
+            mac.send_data(&device, ShortAddress(0x0000), b"\x02"),
+            Err(MacError::NoAck)
+        );
+        assert_eq!(device.transmitted().len(), 3);
+        assert!(device.now_us() - sent_at >= ACK_WAIT_DURATION_US);
+    }
+
+    #[test]
+    fn filters_frames_by_address() {
+        let hardware = TestHardware::new();
+        let mut mac = Mac::new(EXTENDED_ADDRESS);
+        mac.pan_id = PanId(0xd721);
+        mac.short_address = Some(ShortAddress(0x1234));
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+
+        // Data frames to our short address, to the broadcast address, and to
+        // another device.
+        hardware.queue_received(b"\x41\x88\x01\x21\xd7\x34\x12\x00\x00\x01");
+        hardware.queue_received(b"\x41\x88\x02\x21\xd7\xff\xff\x00\x00\x02");
+        hardware.queue_received(b"\x41\x88\x03\x21\xd7\x35\x12\x00\x00\x03");
+        // From another PAN.
+        hardware.queue_received(b"\x41\x88\x04\x22\xd7\x34\x12\x00\x00\x04");
+
+        assert_eq!(
+            mac.receive(&hardware, &mut buffer).unwrap().0.payload,
+            b"\x01"
+        );
+        assert_eq!(
+            mac.receive(&hardware, &mut buffer).unwrap().0.payload,
+            b"\x02"
+        );
+        assert!(mac.receive(&hardware, &mut buffer).is_none());
+        assert!(mac.receive(&hardware, &mut buffer).is_none());
+        // None of them requested an acknowledgement.
+        assert!(hardware.transmitted.borrow().is_empty());
+    }
+
+    #[test]
+    fn sends_data_with_short_addresses() {
+        let hardware = TestHardware::new();
+        let mut mac = Mac::new(EXTENDED_ADDRESS);
+        mac.pan_id = PanId(0xd721);
+        mac.short_address = Some(ShortAddress(0x1234));
+
+        mac.send_data(&hardware, ShortAddress::broadcast(), b"\x08\x00")
+            .unwrap();
+
+        assert_eq!(
+            hardware.transmitted.borrow()[0],
+            b"\x41\x88\x00\x21\xd7\xff\xff\x34\x12\x08\x00"
+        );
+    }
+}
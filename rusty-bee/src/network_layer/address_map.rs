@@ -0,0 +1,90 @@
+use crate::table::{Table, TableFull};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AddressMapping {
+    extended_address: u64,
+    nwk_address: u16,
+}
+
+/// Network addresses of other devices, keyed by extended address
+/// (nwkAddressMap), learned from frames carrying both, such as device
+/// announcements.
+#[derive(Debug, Clone, Default)]
+pub struct AddressMap<const N: usize> {
+    addresses: Table<AddressMapping, N>,
+}
+impl<const N: usize> AddressMap<N> {
+    pub const fn new() -> Self {
+        Self {
+            addresses: Table::new(),
+        }
+    }
+
+    /// Record the network address of the device with `extended_address`,
+    /// replacing the one it had before and forgetting any other device
+    /// that had this network address.
+    pub fn update(&mut self, extended_address: u64, nwk_address: u16) -> Result<(), TableFull> {
+        self.addresses.remove_where(|mapping| {
+            mapping.nwk_address == nwk_address && mapping.extended_address != extended_address
+        });
+        self.addresses.upsert(
+            AddressMapping {
+                extended_address,
+                nwk_address,
+            },
+            |mapping| mapping.extended_address == extended_address,
+        )
+    }
+
+    pub fn remove(&mut self, extended_address: u64) -> bool {
+        self.addresses
+            .remove_where(|mapping| mapping.extended_address == extended_address)
+            > 0
+    }
+
+    pub fn nwk_address(&self, extended_address: u64) -> Option<u16> {
+        self.addresses
+            .find(|mapping| mapping.extended_address == extended_address)
+            .map(|mapping| mapping.nwk_address)
+    }
+
+    pub fn extended_address(&self, nwk_address: u16) -> Option<u64> {
+        self.addresses
+            .find(|mapping| mapping.nwk_address == nwk_address)
+            .map(|mapping| mapping.extended_address)
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_one_address_per_device() {
+        let mut addresses = AddressMap::<2>::new();
+
+        addresses.update(1, 0x1111).unwrap();
+        addresses.update(2, 0x2222).unwrap();
+        // The device rejoined with a new network address.
+        addresses.update(1, 0x3333).unwrap();
+        assert_eq!(addresses.nwk_address(1), Some(0x3333));
+        assert_eq!(addresses.extended_address(0x1111), None);
+
+        // Another device was given the address of the second one.
+        addresses.update(3, 0x2222).unwrap();
+        assert_eq!(addresses.nwk_address(2), None);
+        assert_eq!(addresses.extended_address(0x2222), Some(3));
+        assert_eq!(addresses.len(), 2);
+
+        assert!(addresses.remove(3));
+        assert!(!addresses.remove(3));
+    }
+}
@@ -0,0 +1,958 @@
+use super::{ParseError, WriteError};
+use byte::ctx::Bytes;
+use byte::{BytesExt, LE};
+
+/// Payload of an NWK command frame, i.e. a frame with
+/// `FrameType::Command`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NwkCommand<'a> {
+    RouteRequest(RouteRequest),
+    RouteReply(RouteReply),
+    NetworkStatus(NetworkStatus),
+    Leave(Leave),
+    RouteRecord(RouteRecord<'a>),
+    RejoinRequest(CapabilityInformation),
+    RejoinResponse(RejoinResponse),
+    LinkStatus(LinkStatus<'a>),
+    NetworkReport(NetworkReport<'a>),
+    NetworkUpdate(NetworkUpdate),
+    EndDeviceTimeoutRequest(EndDeviceTimeoutRequest),
+    EndDeviceTimeoutResponse(EndDeviceTimeoutResponse),
+    LinkPowerDelta(LinkPowerDelta<'a>),
+}
+impl<'a> NwkCommand<'a> {
+    pub fn try_parse_from(payload: &'a [u8]) -> Result<Self, ParseError> {
+        let offset = &mut 0;
+
+        let command_id = payload.read_with::<u8>(offset, LE)?;
+        let command = match command_id {
+            0x01 => NwkCommand::RouteRequest(RouteRequest::try_parse_from(payload, offset)?),
+            0x02 => NwkCommand::RouteReply(RouteReply::try_parse_from(payload, offset)?),
+            0x03 => NwkCommand::NetworkStatus(NetworkStatus::try_parse_from(payload, offset)?),
+            0x04 => NwkCommand::Leave(Leave::from(payload.read_with::<u8>(offset, LE)?)),
+            0x05 => NwkCommand::RouteRecord(RouteRecord::try_parse_from(payload, offset)?),
+            0x06 => NwkCommand::RejoinRequest(CapabilityInformation::from(
+                payload.read_with::<u8>(offset, LE)?,
+            )),
+            0x07 => NwkCommand::RejoinResponse(RejoinResponse::try_parse_from(payload, offset)?),
+            0x08 => NwkCommand::LinkStatus(LinkStatus::try_parse_from(payload, offset)?),
+            0x09 => NwkCommand::NetworkReport(NetworkReport::try_parse_from(payload, offset)?),
+            0x0a => NwkCommand::NetworkUpdate(NetworkUpdate::try_parse_from(payload, offset)?),
+            0x0b => NwkCommand::EndDeviceTimeoutRequest(EndDeviceTimeoutRequest::try_parse_from(
+                payload, offset,
+            )?),
+            0x0c => NwkCommand::EndDeviceTimeoutResponse(EndDeviceTimeoutResponse::try_parse_from(
+                payload, offset,
+            )?),
+            0x0d => NwkCommand::LinkPowerDelta(LinkPowerDelta::try_parse_from(payload, offset)?),
+            _ => return Err(ParseError),
+        };
+
+        Ok(command)
+    }
+
+    /// Serialize the command into `buffer` as the payload of an NWK command
+    /// frame, returning the number of bytes written.
+    pub fn write_into(&self, buffer: &mut [u8]) -> Result<usize, WriteError> {
+        let offset = &mut 0;
+
+        buffer.write_with::<u8>(offset, self.command_id(), LE)?;
+        match self {
+            NwkCommand::RouteRequest(command) => command.write_into(buffer, offset)?,
+            NwkCommand::RouteReply(command) => command.write_into(buffer, offset)?,
+            NwkCommand::NetworkStatus(command) => command.write_into(buffer, offset)?,
+            NwkCommand::Leave(command) => buffer.write_with::<u8>(offset, u8::from(command), LE)?,
+            NwkCommand::RouteRecord(command) => command.write_into(buffer, offset)?,
+            NwkCommand::RejoinRequest(capability_information) => {
+                buffer.write_with::<u8>(offset, u8::from(capability_information), LE)?
+            }
+            NwkCommand::RejoinResponse(command) => command.write_into(buffer, offset)?,
+            NwkCommand::LinkStatus(command) => command.write_into(buffer, offset)?,
+            NwkCommand::NetworkReport(command) => command.write_into(buffer, offset)?,
+            NwkCommand::NetworkUpdate(command) => command.write_into(buffer, offset)?,
+            NwkCommand::EndDeviceTimeoutRequest(command) => command.write_into(buffer, offset)?,
+            NwkCommand::EndDeviceTimeoutResponse(command) => command.write_into(buffer, offset)?,
+            NwkCommand::LinkPowerDelta(command) => command.write_into(buffer, offset)?,
+        }
+
+        Ok(*offset)
+    }
+
+    pub fn command_id(&self) -> u8 {
+        match self {
+            NwkCommand::RouteRequest(_) => 0x01,
+            NwkCommand::RouteReply(_) => 0x02,
+            NwkCommand::NetworkStatus(_) => 0x03,
+            NwkCommand::Leave(_) => 0x04,
+            NwkCommand::RouteRecord(_) => 0x05,
+            NwkCommand::RejoinRequest(_) => 0x06,
+            NwkCommand::RejoinResponse(_) => 0x07,
+            NwkCommand::LinkStatus(_) => 0x08,
+            NwkCommand::NetworkReport(_) => 0x09,
+            NwkCommand::NetworkUpdate(_) => 0x0a,
+            NwkCommand::EndDeviceTimeoutRequest(_) => 0x0b,
+            NwkCommand::EndDeviceTimeoutResponse(_) => 0x0c,
+            NwkCommand::LinkPowerDelta(_) => 0x0d,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ManyToOne {
+    NotManyToOne,
+    /// The sender keeps a route record table.
+    RouteRecordTableSupported,
+    RouteRecordTableNotSupported,
+}
+impl TryFrom<u8> for ManyToOne {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(ManyToOne::NotManyToOne),
+            0b01 => Ok(ManyToOne::RouteRecordTableSupported),
+            0b10 => Ok(ManyToOne::RouteRecordTableNotSupported),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteRequest {
+    pub many_to_one: ManyToOne,
+    pub multicast: bool,
+    pub route_request_identifier: u8,
+    pub destination_address: u16,
+    pub path_cost: u8,
+    pub extended_destination: Option<u64>,
+}
+impl RouteRequest {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let options = payload.read_with::<u8>(offset, LE)?;
+        let many_to_one = ManyToOne::try_from((options >> 3) & 0b11)?;
+        let destination_ieee_present = ((options >> 5) & 1) == 1;
+        let multicast = ((options >> 6) & 1) == 1;
+
+        let route_request_identifier = payload.read_with::<u8>(offset, LE)?;
+        let destination_address = payload.read_with::<u16>(offset, LE)?;
+        let path_cost = payload.read_with::<u8>(offset, LE)?;
+        let extended_destination = match destination_ieee_present {
+            true => Some(payload.read_with::<u64>(offset, LE)?),
+            false => None,
+        };
+
+        Ok(Self {
+            many_to_one,
+            multicast,
+            route_request_identifier,
+            destination_address,
+            path_cost,
+            extended_destination,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let many_to_one: u8 = match self.many_to_one {
+            ManyToOne::NotManyToOne => 0b00,
+            ManyToOne::RouteRecordTableSupported => 0b01,
+            ManyToOne::RouteRecordTableNotSupported => 0b10,
+        };
+        let options = (many_to_one << 3)
+            | ((self.extended_destination.is_some() as u8) << 5)
+            | ((self.multicast as u8) << 6);
+
+        buffer.write_with::<u8>(offset, options, LE)?;
+        buffer.write_with::<u8>(offset, self.route_request_identifier, LE)?;
+        buffer.write_with::<u16>(offset, self.destination_address, LE)?;
+        buffer.write_with::<u8>(offset, self.path_cost, LE)?;
+        if let Some(extended_destination) = self.extended_destination {
+            buffer.write_with::<u64>(offset, extended_destination, LE)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteReply {
+    pub multicast: bool,
+    pub route_request_identifier: u8,
+    pub originator_address: u16,
+    pub responder_address: u16,
+    pub path_cost: u8,
+    pub extended_originator: Option<u64>,
+    pub extended_responder: Option<u64>,
+}
+impl RouteReply {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let options = payload.read_with::<u8>(offset, LE)?;
+        let originator_ieee_present = ((options >> 4) & 1) == 1;
+        let responder_ieee_present = ((options >> 5) & 1) == 1;
+        let multicast = ((options >> 6) & 1) == 1;
+
+        let route_request_identifier = payload.read_with::<u8>(offset, LE)?;
+        let originator_address = payload.read_with::<u16>(offset, LE)?;
+        let responder_address = payload.read_with::<u16>(offset, LE)?;
+        let path_cost = payload.read_with::<u8>(offset, LE)?;
+        let extended_originator = match originator_ieee_present {
+            true => Some(payload.read_with::<u64>(offset, LE)?),
+            false => None,
+        };
+        let extended_responder = match responder_ieee_present {
+            true => Some(payload.read_with::<u64>(offset, LE)?),
+            false => None,
+        };
+
+        Ok(Self {
+            multicast,
+            route_request_identifier,
+            originator_address,
+            responder_address,
+            path_cost,
+            extended_originator,
+            extended_responder,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let options = ((self.extended_originator.is_some() as u8) << 4)
+            | ((self.extended_responder.is_some() as u8) << 5)
+            | ((self.multicast as u8) << 6);
+
+        buffer.write_with::<u8>(offset, options, LE)?;
+        buffer.write_with::<u8>(offset, self.route_request_identifier, LE)?;
+        buffer.write_with::<u16>(offset, self.originator_address, LE)?;
+        buffer.write_with::<u16>(offset, self.responder_address, LE)?;
+        buffer.write_with::<u8>(offset, self.path_cost, LE)?;
+        if let Some(extended_originator) = self.extended_originator {
+            buffer.write_with::<u64>(offset, extended_originator, LE)?;
+        }
+        if let Some(extended_responder) = self.extended_responder {
+            buffer.write_with::<u64>(offset, extended_responder, LE)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum NetworkStatusCode {
+    NoRouteAvailable = 0x00,
+    TreeLinkFailure = 0x01,
+    NonTreeLinkFailure = 0x02,
+    LowBatteryLevel = 0x03,
+    NoRoutingCapacity = 0x04,
+    NoIndirectCapacity = 0x05,
+    IndirectTransactionExpiry = 0x06,
+    TargetDeviceUnavailable = 0x07,
+    TargetAddressUnallocated = 0x08,
+    ParentLinkFailure = 0x09,
+    ValidateRoute = 0x0a,
+    SourceRouteFailure = 0x0b,
+    ManyToOneRouteFailure = 0x0c,
+    AddressConflict = 0x0d,
+    VerifyAddresses = 0x0e,
+    PanIdentifierUpdate = 0x0f,
+    NetworkAddressUpdate = 0x10,
+    BadFrameCounter = 0x11,
+    BadKeySequenceNumber = 0x12,
+    UnknownCommand = 0x13,
+}
+impl TryFrom<u8> for NetworkStatusCode {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(NetworkStatusCode::NoRouteAvailable),
+            0x01 => Ok(NetworkStatusCode::TreeLinkFailure),
+            0x02 => Ok(NetworkStatusCode::NonTreeLinkFailure),
+            0x03 => Ok(NetworkStatusCode::LowBatteryLevel),
+            0x04 => Ok(NetworkStatusCode::NoRoutingCapacity),
+            0x05 => Ok(NetworkStatusCode::NoIndirectCapacity),
+            0x06 => Ok(NetworkStatusCode::IndirectTransactionExpiry),
+            0x07 => Ok(NetworkStatusCode::TargetDeviceUnavailable),
+            0x08 => Ok(NetworkStatusCode::TargetAddressUnallocated),
+            0x09 => Ok(NetworkStatusCode::ParentLinkFailure),
+            0x0a => Ok(NetworkStatusCode::ValidateRoute),
+            0x0b => Ok(NetworkStatusCode::SourceRouteFailure),
+            0x0c => Ok(NetworkStatusCode::ManyToOneRouteFailure),
+            0x0d => Ok(NetworkStatusCode::AddressConflict),
+            0x0e => Ok(NetworkStatusCode::VerifyAddresses),
+            0x0f => Ok(NetworkStatusCode::PanIdentifierUpdate),
+            0x10 => Ok(NetworkStatusCode::NetworkAddressUpdate),
+            0x11 => Ok(NetworkStatusCode::BadFrameCounter),
+            0x12 => Ok(NetworkStatusCode::BadKeySequenceNumber),
+            0x13 => Ok(NetworkStatusCode::UnknownCommand),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkStatus {
+    pub status_code: NetworkStatusCode,
+    /// Destination the status is about, not the destination of the frame.
+    pub destination_address: u16,
+}
+impl NetworkStatus {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status_code = NetworkStatusCode::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let destination_address = payload.read_with::<u16>(offset, LE)?;
+
+        Ok(Self {
+            status_code,
+            destination_address,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.status_code as u8, LE)?;
+        buffer.write_with::<u16>(offset, self.destination_address, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leave {
+    pub rejoin: bool,
+    /// Set when asking another device to leave, rather than announcing that
+    /// we are leaving.
+    pub request: bool,
+    pub remove_children: bool,
+}
+impl From<u8> for Leave {
+    fn from(options: u8) -> Self {
+        Self {
+            rejoin: ((options >> 5) & 1) == 1,
+            request: ((options >> 6) & 1) == 1,
+            remove_children: ((options >> 7) & 1) == 1,
+        }
+    }
+}
+impl From<&Leave> for u8 {
+    fn from(leave: &Leave) -> Self {
+        ((leave.rejoin as u8) << 5)
+            | ((leave.request as u8) << 6)
+            | ((leave.remove_children as u8) << 7)
+    }
+}
+
+/// List of 16-bit values sent as pairs of little-endian bytes, borrowed from
+/// the frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AddressList<'a> {
+    pub bytes: &'a [u8],
+}
+impl<'a> AddressList<'a> {
+    fn try_parse_from(
+        payload: &'a [u8],
+        offset: &mut usize,
+        count: usize,
+    ) -> Result<Self, ParseError> {
+        let bytes = payload.read_with::<&[u8]>(offset, Bytes::Len(count * 2))?;
+        Ok(Self { bytes })
+    }
+
+    /// Parse a list preceded by a one byte count.
+    pub(crate) fn try_parse_counted_from(
+        payload: &'a [u8],
+        offset: &mut usize,
+    ) -> Result<Self, ParseError> {
+        let count = payload.read_with::<u8>(offset, LE)?;
+        Self::try_parse_from(payload, offset, count as usize)
+    }
+
+    /// Write the list preceded by a one byte count.
+    pub(crate) fn write_counted_into(
+        &self,
+        buffer: &mut [u8],
+        offset: &mut usize,
+    ) -> Result<(), WriteError> {
+        let count = u8::try_from(self.len()).map_err(|_| WriteError)?;
+        buffer.write_with::<u8>(offset, count, LE)?;
+        buffer.write(offset, self.bytes)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u16> + 'a {
+        self.bytes
+            .chunks_exact(2)
+            .map(|address| u16::from_le_bytes([address[0], address[1]]))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteRecord<'a> {
+    pub relays: AddressList<'a>,
+}
+impl<'a> RouteRecord<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let relays = AddressList::try_parse_counted_from(payload, offset)?;
+        Ok(Self { relays })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        self.relays.write_counted_into(buffer, offset)
+    }
+}
+
+/// MAC capability information, as sent in association and rejoin requests.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CapabilityInformation {
+    pub alternate_pan_coordinator: bool,
+    pub full_function_device: bool,
+    pub mains_power: bool,
+    pub receiver_on_when_idle: bool,
+    pub security_capability: bool,
+    pub allocate_address: bool,
+}
+impl From<u8> for CapabilityInformation {
+    fn from(field: u8) -> Self {
+        Self {
+            alternate_pan_coordinator: (field & 1) == 1,
+            full_function_device: ((field >> 1) & 1) == 1,
+            mains_power: ((field >> 2) & 1) == 1,
+            receiver_on_when_idle: ((field >> 3) & 1) == 1,
+            security_capability: ((field >> 6) & 1) == 1,
+            allocate_address: ((field >> 7) & 1) == 1,
+        }
+    }
+}
+impl From<&CapabilityInformation> for u8 {
+    fn from(capability: &CapabilityInformation) -> Self {
+        (capability.alternate_pan_coordinator as u8)
+            | ((capability.full_function_device as u8) << 1)
+            | ((capability.mains_power as u8) << 2)
+            | ((capability.receiver_on_when_idle as u8) << 3)
+            | ((capability.security_capability as u8) << 6)
+            | ((capability.allocate_address as u8) << 7)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejoinResponse {
+    pub network_address: u16,
+    /// MAC association status, 0 on success.
+    pub rejoin_status: u8,
+}
+impl RejoinResponse {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let network_address = payload.read_with::<u16>(offset, LE)?;
+        let rejoin_status = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            network_address,
+            rejoin_status,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.network_address, LE)?;
+        buffer.write_with::<u8>(offset, self.rejoin_status, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkStatusEntry {
+    pub address: u16,
+    pub incoming_cost: u8,
+    pub outgoing_cost: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkStatus<'a> {
+    pub first_frame: bool,
+    pub last_frame: bool,
+    /// Link status list as sent over the air, 3 bytes per entry.
+    pub entry_list: &'a [u8],
+}
+impl<'a> LinkStatus<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let options = payload.read_with::<u8>(offset, LE)?;
+        let entry_count = (options & 0b11111) as usize;
+        let first_frame = ((options >> 5) & 1) == 1;
+        let last_frame = ((options >> 6) & 1) == 1;
+
+        let entry_list = payload.read_with::<&[u8]>(offset, Bytes::Len(entry_count * 3))?;
+
+        Ok(Self {
+            first_frame,
+            last_frame,
+            entry_list,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let entry_count = self.entry_list.len() / 3;
+        if self.entry_list.len() % 3 != 0 || entry_count > 0b11111 {
+            return Err(WriteError);
+        }
+
+        let options =
+            (entry_count as u8) | ((self.first_frame as u8) << 5) | ((self.last_frame as u8) << 6);
+        buffer.write_with::<u8>(offset, options, LE)?;
+        buffer.write(offset, self.entry_list)?;
+
+        Ok(())
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = LinkStatusEntry> + 'a {
+        self.entry_list
+            .chunks_exact(3)
+            .map(|entry| LinkStatusEntry {
+                address: u16::from_le_bytes([entry[0], entry[1]]),
+                incoming_cost: entry[2] & 0b111,
+                outgoing_cost: (entry[2] >> 4) & 0b111,
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkReport<'a> {
+    /// Report command identifier, 0 is a PAN identifier conflict.
+    pub report_command_identifier: u8,
+    pub extended_pan_id: u64,
+    /// PAN identifiers seen by the reporting device.
+    pub pan_ids: AddressList<'a>,
+}
+impl<'a> NetworkReport<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let options = payload.read_with::<u8>(offset, LE)?;
+        let report_count = (options & 0b11111) as usize;
+        let report_command_identifier = (options >> 5) & 0b111;
+
+        let extended_pan_id = payload.read_with::<u64>(offset, LE)?;
+        let pan_ids = AddressList::try_parse_from(payload, offset, report_count)?;
+
+        Ok(Self {
+            report_command_identifier,
+            extended_pan_id,
+            pan_ids,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        if self.pan_ids.len() > 0b11111 {
+            return Err(WriteError);
+        }
+
+        let options = (self.pan_ids.len() as u8) | ((self.report_command_identifier & 0b111) << 5);
+        buffer.write_with::<u8>(offset, options, LE)?;
+        buffer.write_with::<u64>(offset, self.extended_pan_id, LE)?;
+        buffer.write(offset, self.pan_ids.bytes)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkUpdate {
+    /// Update command identifier, 0 is a PAN identifier update.
+    pub update_command_identifier: u8,
+    pub extended_pan_id: u64,
+    pub update_id: u8,
+    pub new_pan_id: u16,
+}
+impl NetworkUpdate {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let options = payload.read_with::<u8>(offset, LE)?;
+        let update_count = options & 0b11111;
+        let update_command_identifier = (options >> 5) & 0b111;
+        // Only PAN identifier updates are defined, which carry a single PAN ID.
+        if update_count != 1 {
+            return Err(ParseError);
+        }
+
+        let extended_pan_id = payload.read_with::<u64>(offset, LE)?;
+        let update_id = payload.read_with::<u8>(offset, LE)?;
+        let new_pan_id = payload.read_with::<u16>(offset, LE)?;
+
+        Ok(Self {
+            update_command_identifier,
+            extended_pan_id,
+            update_id,
+            new_pan_id,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let options = 1 | ((self.update_command_identifier & 0b111) << 5);
+        buffer.write_with::<u8>(offset, options, LE)?;
+        buffer.write_with::<u64>(offset, self.extended_pan_id, LE)?;
+        buffer.write_with::<u8>(offset, self.update_id, LE)?;
+        buffer.write_with::<u16>(offset, self.new_pan_id, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndDeviceTimeoutRequest {
+    /// Index into the table of timeouts, see `timeout_seconds`.
+    pub requested_timeout: u8,
+    pub end_device_configuration: u8,
+}
+impl EndDeviceTimeoutRequest {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let requested_timeout = payload.read_with::<u8>(offset, LE)?;
+        let end_device_configuration = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            requested_timeout,
+            end_device_configuration,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.requested_timeout, LE)?;
+        buffer.write_with::<u8>(offset, self.end_device_configuration, LE)?;
+        Ok(())
+    }
+
+    /// The requested timeout in seconds, `None` for reserved values.
+    pub fn timeout_seconds(&self) -> Option<u32> {
+        match self.requested_timeout {
+            0 => Some(10),
+            // 2^n minutes for the rest of the values.
+            1..=14 => Some(60 * (1 << self.requested_timeout)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndDeviceTimeoutResponse {
+    /// 0 on success, 1 for an incorrect value.
+    pub status: u8,
+    pub mac_data_poll_keepalive_supported: bool,
+    pub end_device_timeout_request_keepalive_supported: bool,
+    pub power_negotiation_supported: bool,
+}
+impl EndDeviceTimeoutResponse {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = payload.read_with::<u8>(offset, LE)?;
+        let parent_information = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            status,
+            mac_data_poll_keepalive_supported: (parent_information & 1) == 1,
+            end_device_timeout_request_keepalive_supported: ((parent_information >> 1) & 1) == 1,
+            power_negotiation_supported: ((parent_information >> 2) & 1) == 1,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let parent_information = (self.mac_data_poll_keepalive_supported as u8)
+            | ((self.end_device_timeout_request_keepalive_supported as u8) << 1)
+            | ((self.power_negotiation_supported as u8) << 2);
+
+        buffer.write_with::<u8>(offset, self.status, LE)?;
+        buffer.write_with::<u8>(offset, parent_information, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PowerDeltaType {
+    Notification,
+    Request,
+    Response,
+    Reserved,
+}
+impl TryFrom<u8> for PowerDeltaType {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(PowerDeltaType::Notification),
+            0b01 => Ok(PowerDeltaType::Request),
+            0b10 => Ok(PowerDeltaType::Response),
+            0b11 => Ok(PowerDeltaType::Reserved),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerDelta {
+    pub device_address: u16,
+    /// Suggested change in transmit power, in dBm.
+    pub power_delta: i8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkPowerDelta<'a> {
+    pub delta_type: PowerDeltaType,
+    /// Power list as sent over the air, 3 bytes per entry.
+    pub power_list: &'a [u8],
+}
+impl<'a> LinkPowerDelta<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let options = payload.read_with::<u8>(offset, LE)?;
+        let delta_type = PowerDeltaType::try_from(options & 0b11)?;
+
+        let list_count = payload.read_with::<u8>(offset, LE)?;
+        let power_list = payload.read_with::<&[u8]>(offset, Bytes::Len(list_count as usize * 3))?;
+
+        Ok(Self {
+            delta_type,
+            power_list,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let list_count = u8::try_from(self.power_list.len() / 3).map_err(|_| WriteError)?;
+        if self.power_list.len() % 3 != 0 {
+            return Err(WriteError);
+        }
+
+        buffer.write_with::<u8>(offset, self.delta_type as u8, LE)?;
+        buffer.write_with::<u8>(offset, list_count, LE)?;
+        buffer.write(offset, self.power_list)?;
+
+        Ok(())
+    }
+
+    pub fn deltas(&self) -> impl Iterator<Item = PowerDelta> + 'a {
+        self.power_list.chunks_exact(3).map(|entry| PowerDelta {
+            device_address: u16::from_le_bytes([entry[0], entry[1]]),
+            power_delta: entry[2] as i8,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trips(bytes: &[u8]) -> NwkCommand<'_> {
+        let command = NwkCommand::try_parse_from(bytes).unwrap();
+
+        let mut buffer = [0u8; 128];
+        let len = command.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], bytes);
+
+        command
+    }
+
+    #[test]
+    fn parses_many_to_one_route_request() {
+        let command = assert_round_trips(b"\x01\x08\x2a\xfc\xff\x00");
+
+        assert_eq!(
+            command,
+            NwkCommand::RouteRequest(RouteRequest {
+                many_to_one: ManyToOne::RouteRecordTableSupported,
+                multicast: false,
+                route_request_identifier: 0x2a,
+                destination_address: 0xfffc,
+                path_cost: 0,
+                extended_destination: None,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_route_reply_with_extended_addresses() {
+        let command = assert_round_trips(
+            b"\x02\x30\x07\x00\x00\x34\x12\x05\
+\x01\x02\x03\x04\x05\x06\x07\x08\x11\x12\x13\x14\x15\x16\x17\x18",
+        );
+
+        match command {
+            NwkCommand::RouteReply(reply) => {
+                assert_eq!(reply.route_request_identifier, 7);
+                assert_eq!(reply.responder_address, 0x1234);
+                assert_eq!(reply.path_cost, 5);
+                assert_eq!(reply.extended_originator, Some(0x0807060504030201));
+                assert_eq!(reply.extended_responder, Some(0x1817161514131211));
+            }
+            _ => panic!("Expected a route reply"),
+        }
+    }
+
+    #[test]
+    fn parses_network_status() {
+        let command = assert_round_trips(b"\x03\x0b\x34\x12");
+
+        assert_eq!(
+            command,
+            NwkCommand::NetworkStatus(NetworkStatus {
+                status_code: NetworkStatusCode::SourceRouteFailure,
+                destination_address: 0x1234,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_leave() {
+        let command = assert_round_trips(b"\x04\x60");
+
+        assert_eq!(
+            command,
+            NwkCommand::Leave(Leave {
+                rejoin: true,
+                request: true,
+                remove_children: false,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_route_record() {
+        let command = assert_round_trips(b"\x05\x02\xcd\xab\x22\x11");
+
+        match command {
+            NwkCommand::RouteRecord(record) => {
+                assert_eq!(record.relays.len(), 2);
+                let mut relays = record.relays.iter();
+                assert_eq!(relays.next(), Some(0xabcd));
+                assert_eq!(relays.next(), Some(0x1122));
+                assert_eq!(relays.next(), None);
+            }
+            _ => panic!("Expected a route record"),
+        }
+    }
+
+    #[test]
+    fn parses_rejoin_request_and_response() {
+        let command = assert_round_trips(b"\x06\x8e");
+        assert_eq!(
+            command,
+            NwkCommand::RejoinRequest(CapabilityInformation {
+                alternate_pan_coordinator: false,
+                full_function_device: true,
+                mains_power: true,
+                receiver_on_when_idle: true,
+                security_capability: false,
+                allocate_address: true,
+            })
+        );
+
+        let command = assert_round_trips(b"\x07\x34\x12\x00");
+        assert_eq!(
+            command,
+            NwkCommand::RejoinResponse(RejoinResponse {
+                network_address: 0x1234,
+                rejoin_status: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_link_status() {
+        let command = assert_round_trips(b"\x08\x62\x00\x00\x11\x34\x12\x73");
+
+        match command {
+            NwkCommand::LinkStatus(link_status) => {
+                assert!(link_status.first_frame);
+                assert!(link_status.last_frame);
+
+                let mut entries = link_status.entries();
+                assert_eq!(
+                    entries.next(),
+                    Some(LinkStatusEntry {
+                        address: 0x0000,
+                        incoming_cost: 1,
+                        outgoing_cost: 1,
+                    })
+                );
+                assert_eq!(
+                    entries.next(),
+                    Some(LinkStatusEntry {
+                        address: 0x1234,
+                        incoming_cost: 3,
+                        outgoing_cost: 7,
+                    })
+                );
+                assert_eq!(entries.next(), None);
+            }
+            _ => panic!("Expected a link status"),
+        }
+    }
+
+    #[test]
+    fn parses_network_report_and_update() {
+        let command =
+            assert_round_trips(b"\x09\x02\x01\x02\x03\x04\x05\x06\x07\x08\x21\xd7\x22\xd7");
+        match command {
+            NwkCommand::NetworkReport(report) => {
+                assert_eq!(report.report_command_identifier, 0);
+                assert_eq!(report.extended_pan_id, 0x0807060504030201);
+                assert_eq!(report.pan_ids.iter().last(), Some(0xd722));
+            }
+            _ => panic!("Expected a network report"),
+        }
+
+        let command = assert_round_trips(b"\x0a\x01\x01\x02\x03\x04\x05\x06\x07\x08\x03\x23\xd7");
+        assert_eq!(
+            command,
+            NwkCommand::NetworkUpdate(NetworkUpdate {
+                update_command_identifier: 0,
+                extended_pan_id: 0x0807060504030201,
+                update_id: 3,
+                new_pan_id: 0xd723,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_end_device_timeout_commands() {
+        let command = assert_round_trips(b"\x0b\x03\x00");
+        match command {
+            NwkCommand::EndDeviceTimeoutRequest(request) => {
+                assert_eq!(request.timeout_seconds(), Some(480));
+            }
+            _ => panic!("Expected an end device timeout request"),
+        }
+
+        let command = assert_round_trips(b"\x0c\x00\x03");
+        assert_eq!(
+            command,
+            NwkCommand::EndDeviceTimeoutResponse(EndDeviceTimeoutResponse {
+                status: 0,
+                mac_data_poll_keepalive_supported: true,
+                end_device_timeout_request_keepalive_supported: true,
+                power_negotiation_supported: false,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_link_power_delta() {
+        let command = assert_round_trips(b"\x0d\x02\x01\x34\x12\xfd");
+
+        match command {
+            NwkCommand::LinkPowerDelta(link_power_delta) => {
+                assert_eq!(link_power_delta.delta_type, PowerDeltaType::Response);
+                let mut deltas = link_power_delta.deltas();
+                assert_eq!(
+                    deltas.next(),
+                    Some(PowerDelta {
+                        device_address: 0x1234,
+                        power_delta: -3,
+                    })
+                );
+                assert_eq!(deltas.next(), None);
+            }
+            _ => panic!("Expected a link power delta"),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_and_truncated_commands() {
+        assert!(NwkCommand::try_parse_from(b"\x42\x00").is_err());
+        assert!(NwkCommand::try_parse_from(b"\x01\x08\x2a\xfc").is_err());
+        assert!(NwkCommand::try_parse_from(b"\x05\x02\xcd\xab").is_err());
+        assert!(NwkCommand::try_parse_from(b"").is_err());
+    }
+}
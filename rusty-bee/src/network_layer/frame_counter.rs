@@ -0,0 +1,234 @@
+use super::security::{SecurityContext, SecurityError};
+use super::ZigbeePacket;
+use crate::table::Table;
+
+/// How many frames may be sent before the outgoing frame counter is
+/// persisted again, and so how far it jumps forward after a reboot.
+pub const FRAME_COUNTER_PERSIST_INTERVAL: u32 = 1024;
+
+#[derive(Debug, Clone, Copy)]
+struct IncomingFrameCounter {
+    source: u64,
+    frame_counter: u32,
+    /// Value of `IncomingFrameCounters::uses` when a frame from `source`
+    /// was last accepted, to find the least recently used entry.
+    last_used: u32,
+}
+
+/// Highest frame counter received from each device, keyed by extended
+/// address, used to reject replayed frames. When the table is full the
+/// device heard from least recently is forgotten.
+///
+/// Counters are only valid for the key they were received with, so the
+/// table should be cleared when switching to a new network key.
+#[derive(Debug, Clone, Default)]
+pub struct IncomingFrameCounters<const N: usize> {
+    counters: Table<IncomingFrameCounter, N>,
+    uses: u32,
+}
+impl<const N: usize> IncomingFrameCounters<N> {
+    pub const fn new() -> Self {
+        Self {
+            counters: Table::new(),
+            uses: 0,
+        }
+    }
+
+    /// Check the frame counter of an authenticated frame from `source`,
+    /// rejecting it if it isn't higher than the last one accepted.
+    ///
+    /// Must only be called after the frame was authenticated, otherwise
+    /// forged frames could push the counter up and block the device.
+    pub fn accept(&mut self, source: u64, frame_counter: u32) -> Result<(), SecurityError> {
+        self.uses = self.uses.wrapping_add(1);
+        let uses = self.uses;
+
+        if let Some(counter) = self.counters.find_mut(|counter| counter.source == source) {
+            if frame_counter <= counter.frame_counter {
+                return Err(SecurityError::ReplayedFrame);
+            }
+            counter.frame_counter = frame_counter;
+            counter.last_used = uses;
+            return Ok(());
+        }
+
+        let counter = IncomingFrameCounter {
+            source,
+            frame_counter,
+            last_used: uses,
+        };
+        if self.counters.insert(counter).is_err() {
+            let least_recently_used = self
+                .counters
+                .iter()
+                .max_by_key(|counter| uses.wrapping_sub(counter.last_used))
+                .map(|counter| counter.source);
+            self.counters
+                .remove_where(|counter| Some(counter.source) == least_recently_used);
+            // Can't fail, an entry was just removed.
+            let _ = self.counters.insert(counter);
+        }
+
+        Ok(())
+    }
+
+    /// Check the frame counter of a decrypted NWK frame, see `accept`.
+    pub fn accept_packet(&mut self, packet: &ZigbeePacket) -> Result<(), SecurityError> {
+        let header = packet
+            .security_header
+            .as_ref()
+            .ok_or(SecurityError::MalformedFrame)?;
+        let source = header
+            .extended_source
+            .or(packet.extended_source)
+            .ok_or(SecurityError::MalformedFrame)?;
+
+        self.accept(source, header.frame_counter)
+    }
+
+    /// The last frame counter accepted from `source`.
+    pub fn frame_counter(&self, source: u64) -> Option<u32> {
+        self.counters
+            .find(|counter| counter.source == source)
+            .map(|counter| counter.frame_counter)
+    }
+
+    pub fn clear(&mut self) {
+        self.counters.remove_where(|_| true);
+    }
+
+    pub fn len(&self) -> usize {
+        self.counters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counters.is_empty()
+    }
+}
+
+/// Keeps the outgoing frame counter of a `SecurityContext` from ever going
+/// back to a value that was already sent, even across reboots.
+///
+/// The counter is persisted every `FRAME_COUNTER_PERSIST_INTERVAL` frames,
+/// and after a reboot it continues from the persisted value plus that
+/// interval, skipping past any frame sent since it was last persisted.
+#[derive(Debug, Clone)]
+pub struct OutgoingFrameCounter {
+    /// Counter value at which it next needs to be persisted.
+    next_persist: u32,
+}
+impl OutgoingFrameCounter {
+    /// Restore the outgoing frame counter of `context` from the value last
+    /// persisted, or keep it as it is if none was.
+    pub fn restore(context: &mut SecurityContext, persisted: Option<u32>) -> Self {
+        if let Some(persisted) = persisted {
+            context.outgoing_frame_counter =
+                persisted.saturating_add(FRAME_COUNTER_PERSIST_INTERVAL);
+        }
+
+        // The restored value is persisted right away, so another reboot
+        // jumps forward again.
+        Self {
+            next_persist: context.outgoing_frame_counter,
+        }
+    }
+
+    /// The value to persist, if it is due. Needs to be called after every
+    /// frame sent, and the value stored before sending the next one.
+    pub fn poll(&mut self, context: &SecurityContext) -> Option<u32> {
+        let frame_counter = context.outgoing_frame_counter;
+        if frame_counter < self.next_persist {
+            return None;
+        }
+
+        self.next_persist = frame_counter.saturating_add(FRAME_COUNTER_PERSIST_INTERVAL);
+        Some(frame_counter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_replayed_frames() {
+        let mut counters = IncomingFrameCounters::<2>::new();
+
+        counters.accept(0x1111, 5).unwrap();
+        assert_eq!(
+            counters.accept(0x1111, 5),
+            Err(SecurityError::ReplayedFrame)
+        );
+        assert_eq!(
+            counters.accept(0x1111, 4),
+            Err(SecurityError::ReplayedFrame)
+        );
+        counters.accept(0x1111, 6).unwrap();
+        counters.accept(0x2222, 0).unwrap();
+        assert_eq!(counters.frame_counter(0x1111), Some(6));
+
+        counters.clear();
+        assert!(counters.is_empty());
+        counters.accept(0x1111, 1).unwrap();
+    }
+
+    #[test]
+    fn evicts_least_recently_used_source() {
+        let mut counters = IncomingFrameCounters::<2>::new();
+        counters.accept(0x1111, 1).unwrap();
+        counters.accept(0x2222, 1).unwrap();
+        counters.accept(0x1111, 2).unwrap();
+
+        counters.accept(0x3333, 1).unwrap();
+        assert_eq!(counters.len(), 2);
+        assert_eq!(counters.frame_counter(0x1111), Some(2));
+        assert_eq!(counters.frame_counter(0x2222), None);
+        assert_eq!(counters.frame_counter(0x3333), Some(1));
+    }
+
+    #[test]
+    fn checks_decrypted_packets() {
+        let mut counters = IncomingFrameCounters::<1>::new();
+        let frame = b"\x08\x02\xfd\xff\x00\x00\x1e\x2a\x28\xe7\x08\x14\x01\
+\x9e\xc0\x81\x08\x01\x88\x17\x00\x00\x01\x02\x03\x04";
+        let packet = ZigbeePacket::try_parse_from(frame).unwrap();
+
+        counters.accept_packet(&packet).unwrap();
+        assert_eq!(
+            counters.frame_counter(0x00_17_88_01_08_81_c0_9e),
+            Some(18090215)
+        );
+        assert_eq!(
+            counters.accept_packet(&packet),
+            Err(SecurityError::ReplayedFrame)
+        );
+    }
+
+    #[test]
+    fn persists_outgoing_frame_counter() {
+        let mut context = SecurityContext::new(0x00_17_88_01_08_81_c0_9e);
+        let mut persistence = OutgoingFrameCounter::restore(&mut context, None);
+        assert_eq!(persistence.poll(&context), Some(0));
+
+        context.outgoing_frame_counter = FRAME_COUNTER_PERSIST_INTERVAL - 1;
+        assert_eq!(persistence.poll(&context), None);
+        context.outgoing_frame_counter += 1;
+        assert_eq!(
+            persistence.poll(&context),
+            Some(FRAME_COUNTER_PERSIST_INTERVAL)
+        );
+
+        // After a reboot the counter jumps past anything sent since.
+        let mut context = SecurityContext::new(0x00_17_88_01_08_81_c0_9e);
+        let mut persistence =
+            OutgoingFrameCounter::restore(&mut context, Some(FRAME_COUNTER_PERSIST_INTERVAL));
+        assert_eq!(
+            context.outgoing_frame_counter,
+            2 * FRAME_COUNTER_PERSIST_INTERVAL
+        );
+        assert_eq!(
+            persistence.poll(&context),
+            Some(2 * FRAME_COUNTER_PERSIST_INTERVAL)
+        );
+    }
+}
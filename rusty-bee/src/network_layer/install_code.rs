@@ -0,0 +1,131 @@
+use super::security::{mmo_hash, SecurityKey};
+
+/// Length of the CRC appended to an install code.
+const CRC_LENGTH: usize = 2;
+/// Longest install code, including its CRC.
+const MAX_INSTALL_CODE_LENGTH: usize = 16 + CRC_LENGTH;
+
+#[derive(Debug, PartialEq)]
+pub enum InstallCodeError {
+    /// Install codes are 6, 8, 12 or 16 bytes long, followed by the CRC.
+    InvalidLength,
+    /// The CRC doesn't match the code, it was probably mistyped.
+    InvalidCrc,
+}
+
+/// Code printed on a device, from which the link key it joins the network
+/// with is derived. Holds the code followed by its CRC, as printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstallCode {
+    bytes: [u8; MAX_INSTALL_CODE_LENGTH],
+    length: usize,
+}
+impl InstallCode {
+    /// The code and its CRC.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.length]
+    }
+
+    /// The preconfigured link key the device joins with, the MMO hash of
+    /// the code and its CRC.
+    pub fn link_key(&self) -> SecurityKey {
+        mmo_hash(self.as_bytes())
+    }
+}
+impl TryFrom<&[u8]> for InstallCode {
+    type Error = InstallCodeError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if !matches!(bytes.len().checked_sub(CRC_LENGTH), Some(6 | 8 | 12 | 16)) {
+            return Err(InstallCodeError::InvalidLength);
+        }
+
+        let (code, crc) = bytes.split_at(bytes.len() - CRC_LENGTH);
+        if crc16(code).to_le_bytes() != crc {
+            return Err(InstallCodeError::InvalidCrc);
+        }
+
+        let mut install_code = Self {
+            bytes: [0; MAX_INSTALL_CODE_LENGTH],
+            length: bytes.len(),
+        };
+        install_code.bytes[..bytes.len()].copy_from_slice(bytes);
+        Ok(install_code)
+    }
+}
+
+/// CRC-16/X-25, the CRC appended to install codes.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0x8408,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_layer::key_store::{KeyStore, LinkKeyType};
+
+    const INSTALL_CODE: &[u8] =
+        b"\x83\xfe\xd3\x40\x7a\x93\x97\x23\xa5\xc6\x39\xb2\x69\x16\xd5\x05\xc3\xb5";
+
+    #[test]
+    fn validates_install_code() {
+        let install_code = InstallCode::try_from(INSTALL_CODE).unwrap();
+        assert_eq!(install_code.as_bytes(), INSTALL_CODE);
+
+        let mut mistyped = [0u8; 18];
+        mistyped.copy_from_slice(INSTALL_CODE);
+        mistyped[3] ^= 0x01;
+        assert_eq!(
+            InstallCode::try_from(&mistyped[..]),
+            Err(InstallCodeError::InvalidCrc)
+        );
+
+        for length in [0, 1, 7, 9, 17, 19] {
+            let bytes = [0u8; 19];
+            assert_eq!(
+                InstallCode::try_from(&bytes[..length]),
+                Err(InstallCodeError::InvalidLength)
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_short_install_codes() {
+        for length in [6, 8, 12] {
+            let mut bytes = [0u8; 14];
+            bytes[..length].copy_from_slice(&INSTALL_CODE[..length]);
+            let crc = crc16(&bytes[..length]).to_le_bytes();
+            bytes[length..length + CRC_LENGTH].copy_from_slice(&crc);
+
+            let install_code = InstallCode::try_from(&bytes[..length + CRC_LENGTH]).unwrap();
+            assert_eq!(install_code.as_bytes().len(), length + CRC_LENGTH);
+        }
+    }
+
+    #[test]
+    fn registers_derived_link_key() {
+        let install_code = InstallCode::try_from(INSTALL_CODE).unwrap();
+        let mut store = KeyStore::<1>::new();
+
+        store
+            .add_install_code(0x00_17_88_01_08_81_c0_9e, &install_code)
+            .unwrap();
+
+        let link_key = store.link_key(0x00_17_88_01_08_81_c0_9e).unwrap();
+        assert_eq!(link_key.key_type, LinkKeyType::InstallCode);
+        assert_eq!(
+            link_key.key,
+            *b"\x66\xb6\x90\x09\x81\xe1\xee\x3c\xa4\x20\x6b\x6b\x86\x1c\x02\xbb"
+        );
+    }
+}
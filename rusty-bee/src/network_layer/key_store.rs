@@ -0,0 +1,433 @@
+use super::install_code::InstallCode;
+use super::security::{self, SecurityContext, SecurityError, SecurityKey};
+use super::ZigbeePacket;
+use crate::aps::commands::{ApsCommand, TransportKey};
+use crate::aps::{self, ApsFrame};
+use crate::table::{Table, TableFull};
+
+/// A network key and the sequence number it is sent with, which is the
+/// key message number in the security header of frames secured with it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NetworkKey {
+    pub sequence_number: u8,
+    pub key: SecurityKey,
+}
+
+/// How a device's link key was established.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkKeyType {
+    /// Configured out of band, e.g. a well known default key.
+    Preconfigured,
+    /// Derived from the device's install code.
+    InstallCode,
+    /// A unique key shared with the trust center, delivered by it with a
+    /// Transport-Key command.
+    TrustCenter,
+    /// A key shared with another device than the trust center, delivered
+    /// by the trust center with a Transport-Key command.
+    Application,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkKey {
+    /// Extended address of the device the key is shared with.
+    pub extended_address: u64,
+    pub key_type: LinkKeyType,
+    pub key: SecurityKey,
+}
+
+/// The keys this device secures frames with: the active network key, the
+/// alternate one the network is about to switch to, and link keys shared
+/// with other devices.
+#[derive(Debug, Clone, Default)]
+pub struct KeyStore<const N: usize> {
+    active_network_key: Option<NetworkKey>,
+    alternate_network_key: Option<NetworkKey>,
+    link_keys: Table<LinkKey, N>,
+}
+impl<const N: usize> KeyStore<N> {
+    pub const fn new() -> Self {
+        Self {
+            active_network_key: None,
+            alternate_network_key: None,
+            link_keys: Table::new(),
+        }
+    }
+
+    pub fn active_network_key(&self) -> Option<&NetworkKey> {
+        self.active_network_key.as_ref()
+    }
+
+    pub fn alternate_network_key(&self) -> Option<&NetworkKey> {
+        self.alternate_network_key.as_ref()
+    }
+
+    /// Store a network key received from the trust center. The first one
+    /// becomes the active key, later ones replace the alternate key until
+    /// the network switches to them.
+    pub fn add_network_key(&mut self, network_key: NetworkKey) {
+        match self.active_network_key {
+            None => self.active_network_key = Some(network_key),
+            Some(active) if active.sequence_number == network_key.sequence_number => {
+                self.active_network_key = Some(network_key)
+            }
+            Some(_) => self.alternate_network_key = Some(network_key),
+        }
+    }
+
+    /// Make the network key with `sequence_number` the active one, keeping
+    /// the previous key as the alternate so frames still in flight can be
+    /// decrypted. Returns whether the key was known.
+    pub fn switch_network_key(&mut self, sequence_number: u8) -> bool {
+        match self.alternate_network_key {
+            Some(alternate) if alternate.sequence_number == sequence_number => {
+                self.alternate_network_key = self.active_network_key.replace(alternate);
+                true
+            }
+            _ => self
+                .active_network_key
+                .is_some_and(|active| active.sequence_number == sequence_number),
+        }
+    }
+
+    /// The network key with `sequence_number`, either the active or the
+    /// alternate one.
+    pub fn network_key(&self, sequence_number: u8) -> Option<&SecurityKey> {
+        [&self.active_network_key, &self.alternate_network_key]
+            .into_iter()
+            .flatten()
+            .find(|network_key| network_key.sequence_number == sequence_number)
+            .map(|network_key| &network_key.key)
+    }
+
+    /// Store the link key shared with the device with `extended_address`,
+    /// replacing any previous one.
+    pub fn set_link_key(
+        &mut self,
+        extended_address: u64,
+        key_type: LinkKeyType,
+        key: SecurityKey,
+    ) -> Result<(), TableFull> {
+        self.link_keys.upsert(
+            LinkKey {
+                extended_address,
+                key_type,
+                key,
+            },
+            |link_key| link_key.extended_address == extended_address,
+        )
+    }
+
+    /// Store the link key derived from `install_code` for the device with
+    /// `extended_address`, which it will use to join the network.
+    pub fn add_install_code(
+        &mut self,
+        extended_address: u64,
+        install_code: &InstallCode,
+    ) -> Result<(), TableFull> {
+        self.set_link_key(
+            extended_address,
+            LinkKeyType::InstallCode,
+            install_code.link_key(),
+        )
+    }
+
+    pub fn remove_link_key(&mut self, extended_address: u64) -> bool {
+        self.link_keys
+            .remove_where(|link_key| link_key.extended_address == extended_address)
+            > 0
+    }
+
+    pub fn link_key(&self, extended_address: u64) -> Option<&LinkKey> {
+        self.link_keys
+            .find(|link_key| link_key.extended_address == extended_address)
+    }
+
+    pub fn link_keys(&self) -> impl Iterator<Item = &LinkKey> {
+        self.link_keys.iter()
+    }
+
+    /// Update the store from a decrypted APS command received from the
+    /// trust center. Only Transport-Key and Switch-Key commands change it,
+    /// others are ignored.
+    pub fn handle_command(&mut self, command: &ApsCommand) -> Result<(), TableFull> {
+        match command {
+            ApsCommand::TransportKey(TransportKey::StandardNetworkKey {
+                key,
+                sequence_number,
+                ..
+            }) => self.add_network_key(NetworkKey {
+                sequence_number: *sequence_number,
+                key: *key,
+            }),
+            ApsCommand::TransportKey(TransportKey::TrustCenterLinkKey {
+                key,
+                source_address,
+                ..
+            }) => self.set_link_key(*source_address, LinkKeyType::TrustCenter, *key)?,
+            ApsCommand::TransportKey(TransportKey::ApplicationLinkKey {
+                key,
+                partner_address,
+                ..
+            }) => self.set_link_key(*partner_address, LinkKeyType::Application, *key)?,
+            ApsCommand::SwitchKey(switch_key) => {
+                self.switch_network_key(switch_key.sequence_number);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Secure `packet` with the active network key, see
+    /// `SecurityContext::encrypt_frame`.
+    pub fn encrypt_frame(
+        &self,
+        context: &mut SecurityContext,
+        packet: &ZigbeePacket,
+        buffer: &mut [u8],
+    ) -> Result<usize, SecurityError> {
+        let network_key = self
+            .active_network_key
+            .as_ref()
+            .ok_or(SecurityError::UnknownKey)?;
+        context.key_sequence_number = network_key.sequence_number;
+        context.encrypt_frame(packet, &network_key.key, buffer)
+    }
+
+    /// Decrypt a secured NWK frame with the network key matching the key
+    /// message number in its security header, see
+    /// `security::decrypt_frame`.
+    pub fn decrypt_frame<'a>(
+        &self,
+        frame: &'a mut [u8],
+        security_level: u8,
+    ) -> Result<ZigbeePacket<'a>, SecurityError> {
+        let key = {
+            let packet = ZigbeePacket::try_parse_with_security_level(frame, security_level)?;
+            let header = packet
+                .security_header
+                .as_ref()
+                .ok_or(SecurityError::MalformedFrame)?;
+            *self
+                .network_key(header.key_message_number)
+                .ok_or(SecurityError::UnknownKey)?
+        };
+
+        security::decrypt_frame(frame, &key, security_level)
+    }
+
+    /// Decrypt a secured APS frame sent by the device with
+    /// `extended_source`, with the link key shared with it or, for frames
+    /// secured with the network key, the network key matching the key
+    /// message number. See `aps::security::decrypt_frame`.
+    pub fn decrypt_aps_frame<'a>(
+        &self,
+        frame: &'a mut [u8],
+        security_level: u8,
+        extended_source: u64,
+    ) -> Result<ApsFrame<'a>, SecurityError> {
+        let key = {
+            let parsed = ApsFrame::try_parse_with_security_level(frame, security_level)?;
+            let header = parsed
+                .security_header
+                .as_ref()
+                .ok_or(SecurityError::MalformedFrame)?;
+            let source = header.extended_source.unwrap_or(extended_source);
+            let key = match header.security_control_field.key_identifier {
+                security::KeyIdentifier::Network => self.network_key(header.key_message_number),
+                _ => self.link_key(source).map(|link_key| &link_key.key),
+            };
+            *key.ok_or(SecurityError::UnknownKey)?
+        };
+
+        aps::security::decrypt_frame(frame, &key, security_level, Some(extended_source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aps::commands::SwitchKey;
+    use crate::aps::security::{encrypt_frame, ZIGBEE_ALLIANCE_09_KEY};
+    use crate::network_layer::security::{KeyIdentifier, DEFAULT_SECURITY_LEVEL};
+
+    const TRUST_CENTER_ADDRESS: u64 = 0x00_12_4b_00_01_02_03_04;
+    const FIRST_KEY: SecurityKey =
+        *b"\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10";
+    const SECOND_KEY: SecurityKey =
+        *b"\x11\x12\x13\x14\x15\x16\x17\x18\x19\x1a\x1b\x1c\x1d\x1e\x1f\x20";
+
+    fn packet(payload: &[u8]) -> ZigbeePacket<'_> {
+        ZigbeePacket {
+            frame_control_field: crate::network_layer::FrameControlField::from(0x0008),
+            destination: 0xfffd,
+            source: 0x0000,
+            radius: 0x1e,
+            sequence_number: 0x2a,
+            extended_destination: None,
+            extended_source: None,
+            multicast_control: None,
+            source_route: None,
+            security_header: None,
+            payload,
+        }
+    }
+
+    #[test]
+    fn switches_network_keys() {
+        let mut store = KeyStore::<1>::new();
+        store
+            .handle_command(&ApsCommand::TransportKey(
+                TransportKey::StandardNetworkKey {
+                    key: FIRST_KEY,
+                    sequence_number: 0,
+                    destination_address: 0,
+                    source_address: TRUST_CENTER_ADDRESS,
+                },
+            ))
+            .unwrap();
+        store.add_network_key(NetworkKey {
+            sequence_number: 1,
+            key: SECOND_KEY,
+        });
+        assert_eq!(store.active_network_key().unwrap().sequence_number, 0);
+        assert_eq!(store.network_key(1), Some(&SECOND_KEY));
+        assert!(!store.switch_network_key(2));
+
+        store
+            .handle_command(&ApsCommand::SwitchKey(SwitchKey { sequence_number: 1 }))
+            .unwrap();
+        assert_eq!(store.active_network_key().unwrap().key, SECOND_KEY);
+        assert_eq!(store.alternate_network_key().unwrap().key, FIRST_KEY);
+        assert!(store.switch_network_key(1));
+    }
+
+    #[test]
+    fn stores_transported_link_keys() {
+        let mut store = KeyStore::<2>::new();
+        store
+            .handle_command(&ApsCommand::TransportKey(
+                TransportKey::TrustCenterLinkKey {
+                    key: FIRST_KEY,
+                    destination_address: 0x1234,
+                    source_address: TRUST_CENTER_ADDRESS,
+                },
+            ))
+            .unwrap();
+        store
+            .handle_command(&ApsCommand::TransportKey(
+                TransportKey::ApplicationLinkKey {
+                    key: SECOND_KEY,
+                    partner_address: 0x5678,
+                    initiator: true,
+                },
+            ))
+            .unwrap();
+
+        let trust_center = store.link_key(TRUST_CENTER_ADDRESS).unwrap();
+        assert_eq!(trust_center.key_type, LinkKeyType::TrustCenter);
+        assert_eq!(trust_center.key, FIRST_KEY);
+        let partner = store.link_key(0x5678).unwrap();
+        assert_eq!(partner.key_type, LinkKeyType::Application);
+        assert_eq!(partner.key, SECOND_KEY);
+    }
+
+    #[test]
+    fn decrypts_with_key_of_sequence_number() {
+        let mut store = KeyStore::<1>::new();
+        store.add_network_key(NetworkKey {
+            sequence_number: 0,
+            key: FIRST_KEY,
+        });
+        let mut context = SecurityContext::new(TRUST_CENTER_ADDRESS);
+        let mut old_frame = [0u8; 64];
+        let old_length = store
+            .encrypt_frame(&mut context, &packet(b"\x01\x02"), &mut old_frame)
+            .unwrap();
+
+        store.add_network_key(NetworkKey {
+            sequence_number: 1,
+            key: SECOND_KEY,
+        });
+        store.switch_network_key(1);
+        let mut new_frame = [0u8; 64];
+        let new_length = store
+            .encrypt_frame(&mut context, &packet(b"\x03\x04"), &mut new_frame)
+            .unwrap();
+        assert_eq!(context.key_sequence_number, 1);
+
+        let old = store
+            .decrypt_frame(&mut old_frame[..old_length], DEFAULT_SECURITY_LEVEL)
+            .unwrap();
+        assert_eq!(old.payload, b"\x01\x02");
+        let new = store
+            .decrypt_frame(&mut new_frame[..new_length], DEFAULT_SECURITY_LEVEL)
+            .unwrap();
+        assert_eq!(new.payload, b"\x03\x04");
+
+        let mut unknown = KeyStore::<1>::new();
+        unknown.add_network_key(NetworkKey {
+            sequence_number: 2,
+            key: FIRST_KEY,
+        });
+        assert_eq!(
+            unknown
+                .decrypt_frame(&mut new_frame[..new_length], DEFAULT_SECURITY_LEVEL)
+                .unwrap_err(),
+            SecurityError::UnknownKey
+        );
+    }
+
+    #[test]
+    fn decrypts_with_link_key_of_source() {
+        let mut store = KeyStore::<1>::new();
+        store
+            .set_link_key(
+                TRUST_CENTER_ADDRESS,
+                LinkKeyType::Preconfigured,
+                ZIGBEE_ALLIANCE_09_KEY,
+            )
+            .unwrap();
+        assert_eq!(
+            store.set_link_key(0x1234, LinkKeyType::InstallCode, FIRST_KEY),
+            Err(TableFull)
+        );
+
+        let frame = ApsFrame::try_parse_from(b"\x01\x2a\x09\x01").unwrap();
+        let mut context = SecurityContext::new(TRUST_CENTER_ADDRESS);
+        let mut buffer = [0u8; 64];
+        let length = encrypt_frame(
+            &frame,
+            KeyIdentifier::KeyTransport,
+            &ZIGBEE_ALLIANCE_09_KEY,
+            &mut context,
+            &mut buffer,
+        )
+        .unwrap();
+
+        let decrypted = store
+            .decrypt_aps_frame(
+                &mut buffer[..length],
+                DEFAULT_SECURITY_LEVEL,
+                TRUST_CENTER_ADDRESS,
+            )
+            .unwrap();
+        assert_eq!(
+            decrypted.command().unwrap(),
+            ApsCommand::SwitchKey(SwitchKey { sequence_number: 1 })
+        );
+
+        assert!(store.remove_link_key(TRUST_CENTER_ADDRESS));
+        assert_eq!(
+            store
+                .decrypt_aps_frame(
+                    &mut buffer[..length],
+                    DEFAULT_SECURITY_LEVEL,
+                    TRUST_CENTER_ADDRESS
+                )
+                .unwrap_err(),
+            SecurityError::UnknownKey
+        );
+    }
+}
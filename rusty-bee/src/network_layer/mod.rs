@@ -0,0 +1,683 @@
+use core::array::TryFromSliceError;
+
+use self::commands::NwkCommand;
+use self::security::SecurityHeader;
+use byte::ctx::Bytes;
+use byte::{BytesExt, LE};
+
+pub mod address_map;
+pub mod commands;
+pub mod frame_counter;
+pub mod install_code;
+pub mod key_store;
+pub mod neighbor_table;
+pub mod routing_table;
+pub mod security;
+
+#[derive(Debug, Clone)]
+pub struct ParseError;
+impl From<()> for ParseError {
+    fn from(_: ()) -> Self {
+        Self
+    }
+}
+impl From<byte::Error> for ParseError {
+    fn from(_: byte::Error) -> Self {
+        Self
+    }
+}
+impl From<TryFromSliceError> for ParseError {
+    fn from(_: TryFromSliceError) -> Self {
+        Self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WriteError;
+impl From<byte::Error> for WriteError {
+    fn from(_: byte::Error) -> Self {
+        Self
+    }
+}
+
+#[derive(Debug)]
+pub struct ZigbeePacket<'a> {
+    pub frame_control_field: FrameControlField,
+    pub destination: u16,
+    pub source: u16,
+    pub radius: u8,
+    pub sequence_number: u8,
+    pub extended_destination: Option<u64>,
+    pub extended_source: Option<u64>,
+    /// Present for multicast frames, which are sent to the group in
+    /// `destination`.
+    pub multicast_control: Option<MulticastControl>,
+    pub source_route: Option<SourceRoute<'a>>,
+    pub security_header: Option<security::SecurityHeader<'a>>,
+    pub payload: &'a [u8],
+}
+impl<'a> ZigbeePacket<'a> {
+    /// Parse an NWK frame, assuming the network uses the default security
+    /// level for frames that don't specify their own.
+    pub fn try_parse_from(packet: &'a [u8]) -> Result<Self, ParseError> {
+        Self::try_parse_with_security_level(packet, security::DEFAULT_SECURITY_LEVEL)
+    }
+
+    /// Parse an NWK frame, using `security_level` for secured frames that
+    /// were sent with a security level of 0, as is done over the air.
+    pub fn try_parse_with_security_level(
+        packet: &'a [u8],
+        security_level: u8,
+    ) -> Result<Self, ParseError> {
+        let offset = &mut 0;
+
+        let fcf = packet.read_with::<u16>(offset, LE)?;
+        let fcf = FrameControlField::from(fcf);
+
+        let destination = packet.read_with::<u16>(offset, LE)?;
+        let source = packet.read_with::<u16>(offset, LE)?;
+
+        let radius = packet.read_with::<u8>(offset, LE)?;
+        let sequence_number = packet.read_with::<u8>(offset, LE)?;
+
+        let extended_destination = match fcf.destination_present {
+            true => Some(packet.read_with::<u64>(offset, LE)?),
+            false => None,
+        };
+        let extended_source = match fcf.source_address_present {
+            true => Some(packet.read_with::<u64>(offset, LE)?),
+            false => None,
+        };
+        let multicast_control = match fcf.multicast_present {
+            true => Some(MulticastControl::from(packet.read_with::<u8>(offset, LE)?)),
+            false => None,
+        };
+
+        let source_route = match fcf.source_route_present {
+            true => Some(SourceRoute::try_parse_from(packet, offset)?),
+            false => None,
+        };
+
+        let security_header = match fcf.security_present {
+            true => Some(SecurityHeader::try_parse_with_security_level(
+                packet,
+                offset,
+                security_level,
+            )?),
+            false => None,
+        };
+
+        // If there is a MAC, don't include it in the payload.
+        let end_index = match security_header {
+            Some(ref header) => packet.len() - header.message_integrity_code.len(),
+            None => packet.len(),
+        };
+
+        let payload = &packet[*offset..end_index];
+
+        Ok(ZigbeePacket {
+            frame_control_field: fcf,
+            destination,
+            source,
+            radius,
+            sequence_number,
+            extended_destination,
+            extended_source,
+            multicast_control,
+            source_route,
+            security_header,
+            payload,
+        })
+    }
+
+    /// Parse the payload of a command frame. The payload of secured frames
+    /// needs to be decrypted first, see `security::decrypt_frame`.
+    pub fn command(&self) -> Result<NwkCommand<'a>, ParseError> {
+        if self.frame_control_field.frame_type != FrameType::Command {
+            return Err(ParseError);
+        }
+        NwkCommand::try_parse_from(self.payload)
+    }
+
+    /// Serialize the packet into `buffer` as an NWK frame, returning the
+    /// number of bytes written.
+    ///
+    /// The payload is written as-is, so for secured packets it should already
+    /// be encrypted. The message integrity code from the security header is
+    /// appended after the payload.
+    pub fn write_into(&self, buffer: &mut [u8]) -> Result<usize, WriteError> {
+        let offset = &mut 0;
+
+        let fcf = u16::from(&self.frame_control_field);
+        buffer.write_with::<u16>(offset, fcf, LE)?;
+
+        buffer.write_with::<u16>(offset, self.destination, LE)?;
+        buffer.write_with::<u16>(offset, self.source, LE)?;
+
+        buffer.write_with::<u8>(offset, self.radius, LE)?;
+        buffer.write_with::<u8>(offset, self.sequence_number, LE)?;
+
+        if self.frame_control_field.destination_present {
+            let extended_destination = self.extended_destination.ok_or(WriteError)?;
+            buffer.write_with::<u64>(offset, extended_destination, LE)?;
+        }
+        if self.frame_control_field.source_address_present {
+            let extended_source = self.extended_source.ok_or(WriteError)?;
+            buffer.write_with::<u64>(offset, extended_source, LE)?;
+        }
+        if self.frame_control_field.multicast_present {
+            let multicast_control = self.multicast_control.ok_or(WriteError)?;
+            buffer.write_with::<u8>(offset, u8::from(&multicast_control), LE)?;
+        }
+        if self.frame_control_field.source_route_present {
+            let source_route = self.source_route.as_ref().ok_or(WriteError)?;
+            source_route.write_into(buffer, offset)?;
+        }
+
+        let security_header = match self.frame_control_field.security_present {
+            true => Some(self.security_header.as_ref().ok_or(WriteError)?),
+            false => None,
+        };
+        if let Some(header) = security_header {
+            header.write_into(buffer, offset)?;
+        }
+
+        buffer.write(offset, self.payload)?;
+
+        if let Some(header) = security_header {
+            buffer.write(offset, header.message_integrity_code)?;
+        }
+
+        Ok(*offset)
+    }
+}
+
+/// Path for a source-routed frame, listed from the relay closest to the
+/// destination to the one closest to the originator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceRoute<'a> {
+    /// Index into the relay list of the next relay to pass the frame on.
+    pub relay_index: u8,
+    /// Relay list as sent over the air, pairs of little-endian bytes holding
+    /// each relay's short address.
+    pub relay_list: &'a [u8],
+}
+impl<'a> SourceRoute<'a> {
+    pub fn try_parse_from(packet: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let relay_count = packet.read_with::<u8>(offset, LE)?;
+        let relay_index = packet.read_with::<u8>(offset, LE)?;
+        let relay_list = packet.read_with::<&[u8]>(offset, Bytes::Len(relay_count as usize * 2))?;
+
+        Ok(Self {
+            relay_index,
+            relay_list,
+        })
+    }
+
+    pub fn write_into(&self, packet: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        if self.relay_list.len() % 2 != 0 || self.relay_count() > u8::MAX as usize {
+            return Err(WriteError);
+        }
+
+        packet.write_with::<u8>(offset, self.relay_count() as u8, LE)?;
+        packet.write_with::<u8>(offset, self.relay_index, LE)?;
+        packet.write(offset, self.relay_list)?;
+
+        Ok(())
+    }
+
+    pub fn relay_count(&self) -> usize {
+        self.relay_list.len() / 2
+    }
+
+    /// Short address of the relay at `index` in the relay list.
+    pub fn relay(&self, index: usize) -> Option<u16> {
+        let bytes = self.relay_list.get((index * 2)..(index * 2 + 2))?;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn relays(&self) -> impl Iterator<Item = u16> + 'a {
+        self.relay_list
+            .chunks_exact(2)
+            .map(|relay| u16::from_le_bytes([relay[0], relay[1]]))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MulticastControl {
+    pub mode: MulticastMode,
+    /// Remaining hops the frame may travel between devices that aren't
+    /// members of the group.
+    pub non_member_radius: u8,
+    /// Value `non_member_radius` is reset to when a member of the group
+    /// passes the frame on.
+    pub max_non_member_radius: u8,
+}
+impl From<u8> for MulticastControl {
+    fn from(field: u8) -> Self {
+        // Should never panic, all possible values covered by MulticastMode.
+        let mode = MulticastMode::try_from(field & 0b11).unwrap();
+        let non_member_radius = (field >> 2) & 0b111;
+        let max_non_member_radius = (field >> 5) & 0b111;
+
+        Self {
+            mode,
+            non_member_radius,
+            max_non_member_radius,
+        }
+    }
+}
+impl From<&MulticastControl> for u8 {
+    fn from(control: &MulticastControl) -> Self {
+        let mode: u8 = match control.mode {
+            MulticastMode::NonMember => 0b00,
+            MulticastMode::Member => 0b01,
+            MulticastMode::Reserved => 0b10,
+        };
+
+        mode | ((control.non_member_radius & 0b111) << 2)
+            | ((control.max_non_member_radius & 0b111) << 5)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MulticastMode {
+    /// Sent by a device that isn't a member of the group, the frame is
+    /// unicast towards a member before being broadcast.
+    NonMember,
+    /// Broadcast by members of the group.
+    Member,
+    Reserved,
+}
+impl TryFrom<u8> for MulticastMode {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(MulticastMode::NonMember),
+            0b01 => Ok(MulticastMode::Member),
+            0b10 => Ok(MulticastMode::Reserved),
+            0b11 => Ok(MulticastMode::Reserved),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameControlField {
+    pub frame_type: FrameType,
+    pub protocol_version: u8,
+    pub discover_route: DiscoverRoute,
+    pub multicast_present: bool,
+    pub security_present: bool,
+    pub source_route_present: bool,
+    pub destination_present: bool,
+    pub source_address_present: bool,
+    pub end_device_initiator: bool,
+}
+impl From<u16> for FrameControlField {
+    fn from(field: u16) -> Self {
+        // Should never panic, all possible values covered by FrameType.
+        let frame_type = FrameType::try_from((field & 0b11) as u8).unwrap();
+        let protocol_version: u8 = ((field >> 2) & 0b1111) as u8;
+        // Should never panic, all possible values covered by DiscoverRoute.
+        let discover_route = DiscoverRoute::try_from(((field >> 6) & 0b11) as u8).unwrap();
+        let multicast_present = ((field >> 8) & 1) == 1;
+        let security_present = ((field >> 9) & 1) == 1;
+        let source_route_present = ((field >> 10) & 1) == 1;
+        let destination_present = ((field >> 11) & 1) == 1;
+        let source_address_present = ((field >> 12) & 1) == 1;
+        let end_device_initiator = ((field >> 13) & 1) == 1;
+
+        Self {
+            frame_type,
+            protocol_version,
+            discover_route,
+            multicast_present,
+            security_present,
+            source_route_present,
+            destination_present,
+            source_address_present,
+            end_device_initiator,
+        }
+    }
+}
+impl From<&FrameControlField> for u16 {
+    fn from(fcf: &FrameControlField) -> Self {
+        let frame_type: u16 = match fcf.frame_type {
+            FrameType::Data => 0b00,
+            FrameType::Command => 0b01,
+            FrameType::Reserved => 0b10,
+            FrameType::InterPAN => 0b11,
+        };
+        let discover_route: u16 = match fcf.discover_route {
+            DiscoverRoute::SurpressRouteDiscovery => 0b00,
+            DiscoverRoute::EnableRouteDiscovery => 0b01,
+            DiscoverRoute::Reserved => 0b10,
+        };
+
+        frame_type
+            | ((fcf.protocol_version as u16 & 0b1111) << 2)
+            | (discover_route << 6)
+            | ((fcf.multicast_present as u16) << 8)
+            | ((fcf.security_present as u16) << 9)
+            | ((fcf.source_route_present as u16) << 10)
+            | ((fcf.destination_present as u16) << 11)
+            | ((fcf.source_address_present as u16) << 12)
+            | ((fcf.end_device_initiator as u16) << 13)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum DiscoverRoute {
+    SurpressRouteDiscovery,
+    EnableRouteDiscovery,
+    Reserved,
+}
+impl TryFrom<u8> for DiscoverRoute {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(DiscoverRoute::SurpressRouteDiscovery),
+            0b01 => Ok(DiscoverRoute::EnableRouteDiscovery),
+            0b10 => Ok(DiscoverRoute::Reserved),
+            0b11 => Ok(DiscoverRoute::Reserved),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum FrameType {
+    Data,
+    Command,
+    Reserved,
+    InterPAN,
+}
+impl TryFrom<u8> for FrameType {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(FrameType::Data),
+            0b01 => Ok(FrameType::Command),
+            0b10 => Ok(FrameType::Reserved),
+            0b11 => Ok(FrameType::InterPAN),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::security::*;
+    use super::*;
+
+    #[test]
+    fn parses_data_frame_control_field_correctly() {
+        let fcf = FrameControlField::from(0x0208);
+
+        assert_eq!(fcf.frame_type, FrameType::Data);
+        assert_eq!(fcf.protocol_version, 2);
+        assert_eq!(fcf.discover_route, DiscoverRoute::SurpressRouteDiscovery);
+        assert_eq!(fcf.multicast_present, false);
+        assert_eq!(fcf.security_present, true);
+        assert_eq!(fcf.source_route_present, false);
+        assert_eq!(fcf.destination_present, false);
+        assert_eq!(fcf.source_address_present, false);
+        assert_eq!(fcf.end_device_initiator, false);
+    }
+
+    #[test]
+    fn parses_command_frame_control_field_correctly() {
+        let fcf = FrameControlField::from(0x1a09);
+
+        assert_eq!(fcf.frame_type, FrameType::Command);
+        assert_eq!(fcf.protocol_version, 2);
+        assert_eq!(fcf.discover_route, DiscoverRoute::SurpressRouteDiscovery);
+        assert_eq!(fcf.multicast_present, false);
+        assert_eq!(fcf.security_present, true);
+        assert_eq!(fcf.source_route_present, false);
+        assert_eq!(fcf.destination_present, true);
+        assert_eq!(fcf.source_address_present, true);
+        assert_eq!(fcf.end_device_initiator, false);
+    }
+
+    #[test]
+    fn parses_full_broadcast_packet() {
+        let packet = b"\
+\x09\x1a\xbc\x8d\x81\x01\x1d\x79\xe1\xc2\xd9\x01\x01\x88\x17\x00\
+\x9e\xc0\x81\x08\x01\x88\x17\x00\x28\xe7\x08\x14\x01\x3b\xbd\x5d\
+\x0b\x01\x88\x17\x00\x00\xdc\x0e\x9a\x26\x3f\x28\x6a\xf1\
+";
+
+        let packet = ZigbeePacket::try_parse_from(packet).unwrap();
+
+        assert_eq!(packet.destination, 0x8dbc);
+        assert_eq!(packet.source, 0x0181);
+        assert_eq!(packet.radius, 29);
+        assert_eq!(packet.sequence_number, 121);
+        assert_eq!(packet.extended_destination, Some(0x00_17_88_01_01_d9_c2_e1));
+        assert_eq!(packet.extended_source, Some(0x00_17_88_01_08_81_c0_9e));
+
+        let security_header = packet.security_header.unwrap();
+        assert_eq!(
+            security_header.security_control_field.key_identifier,
+            KeyIdentifier::Network
+        );
+        assert_eq!(security_header.frame_counter, 18090215);
+        assert_eq!(security_header.key_message_number, 0);
+        assert_eq!(
+            security_header.message_integrity_code,
+            [0x3f, 0x28, 0x6a, 0xf1]
+        );
+
+        assert_eq!(packet.payload, b"\xDC\x0E\x9A\x26");
+    }
+
+    #[test]
+    fn parses_network_key_security_control_field() {
+        let scf = SecurityControlField::try_from(0x28).unwrap();
+
+        // The security level is zeroed over the air.
+        assert!(!scf.using_encryption);
+        assert_eq!(scf.message_integrity_size, MessageIntegritySize::None);
+        assert_eq!(scf.key_identifier, KeyIdentifier::Network);
+        assert_eq!(scf.using_extended_nonce, true);
+    }
+
+    #[test]
+    fn parses_security_level_in_security_control_field() {
+        let scf = SecurityControlField::from(0x06);
+
+        assert!(scf.using_encryption);
+        assert_eq!(scf.message_integrity_size, MessageIntegritySize::B64);
+        assert_eq!(scf.key_identifier, KeyIdentifier::Data);
+        assert!(!scf.using_extended_nonce);
+        assert_eq!(scf.security_level(), 6);
+    }
+
+    #[test]
+    fn applies_network_security_level_to_zeroed_frames() {
+        let packet = b"\
+\x08\x02\xfd\xff\x00\x00\x1e\x2a\x28\x01\x00\x00\x00\x9e\xc0\x81\
+\x08\x01\x88\x17\x00\x00\xaa\xbb\x01\x02\x03\x04\x05\x06\x07\x08\
+";
+
+        let parsed = ZigbeePacket::try_parse_from(packet).unwrap();
+        let header = parsed.security_header.unwrap();
+        assert_eq!(header.security_control_field.security_level(), 5);
+        assert_eq!(header.message_integrity_code, [0x05, 0x06, 0x07, 0x08]);
+        assert_eq!(parsed.payload, b"\xaa\xbb\x01\x02\x03\x04");
+
+        let parsed = ZigbeePacket::try_parse_with_security_level(packet, 6).unwrap();
+        let header = parsed.security_header.unwrap();
+        assert_eq!(header.security_control_field.security_level(), 6);
+        assert_eq!(
+            header.message_integrity_code,
+            [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]
+        );
+        assert_eq!(parsed.payload, b"\xaa\xbb");
+
+        let parsed = ZigbeePacket::try_parse_with_security_level(packet, 4).unwrap();
+        assert!(parsed
+            .security_header
+            .unwrap()
+            .message_integrity_code
+            .is_empty());
+        assert_eq!(parsed.payload.len(), 10);
+
+        assert!(ZigbeePacket::try_parse_with_security_level(packet, 7).is_err());
+    }
+
+    #[test]
+    fn honors_security_level_sent_over_the_air() {
+        // Security control field of 0x2e has a level of 6, so an 8 byte MIC.
+        let packet = b"\
+\x08\x02\xfd\xff\x00\x00\x1e\x2a\x2e\x01\x00\x00\x00\x9e\xc0\x81\
+\x08\x01\x88\x17\x00\x00\xaa\xbb\x01\x02\x03\x04\x05\x06\x07\x08\
+";
+
+        let parsed = ZigbeePacket::try_parse_with_security_level(packet, 5).unwrap();
+        let header = parsed.security_header.unwrap();
+        assert_eq!(header.security_control_field.security_level(), 6);
+        assert_eq!(parsed.payload, b"\xaa\xbb");
+    }
+
+    #[test]
+    fn frame_control_field_round_trips() {
+        for field in [0x0208, 0x1a09] {
+            let fcf = FrameControlField::from(field);
+            assert_eq!(u16::from(&fcf), field);
+        }
+    }
+
+    #[test]
+    fn security_control_field_round_trips() {
+        let scf = SecurityControlField::from(0x28);
+        assert_eq!(u8::from(&scf), 0x28);
+    }
+
+    #[test]
+    fn writes_full_broadcast_packet() {
+        let bytes = b"\
+\x09\x1a\xbc\x8d\x81\x01\x1d\x79\xe1\xc2\xd9\x01\x01\x88\x17\x00\
+\x9e\xc0\x81\x08\x01\x88\x17\x00\x28\xe7\x08\x14\x01\x3b\xbd\x5d\
+\x0b\x01\x88\x17\x00\x00\xdc\x0e\x9a\x26\x3f\x28\x6a\xf1\
+";
+
+        let packet = ZigbeePacket::try_parse_from(bytes).unwrap();
+
+        let mut buffer = [0u8; 128];
+        let len = packet.write_into(&mut buffer).unwrap();
+
+        assert_eq!(&buffer[..len], &bytes[..]);
+    }
+
+    #[test]
+    fn parses_multicast_packet() {
+        let bytes = b"\x08\x01\x01\x00\x34\x12\x1e\x05\xed\xaa";
+
+        let packet = ZigbeePacket::try_parse_from(bytes).unwrap();
+        assert!(packet.frame_control_field.multicast_present);
+        assert_eq!(packet.destination, 0x0001);
+        assert_eq!(
+            packet.multicast_control,
+            Some(MulticastControl {
+                mode: MulticastMode::Member,
+                non_member_radius: 3,
+                max_non_member_radius: 7,
+            })
+        );
+        assert_eq!(packet.payload, b"\xaa");
+
+        let mut buffer = [0u8; 16];
+        let len = packet.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], bytes);
+    }
+
+    #[test]
+    fn writes_unsecured_data_packet() {
+        let packet = ZigbeePacket {
+            frame_control_field: FrameControlField::from(0x0008),
+            destination: 0xfffd,
+            source: 0x0000,
+            radius: 30,
+            sequence_number: 7,
+            extended_destination: None,
+            extended_source: None,
+            multicast_control: None,
+            source_route: None,
+            security_header: None,
+            payload: b"\x08\x00\x0a",
+        };
+
+        let mut buffer = [0u8; 128];
+        let len = packet.write_into(&mut buffer).unwrap();
+
+        assert_eq!(
+            &buffer[..len],
+            b"\x08\x00\xfd\xff\x00\x00\x1e\x07\x08\x00\x0a"
+        );
+
+        let parsed = ZigbeePacket::try_parse_from(&buffer[..len]).unwrap();
+        assert_eq!(parsed.destination, 0xfffd);
+        assert_eq!(parsed.radius, 30);
+        assert_eq!(parsed.payload, b"\x08\x00\x0a");
+    }
+
+    #[test]
+    fn write_fails_when_buffer_too_small() {
+        let packet = ZigbeePacket::try_parse_from(b"\x08\x00\xfd\xff\x00\x00\x1e\x07\x01").unwrap();
+
+        let mut buffer = [0u8; 4];
+        assert!(packet.write_into(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn parses_source_routed_packet() {
+        let bytes = b"\
+\x08\x04\x34\x12\x00\x00\x1e\x05\x03\x01\xcd\xab\x22\x11\x00\xff\
+\x01\x02\
+";
+
+        let packet = ZigbeePacket::try_parse_from(bytes).unwrap();
+
+        let source_route = packet.source_route.unwrap();
+        assert_eq!(source_route.relay_index, 1);
+        assert_eq!(source_route.relay_count(), 3);
+        assert_eq!(source_route.relay(1), Some(0x1122));
+        assert_eq!(source_route.relay(3), None);
+
+        let mut relays = source_route.relays();
+        assert_eq!(relays.next(), Some(0xabcd));
+        assert_eq!(relays.next(), Some(0x1122));
+        assert_eq!(relays.next(), Some(0xff00));
+        assert_eq!(relays.next(), None);
+
+        assert_eq!(packet.payload, b"\x01\x02");
+
+        let mut buffer = [0u8; 128];
+        let len = packet.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &bytes[..]);
+    }
+
+    #[test]
+    fn rejects_truncated_relay_list() {
+        let bytes = b"\x08\x04\x34\x12\x00\x00\x1e\x05\x03\x01\xcd\xab\x22";
+
+        assert!(ZigbeePacket::try_parse_from(bytes).is_err());
+    }
+
+    #[test]
+    fn parses_command_payload() {
+        let bytes = b"\x09\x00\xfc\xff\x00\x00\x1e\x05\x01\x08\x2a\xfc\xff\x00";
+
+        let packet = ZigbeePacket::try_parse_from(bytes).unwrap();
+        assert_eq!(packet.command().unwrap().command_id(), 0x01);
+
+        let data = ZigbeePacket::try_parse_from(b"\x08\x00\xfd\xff\x00\x00\x1e\x07\x01").unwrap();
+        assert!(data.command().is_err());
+    }
+}
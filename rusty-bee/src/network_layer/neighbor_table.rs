@@ -0,0 +1,121 @@
+use crate::table::{Table, TableFull};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DeviceType {
+    Coordinator,
+    Router,
+    EndDevice,
+    Unknown,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Relationship {
+    Parent,
+    Child,
+    Sibling,
+    None,
+    PreviousChild,
+    UnauthenticatedChild,
+}
+
+/// A device within radio range of this one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbor {
+    pub extended_pan_id: u64,
+    pub extended_address: u64,
+    pub nwk_address: u16,
+    pub device_type: DeviceType,
+    /// `None` when unknown.
+    pub receiver_on_when_idle: Option<bool>,
+    pub relationship: Relationship,
+    /// `None` when unknown.
+    pub permit_joining: Option<bool>,
+    pub depth: u8,
+    /// Link quality of frames received from the neighbor.
+    pub lqi: u8,
+}
+
+/// Neighbors of this device, keyed by extended address.
+#[derive(Debug, Clone, Default)]
+pub struct NeighborTable<const N: usize> {
+    neighbors: Table<Neighbor, N>,
+}
+impl<const N: usize> NeighborTable<N> {
+    pub const fn new() -> Self {
+        Self {
+            neighbors: Table::new(),
+        }
+    }
+
+    /// Add a neighbor, replacing the entry for the same device if there is
+    /// one.
+    pub fn update(&mut self, neighbor: Neighbor) -> Result<(), TableFull> {
+        self.neighbors.upsert(neighbor, |existing| {
+            existing.extended_address == neighbor.extended_address
+        })
+    }
+
+    pub fn remove(&mut self, extended_address: u64) -> bool {
+        self.neighbors
+            .remove_where(|neighbor| neighbor.extended_address == extended_address)
+            > 0
+    }
+
+    pub fn by_nwk_address(&self, nwk_address: u16) -> Option<&Neighbor> {
+        self.neighbors
+            .find(|neighbor| neighbor.nwk_address == nwk_address)
+    }
+
+    pub fn by_extended_address(&self, extended_address: u64) -> Option<&Neighbor> {
+        self.neighbors
+            .find(|neighbor| neighbor.extended_address == extended_address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Neighbor> {
+        self.neighbors.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.neighbors.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neighbor(extended_address: u64, nwk_address: u16) -> Neighbor {
+        Neighbor {
+            extended_pan_id: 0xdddd_dddd_dddd_dddd,
+            extended_address,
+            nwk_address,
+            device_type: DeviceType::Router,
+            receiver_on_when_idle: Some(true),
+            relationship: Relationship::Sibling,
+            permit_joining: None,
+            depth: 1,
+            lqi: 200,
+        }
+    }
+
+    #[test]
+    fn updates_neighbor_by_extended_address() {
+        let mut table = NeighborTable::<4>::new();
+
+        table.update(neighbor(1, 0x1111)).unwrap();
+        table.update(neighbor(2, 0x2222)).unwrap();
+        // The device rejoined with a new network address.
+        table.update(neighbor(1, 0x3333)).unwrap();
+
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.by_nwk_address(0x3333).unwrap().extended_address, 1);
+        assert!(table.by_nwk_address(0x1111).is_none());
+
+        assert!(table.remove(2));
+        assert!(table.by_extended_address(2).is_none());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::table::{Table, TableFull};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum RouteStatus {
+    Active,
+    DiscoveryUnderway,
+    DiscoveryFailed,
+    Inactive,
+    ValidationUnderway,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Route {
+    pub destination: u16,
+    pub status: RouteStatus,
+    /// The destination can't hold a route record table.
+    pub memory_constrained: bool,
+    /// The destination is a concentrator that sent a many-to-one route
+    /// request.
+    pub many_to_one: bool,
+    /// A route record needs to be sent before the next data frame.
+    pub route_record_required: bool,
+    pub next_hop: u16,
+}
+
+/// Routes to other devices, keyed by destination network address.
+#[derive(Debug, Clone, Default)]
+pub struct RoutingTable<const N: usize> {
+    routes: Table<Route, N>,
+}
+impl<const N: usize> RoutingTable<N> {
+    pub const fn new() -> Self {
+        Self {
+            routes: Table::new(),
+        }
+    }
+
+    /// Add a route, replacing the existing route to the same destination.
+    pub fn update(&mut self, route: Route) -> Result<(), TableFull> {
+        self.routes
+            .upsert(route, |existing| existing.destination == route.destination)
+    }
+
+    pub fn remove(&mut self, destination: u16) -> bool {
+        self.routes
+            .remove_where(|route| route.destination == destination)
+            > 0
+    }
+
+    /// The next hop towards `destination`, if there is an active route.
+    pub fn next_hop(&self, destination: u16) -> Option<u16> {
+        self.routes
+            .find(|route| route.destination == destination && route.status == RouteStatus::Active)
+            .map(|route| route.next_hop)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Route> {
+        self.routes.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(destination: u16, status: RouteStatus, next_hop: u16) -> Route {
+        Route {
+            destination,
+            status,
+            memory_constrained: false,
+            many_to_one: false,
+            route_record_required: false,
+            next_hop,
+        }
+    }
+
+    #[test]
+    fn only_uses_active_routes() {
+        let mut table = RoutingTable::<2>::new();
+
+        table
+            .update(route(0x1234, RouteStatus::DiscoveryUnderway, 0xffff))
+            .unwrap();
+        assert_eq!(table.next_hop(0x1234), None);
+
+        table
+            .update(route(0x1234, RouteStatus::Active, 0x0001))
+            .unwrap();
+        assert_eq!(table.next_hop(0x1234), Some(0x0001));
+        assert_eq!(table.len(), 1);
+
+        table
+            .update(route(0x0002, RouteStatus::Active, 0x0002))
+            .unwrap();
+        assert_eq!(
+            table.update(route(0x0003, RouteStatus::Active, 0x0003)),
+            Err(TableFull)
+        );
+
+        assert!(table.remove(0x1234));
+        assert_eq!(table.next_hop(0x1234), None);
+    }
+}
@@ -0,0 +1,811 @@
+use super::{ParseError, WriteError, ZigbeePacket};
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::Aes128;
+use byte::{BytesExt, LE};
+use ccm::aead::generic_array::{ArrayLength, GenericArray};
+use ccm::aead::AeadInPlace;
+use ccm::consts::{U13, U16, U4, U8};
+use ccm::{Ccm, TagSize};
+
+/// AES-128 key used to secure frames, e.g. the network key.
+pub type SecurityKey = [u8; 16];
+
+/// Security level used by Zigbee networks (ENC-MIC-32) unless configured
+/// otherwise.
+pub const DEFAULT_SECURITY_LEVEL: u8 = 5;
+
+/// Length of the CCM* nonce used by Zigbee.
+pub const NONCE_LENGTH: usize = 13;
+
+#[derive(Debug, PartialEq)]
+pub enum SecurityError {
+    /// The frame could not be parsed or is missing its security header.
+    MalformedFrame,
+    /// The message integrity code did not match, either the frame was
+    /// tampered with or the wrong key was used.
+    AuthenticationFailed,
+    /// The outgoing frame counter has run out, no more frames can be sent
+    /// with the current key.
+    FrameCounterExhausted,
+    /// No key is known for the key message number or sender of the frame.
+    UnknownKey,
+    /// The frame counter is not higher than the last one received from the
+    /// sender, the frame was replayed or is out of date.
+    ReplayedFrame,
+    /// Frames can't be secured with a security level of 0, which neither
+    /// encrypts nor authenticates them. Unsecured frames are sent without
+    /// `security_present` instead.
+    InvalidSecurityLevel,
+}
+impl From<ParseError> for SecurityError {
+    fn from(_: ParseError) -> Self {
+        Self::MalformedFrame
+    }
+}
+impl From<WriteError> for SecurityError {
+    fn from(_: WriteError) -> Self {
+        Self::MalformedFrame
+    }
+}
+
+/// State needed to secure the frames we send.
+#[derive(Debug)]
+pub struct SecurityContext {
+    /// Our own extended address, sent as the source in the auxiliary header.
+    pub extended_source: u64,
+    /// Frame counter for the next frame we send.
+    pub outgoing_frame_counter: u32,
+    /// Sequence number of the active network key.
+    pub key_sequence_number: u8,
+    /// The network's security level, nwkSecurityLevel in the spec.
+    pub security_level: u8,
+}
+impl SecurityContext {
+    pub fn new(extended_source: u64) -> Self {
+        Self {
+            extended_source,
+            outgoing_frame_counter: 0,
+            key_sequence_number: 0,
+            security_level: DEFAULT_SECURITY_LEVEL,
+        }
+    }
+
+    /// Secure `packet` with the network `key` and write it into `buffer`,
+    /// returning the number of bytes written.
+    ///
+    /// The packet's payload is the plaintext, any security header on it is
+    /// replaced with one using the next outgoing frame counter.
+    pub fn encrypt_frame(
+        &mut self,
+        packet: &ZigbeePacket,
+        key: &SecurityKey,
+        buffer: &mut [u8],
+    ) -> Result<usize, SecurityError> {
+        if self.security_level & 0b111 == 0 {
+            return Err(SecurityError::InvalidSecurityLevel);
+        }
+        // A frame counter of 0xffffffff must never be sent.
+        if self.outgoing_frame_counter == u32::MAX {
+            return Err(SecurityError::FrameCounterExhausted);
+        }
+
+        let mut security_control_field = SecurityControlField {
+            using_encryption: false,
+            message_integrity_size: MessageIntegritySize::None,
+            key_identifier: KeyIdentifier::Network,
+            using_extended_nonce: true,
+        };
+        security_control_field.set_security_level(self.security_level);
+        let security_level = security_control_field.security_level();
+        let mic_length = security_control_field.message_integrity_size.num_bytes();
+
+        let security_header = SecurityHeader {
+            security_control_field,
+            frame_counter: self.outgoing_frame_counter,
+            extended_source: Some(self.extended_source),
+            key_message_number: self.key_sequence_number,
+            // Placeholder until the frame is encrypted.
+            message_integrity_code: &[0; 16][..mic_length],
+        };
+        let auxiliary_header_length = security_header.header_length();
+
+        let mut frame_control_field = packet.frame_control_field.clone();
+        frame_control_field.security_present = true;
+        let secured = ZigbeePacket {
+            frame_control_field,
+            security_header: Some(security_header),
+            ..*packet
+        };
+        let length = secured.write_into(buffer)?;
+
+        let header_length = length - mic_length - packet.payload.len();
+        let control_offset = header_length - auxiliary_header_length;
+
+        let sent_control = buffer[control_offset];
+        buffer[control_offset] |= security_level;
+        let nonce = build_nonce(
+            self.extended_source,
+            self.outgoing_frame_counter,
+            buffer[control_offset],
+        );
+
+        let (data, message_integrity_code) = buffer[..length].split_at_mut(length - mic_length);
+        ccm_star_encrypt(
+            key,
+            &nonce,
+            security_level,
+            data,
+            header_length,
+            message_integrity_code,
+        )?;
+
+        // The security level is never sent over the air.
+        buffer[control_offset] = sent_control;
+        self.outgoing_frame_counter += 1;
+
+        Ok(length)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyIdentifier {
+    Data,
+    Network,
+    KeyTransport,
+    KeyLoad,
+}
+impl TryFrom<u8> for KeyIdentifier {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(KeyIdentifier::Data),
+            0b01 => Ok(KeyIdentifier::Network),
+            0b10 => Ok(KeyIdentifier::KeyTransport),
+            0b11 => Ok(KeyIdentifier::KeyLoad),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageIntegritySize {
+    None,
+    B32,
+    B64,
+    B128,
+}
+impl MessageIntegritySize {
+    /// Number of bytes the message integrity code takes up in the frame.
+    pub fn num_bytes(&self) -> usize {
+        match self {
+            MessageIntegritySize::None => 0,
+            MessageIntegritySize::B32 => 4,
+            MessageIntegritySize::B64 => 8,
+            MessageIntegritySize::B128 => 16,
+        }
+    }
+}
+impl TryFrom<u8> for MessageIntegritySize {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(MessageIntegritySize::None),
+            0b01 => Ok(MessageIntegritySize::B32),
+            0b10 => Ok(MessageIntegritySize::B64),
+            0b11 => Ok(MessageIntegritySize::B128),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityControlField {
+    pub using_encryption: bool,
+    pub message_integrity_size: MessageIntegritySize,
+    pub key_identifier: KeyIdentifier,
+    pub using_extended_nonce: bool,
+}
+impl From<u8> for SecurityControlField {
+    fn from(field: u8) -> Self {
+        // Note that the security level is usually zeroed over the air, see
+        // `SecurityHeader::try_parse_with_security_level`.
+        let using_encryption = ((field >> 2) & 1) == 1;
+        // Should never panic, all possible values covered by MessageIntegritySize.
+        let message_integrity_size = MessageIntegritySize::try_from(field & 0b11).unwrap();
+
+        let key_identifier = KeyIdentifier::try_from((field >> 3) & 0b11).unwrap();
+        let using_extended_nonce = ((field >> 5) & 1) == 1;
+
+        Self {
+            using_encryption,
+            message_integrity_size,
+            key_identifier,
+            using_extended_nonce,
+        }
+    }
+}
+impl SecurityControlField {
+    /// The 3-bit security level, bit 2 is encryption and bits 0-1 the size
+    /// of the message integrity code.
+    pub fn security_level(&self) -> u8 {
+        let message_integrity_size = match self.message_integrity_size {
+            MessageIntegritySize::None => 0b00,
+            MessageIntegritySize::B32 => 0b01,
+            MessageIntegritySize::B64 => 0b10,
+            MessageIntegritySize::B128 => 0b11,
+        };
+        ((self.using_encryption as u8) << 2) | message_integrity_size
+    }
+
+    /// Set `using_encryption` and `message_integrity_size` from a 3-bit
+    /// security level.
+    pub fn set_security_level(&mut self, security_level: u8) {
+        self.using_encryption = ((security_level >> 2) & 1) == 1;
+        // Should never panic, all possible values covered by MessageIntegritySize.
+        self.message_integrity_size =
+            MessageIntegritySize::try_from(security_level & 0b11).unwrap();
+    }
+}
+impl From<&SecurityControlField> for u8 {
+    fn from(field: &SecurityControlField) -> Self {
+        let key_identifier: u8 = match field.key_identifier {
+            KeyIdentifier::Data => 0b00,
+            KeyIdentifier::Network => 0b01,
+            KeyIdentifier::KeyTransport => 0b10,
+            KeyIdentifier::KeyLoad => 0b11,
+        };
+
+        // The security level is always sent over the air as 0, receivers
+        // are expected to fill in the level in use on the network.
+        (key_identifier << 3) | ((field.using_extended_nonce as u8) << 5)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityHeader<'a> {
+    pub security_control_field: SecurityControlField,
+    pub frame_counter: u32,
+    pub extended_source: Option<u64>,
+    /// Sequence number of the network key used. Only sent for frames secured
+    /// with the network key, 0 otherwise.
+    pub key_message_number: u8,
+    pub message_integrity_code: &'a [u8],
+}
+impl<'a> SecurityHeader<'a> {
+    pub fn try_parse_from(packet: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        Self::try_parse_with_security_level(packet, offset, DEFAULT_SECURITY_LEVEL)
+    }
+
+    /// Parse the auxiliary header, falling back to `security_level` when the
+    /// frame was sent with a level of 0. Zigbee devices never send the level
+    /// over the air, but sniffed traffic from other stacks may.
+    pub fn try_parse_with_security_level(
+        packet: &'a [u8],
+        offset: &mut usize,
+        security_level: u8,
+    ) -> Result<Self, ParseError> {
+        let security_control_field = packet.read_with::<u8>(offset, LE)?;
+        let mut security_control_field = SecurityControlField::from(security_control_field);
+        if security_control_field.security_level() == 0 {
+            security_control_field.set_security_level(security_level);
+        }
+
+        let frame_counter = packet.read_with::<u32>(offset, LE)?;
+
+        let extended_source = match security_control_field.using_extended_nonce {
+            true => Some(packet.read_with::<u64>(offset, LE)?),
+            false => None,
+        };
+
+        let key_message_number = match security_control_field.key_identifier {
+            KeyIdentifier::Network => packet.read_with::<u8>(offset, LE)?,
+            _ => 0,
+        };
+
+        // The message integrity code takes up the end of the frame.
+        let mic_length = security_control_field.message_integrity_size.num_bytes();
+        if packet.len() < *offset + mic_length {
+            return Err(ParseError);
+        }
+        let message_integrity_code = &packet[(packet.len() - mic_length)..];
+
+        Ok(Self {
+            security_control_field,
+            frame_counter,
+            extended_source,
+            key_message_number,
+            message_integrity_code,
+        })
+    }
+
+    /// Number of bytes the auxiliary header takes up in the frame, not
+    /// including the message integrity code at the end.
+    pub fn header_length(&self) -> usize {
+        let extended_source_length = match self.security_control_field.using_extended_nonce {
+            true => 8,
+            false => 0,
+        };
+        let key_sequence_number_length = match self.security_control_field.key_identifier {
+            KeyIdentifier::Network => 1,
+            _ => 0,
+        };
+        1 + 4 + extended_source_length + key_sequence_number_length
+    }
+
+    /// Write the auxiliary security header at `offset`. The message
+    /// integrity code comes after the payload, so it is left to the caller.
+    pub fn write_into(&self, packet: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let security_control_field = u8::from(&self.security_control_field);
+        packet.write_with::<u8>(offset, security_control_field, LE)?;
+
+        packet.write_with::<u32>(offset, self.frame_counter, LE)?;
+
+        if self.security_control_field.using_extended_nonce {
+            let extended_source = self.extended_source.ok_or(WriteError)?;
+            packet.write_with::<u64>(offset, extended_source, LE)?;
+        }
+
+        if self.security_control_field.key_identifier == KeyIdentifier::Network {
+            packet.write_with::<u8>(offset, self.key_message_number, LE)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Build the CCM* nonce, laid out as the source address, frame counter and
+/// security control field in the same byte order they are sent in.
+pub fn build_nonce(
+    extended_source: u64,
+    frame_counter: u32,
+    security_control_field: u8,
+) -> [u8; NONCE_LENGTH] {
+    let mut nonce = [0u8; NONCE_LENGTH];
+    nonce[..8].copy_from_slice(&extended_source.to_le_bytes());
+    nonce[8..12].copy_from_slice(&frame_counter.to_le_bytes());
+    nonce[12] = security_control_field;
+    nonce
+}
+
+/// Decrypt and authenticate a secured NWK frame in place.
+///
+/// The security level is not sent over the air, so `security_level` is
+/// filled back in before checking the message integrity code. On success the
+/// returned packet's `payload` is the plaintext.
+pub fn decrypt_frame<'a>(
+    frame: &'a mut [u8],
+    key: &SecurityKey,
+    security_level: u8,
+) -> Result<ZigbeePacket<'a>, SecurityError> {
+    let (
+        header_length,
+        payload_length,
+        control_offset,
+        security_level,
+        extended_source,
+        frame_counter,
+    ) = {
+        let packet = ZigbeePacket::try_parse_with_security_level(frame, security_level)?;
+        let header = packet
+            .security_header
+            .as_ref()
+            .ok_or(SecurityError::MalformedFrame)?;
+
+        let mic_length = header.message_integrity_code.len();
+        let payload_length = packet.payload.len();
+        let header_length = frame.len() - mic_length - payload_length;
+        let control_offset = header_length - header.header_length();
+
+        let extended_source = header
+            .extended_source
+            .or(packet.extended_source)
+            .ok_or(SecurityError::MalformedFrame)?;
+
+        (
+            header_length,
+            payload_length,
+            control_offset,
+            header.security_control_field.security_level(),
+            extended_source,
+            header.frame_counter,
+        )
+    };
+
+    let received_control = frame[control_offset];
+    frame[control_offset] = (received_control & !0b111) | security_level;
+    let nonce = build_nonce(extended_source, frame_counter, frame[control_offset]);
+
+    let (authenticated, message_integrity_code) =
+        frame.split_at_mut(header_length + payload_length);
+    let result = ccm_star_decrypt(
+        key,
+        &nonce,
+        security_level,
+        authenticated,
+        header_length,
+        message_integrity_code,
+    );
+
+    // Put the frame back the way it was received.
+    frame[control_offset] = received_control;
+    result?;
+
+    let frame: &'a [u8] = frame;
+    Ok(ZigbeePacket::try_parse_with_security_level(
+        frame,
+        security_level,
+    )?)
+}
+
+/// CCM* encrypt `data`, which holds `header_length` bytes of header to
+/// authenticate followed by the payload. The payload is encrypted in place and
+/// the message integrity code, sized by the length of `message_integrity_code`,
+/// is written out.
+pub(crate) fn ccm_star_encrypt(
+    key: &SecurityKey,
+    nonce: &[u8; NONCE_LENGTH],
+    security_level: u8,
+    data: &mut [u8],
+    header_length: usize,
+    message_integrity_code: &mut [u8],
+) -> Result<(), SecurityError> {
+    let using_encryption = (security_level & 0b100) != 0;
+    let (header, payload) = match using_encryption {
+        // Levels without encryption authenticate the whole frame.
+        false => data.split_at_mut(data.len()),
+        true => data.split_at_mut(header_length),
+    };
+
+    match (message_integrity_code.len(), using_encryption) {
+        (0, false) => Ok(()),
+        (0, true) => {
+            apply_keystream(key, nonce, payload);
+            Ok(())
+        }
+        (4, _) => ccm_encrypt::<U4>(key, nonce, header, payload, message_integrity_code),
+        (8, _) => ccm_encrypt::<U8>(key, nonce, header, payload, message_integrity_code),
+        (16, _) => ccm_encrypt::<U16>(key, nonce, header, payload, message_integrity_code),
+        _ => Err(SecurityError::MalformedFrame),
+    }
+}
+
+fn ccm_encrypt<M: ArrayLength<u8> + TagSize>(
+    key: &SecurityKey,
+    nonce: &[u8; NONCE_LENGTH],
+    header: &[u8],
+    payload: &mut [u8],
+    message_integrity_code: &mut [u8],
+) -> Result<(), SecurityError> {
+    let cipher = Ccm::<Aes128, M, U13>::new(GenericArray::from_slice(key));
+    let tag = cipher
+        .encrypt_in_place_detached(GenericArray::from_slice(nonce), header, payload)
+        .map_err(|_| SecurityError::MalformedFrame)?;
+    message_integrity_code.copy_from_slice(&tag);
+    Ok(())
+}
+
+/// Inverse of CCM* encryption for `data`, which holds `header_length` bytes
+/// of authenticated-only header followed by the (possibly encrypted)
+/// payload. The payload is decrypted in place.
+pub(crate) fn ccm_star_decrypt(
+    key: &SecurityKey,
+    nonce: &[u8; NONCE_LENGTH],
+    security_level: u8,
+    data: &mut [u8],
+    header_length: usize,
+    message_integrity_code: &[u8],
+) -> Result<(), SecurityError> {
+    let using_encryption = (security_level & 0b100) != 0;
+    let (header, payload) = match using_encryption {
+        // Levels without encryption authenticate the whole frame.
+        false => data.split_at_mut(data.len()),
+        true => data.split_at_mut(header_length),
+    };
+
+    match (message_integrity_code.len(), using_encryption) {
+        (0, false) => Ok(()),
+        (0, true) => {
+            apply_keystream(key, nonce, payload);
+            Ok(())
+        }
+        (4, _) => ccm_decrypt::<U4>(key, nonce, header, payload, message_integrity_code),
+        (8, _) => ccm_decrypt::<U8>(key, nonce, header, payload, message_integrity_code),
+        (16, _) => ccm_decrypt::<U16>(key, nonce, header, payload, message_integrity_code),
+        _ => Err(SecurityError::MalformedFrame),
+    }
+}
+
+fn ccm_decrypt<M: ArrayLength<u8> + TagSize>(
+    key: &SecurityKey,
+    nonce: &[u8; NONCE_LENGTH],
+    header: &[u8],
+    payload: &mut [u8],
+    message_integrity_code: &[u8],
+) -> Result<(), SecurityError> {
+    let cipher = Ccm::<Aes128, M, U13>::new(GenericArray::from_slice(key));
+    cipher
+        .decrypt_in_place_detached(
+            GenericArray::from_slice(nonce),
+            header,
+            payload,
+            GenericArray::from_slice(message_integrity_code),
+        )
+        .map_err(|_| SecurityError::AuthenticationFailed)
+}
+
+/// CCM* with no integrity code is plain CTR mode, with the counter blocks
+/// starting at 1 just like the CCM encryption blocks.
+fn apply_keystream(key: &SecurityKey, nonce: &[u8; NONCE_LENGTH], payload: &mut [u8]) {
+    let cipher = Aes128::new(GenericArray::from_slice(key));
+
+    for (i, chunk) in payload.chunks_mut(16).enumerate() {
+        let mut block = GenericArray::from([0u8; 16]);
+        // Flags field holds L - 1 where L = 2 is the size of the counter.
+        block[0] = 1;
+        block[1..14].copy_from_slice(nonce);
+        block[14..16].copy_from_slice(&((i + 1) as u16).to_be_bytes());
+        cipher.encrypt_block(&mut block);
+
+        for (byte, key_byte) in chunk.iter_mut().zip(block.iter()) {
+            *byte ^= key_byte;
+        }
+    }
+}
+
+/// Matyas-Meyer-Oseas hash built on AES-128, used to derive keys. Only
+/// messages shorter than 8 KiB are supported, as their length in bits is
+/// padded into 16 bits.
+pub fn mmo_hash(message: &[u8]) -> SecurityKey {
+    let bit_length = ((message.len() * 8) as u16).to_be_bytes();
+    // A 1 bit after the message, then zeros, then the bit length, filling
+    // up the last block.
+    let padded_length = (message.len() + 1 + bit_length.len()).div_ceil(16) * 16;
+    let padded_byte = |index: usize| match index {
+        _ if index < message.len() => message[index],
+        _ if index == message.len() => 0x80,
+        _ if index >= padded_length - bit_length.len() => {
+            bit_length[index + bit_length.len() - padded_length]
+        }
+        _ => 0,
+    };
+
+    let mut hash = [0u8; 16];
+    for block_start in (0..padded_length).step_by(16) {
+        let mut block = [0u8; 16];
+        for (i, byte) in block.iter_mut().enumerate() {
+            *byte = padded_byte(block_start + i);
+        }
+
+        let cipher = Aes128::new(GenericArray::from_slice(&hash));
+        let mut encrypted = GenericArray::from(block);
+        cipher.encrypt_block(&mut encrypted);
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = encrypted[i] ^ block[i];
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: SecurityKey = [
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+        0x10,
+    ];
+
+    // Broadcast data frame secured at level 5 with `KEY`.
+    const SECURED_FRAME: &[u8] = b"\
+\x08\x02\xfd\xff\x00\x00\x1e\x2a\x28\xe7\x08\x14\x01\x9e\xc0\x81\
+\x08\x01\x88\x17\x00\x00\xda\x26\xf7\x98\xae\x92\xd1\x95\x69\x41\
+\x82\xf7\xf1\x8a\x40\x10\xd7\
+";
+
+    #[test]
+    fn builds_nonce_in_over_the_air_order() {
+        let nonce = build_nonce(0x00_17_88_01_08_81_c0_9e, 18090215, 0x2d);
+
+        assert_eq!(
+            nonce,
+            *b"\x9e\xc0\x81\x08\x01\x88\x17\x00\xe7\x08\x14\x01\x2d"
+        );
+    }
+
+    #[test]
+    fn hashes_install_code() {
+        // Example install code with its CRC, and the link key derived from it.
+        let install_code =
+            b"\x83\xfe\xd3\x40\x7a\x93\x97\x23\xa5\xc6\x39\xb2\x69\x16\xd5\x05\xc3\xb5";
+
+        assert_eq!(
+            mmo_hash(install_code),
+            *b"\x66\xb6\x90\x09\x81\xe1\xee\x3c\xa4\x20\x6b\x6b\x86\x1c\x02\xbb"
+        );
+    }
+
+    #[test]
+    fn decrypts_secured_frame() {
+        let mut frame = [0u8; 39];
+        frame.copy_from_slice(SECURED_FRAME);
+
+        let packet = decrypt_frame(&mut frame, &KEY, DEFAULT_SECURITY_LEVEL).unwrap();
+
+        assert_eq!(packet.destination, 0xfffd);
+        assert_eq!(packet.sequence_number, 0x2a);
+        assert_eq!(
+            packet.payload,
+            b"\x40\x0a\x06\x00\x04\x01\x01\xab\x18\x0a\x0b\x05\x00"
+        );
+    }
+
+    #[test]
+    fn decrypt_leaves_security_control_field_as_received() {
+        let mut frame = [0u8; 39];
+        frame.copy_from_slice(SECURED_FRAME);
+
+        decrypt_frame(&mut frame, &KEY, DEFAULT_SECURITY_LEVEL).unwrap();
+
+        assert_eq!(frame[8], 0x28);
+    }
+
+    #[test]
+    fn rejects_frame_with_wrong_key() {
+        let mut frame = [0u8; 39];
+        frame.copy_from_slice(SECURED_FRAME);
+
+        let mut key = KEY;
+        key[0] ^= 0xff;
+
+        assert_eq!(
+            decrypt_frame(&mut frame, &key, DEFAULT_SECURITY_LEVEL).unwrap_err(),
+            SecurityError::AuthenticationFailed
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_frame() {
+        let mut frame = [0u8; 39];
+        frame.copy_from_slice(SECURED_FRAME);
+        // Bump the radius, which is authenticated but not encrypted.
+        frame[6] = 0x1d;
+
+        assert_eq!(
+            decrypt_frame(&mut frame, &KEY, DEFAULT_SECURITY_LEVEL).unwrap_err(),
+            SecurityError::AuthenticationFailed
+        );
+    }
+
+    #[test]
+    fn rejects_unsecured_frame() {
+        let mut frame = *b"\x08\x00\xfd\xff\x00\x00\x1e\x07\x01";
+
+        assert_eq!(
+            decrypt_frame(&mut frame, &KEY, DEFAULT_SECURITY_LEVEL).unwrap_err(),
+            SecurityError::MalformedFrame
+        );
+    }
+
+    fn unsecured_packet(payload: &[u8]) -> ZigbeePacket<'_> {
+        ZigbeePacket {
+            frame_control_field: crate::network_layer::FrameControlField::from(0x0008),
+            destination: 0xfffd,
+            source: 0x0000,
+            radius: 0x1e,
+            sequence_number: 0x2a,
+            extended_destination: None,
+            extended_source: None,
+            multicast_control: None,
+            source_route: None,
+            security_header: None,
+            payload,
+        }
+    }
+
+    #[test]
+    fn encrypts_frame() {
+        let mut context = SecurityContext::new(0x00_17_88_01_08_81_c0_9e);
+        context.outgoing_frame_counter = 18090215;
+
+        let packet = unsecured_packet(b"\x40\x0a\x06\x00\x04\x01\x01\xab\x18\x0a\x0b\x05\x00");
+
+        let mut buffer = [0u8; 128];
+        let len = context.encrypt_frame(&packet, &KEY, &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..len], SECURED_FRAME);
+        assert_eq!(context.outgoing_frame_counter, 18090216);
+    }
+
+    #[test]
+    fn encrypted_frames_decrypt() {
+        let mut context = SecurityContext::new(0x42_42_42_42_42_42_42_42);
+        let packet = unsecured_packet(b"hello zigbee");
+
+        for expected_counter in 0..3 {
+            let mut buffer = [0u8; 128];
+            let len = context.encrypt_frame(&packet, &KEY, &mut buffer).unwrap();
+
+            let decrypted =
+                decrypt_frame(&mut buffer[..len], &KEY, DEFAULT_SECURITY_LEVEL).unwrap();
+            assert_eq!(decrypted.payload, b"hello zigbee");
+
+            let header = decrypted.security_header.unwrap();
+            assert_eq!(header.frame_counter, expected_counter);
+            assert_eq!(header.extended_source, Some(0x42_42_42_42_42_42_42_42));
+        }
+    }
+
+    #[test]
+    fn refuses_to_encrypt_with_exhausted_frame_counter() {
+        let mut context = SecurityContext::new(0x42_42_42_42_42_42_42_42);
+        context.outgoing_frame_counter = u32::MAX;
+
+        let mut buffer = [0u8; 128];
+        assert_eq!(
+            context
+                .encrypt_frame(&unsecured_packet(b"\x01"), &KEY, &mut buffer)
+                .unwrap_err(),
+            SecurityError::FrameCounterExhausted
+        );
+    }
+
+    #[test]
+    fn encrypts_and_decrypts_at_every_security_level() {
+        for security_level in 1..8 {
+            let mut context = SecurityContext::new(0x42_42_42_42_42_42_42_42);
+            context.security_level = security_level;
+
+            let mut buffer = [0u8; 128];
+            let len = context
+                .encrypt_frame(&unsecured_packet(b"hello zigbee"), &KEY, &mut buffer)
+                .unwrap();
+
+            // Security level is never sent over the air.
+            assert_eq!(buffer[8] & 0b111, 0);
+            let mic_length = [0, 4, 8, 16][(security_level & 0b11) as usize];
+            assert_eq!(len, 22 + 12 + mic_length);
+
+            // Payload is only hidden when using encryption.
+            let encrypted = security_level >= 4;
+            assert_eq!(&buffer[22..34] != b"hello zigbee", encrypted);
+
+            let decrypted = decrypt_frame(&mut buffer[..len], &KEY, security_level).unwrap();
+            assert_eq!(decrypted.payload, b"hello zigbee");
+        }
+    }
+
+    #[test]
+    fn refuses_to_secure_frames_at_security_level_0() {
+        let mut context = SecurityContext::new(0x42_42_42_42_42_42_42_42);
+        context.security_level = 0;
+
+        let mut buffer = [0u8; 128];
+        assert_eq!(
+            context
+                .encrypt_frame(&unsecured_packet(b"\x01"), &KEY, &mut buffer)
+                .unwrap_err(),
+            SecurityError::InvalidSecurityLevel
+        );
+        assert_eq!(context.outgoing_frame_counter, 0);
+    }
+
+    #[test]
+    fn authenticates_frames_without_encryption() {
+        let mut context = SecurityContext::new(0x42_42_42_42_42_42_42_42);
+        context.security_level = 2;
+
+        let mut buffer = [0u8; 128];
+        let len = context
+            .encrypt_frame(&unsecured_packet(b"hello zigbee"), &KEY, &mut buffer)
+            .unwrap();
+        // Flip a bit in the plaintext payload.
+        buffer[22] ^= 1;
+
+        assert_eq!(
+            decrypt_frame(&mut buffer[..len], &KEY, 2).unwrap_err(),
+            SecurityError::AuthenticationFailed
+        );
+    }
+}
@@ -0,0 +1,594 @@
+use crate::hardware::{
+    Clock, Radio, Random, ReceiveInfo, Storage, StorageError, StorageKey, MAX_CHANNEL, MIN_CHANNEL,
+};
+use crate::ZigbeeHardware;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Time to send one byte at the 250 kbit/s of the 2.4 GHz band.
+const BYTE_DURATION_US: u64 = 32;
+/// Bytes sent over the air on top of the frame: the preamble, start of
+/// frame delimiter, length and frame check sequence.
+const FRAME_OVERHEAD: usize = 4 + 1 + 1 + 2;
+/// How far the clock moves forward each time a node polls for a frame
+/// while none is pending, one symbol period.
+const IDLE_TICK_US: u64 = 16;
+/// Energy level from which clear channel assessment finds a channel busy.
+const CCA_THRESHOLD_DBM: i8 = -75;
+/// Lowest energy level `energy_detect` reports, as 0.
+const ENERGY_DETECT_FLOOR_DBM: i8 = -93;
+
+/// How frames sent by one node are received by another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Link {
+    /// Signal strength the frames are received with when sent at 0 dBm,
+    /// in dBm. It changes along with the sender's transmit power.
+    pub rssi: i8,
+    pub lqi: u8,
+    /// Probability of a frame getting lost, from 0.0 to 1.0.
+    pub loss: f32,
+    /// Delay before a frame is received, on top of the time it takes to
+    /// send it.
+    pub latency_us: u32,
+}
+impl Default for Link {
+    fn default() -> Self {
+        Self {
+            rssi: -50,
+            lqi: 255,
+            loss: 0.0,
+            latency_us: 0,
+        }
+    }
+}
+
+/// Run for a node whenever another node polls for a frame, see
+/// `Medium::set_idle_handler`.
+type IdleHandler = Box<dyn FnMut(&SimulatedNode)>;
+
+struct Delivery {
+    /// Virtual time at which the frame has been received.
+    at_us: u64,
+    frame: Vec<u8>,
+    rssi: i8,
+    lqi: u8,
+}
+
+struct NodeState {
+    extended_address: u64,
+    channel: u8,
+    tx_power: i8,
+    /// Energy on the channel from sources outside the simulation.
+    noise_dbm: Option<i8>,
+    /// Frames sent to this node, in the order they are received.
+    inbox: VecDeque<Delivery>,
+    /// Frames sent by this node, oldest first.
+    transmitted: Vec<Vec<u8>>,
+    random_state: u32,
+    /// When the alarm goes off, on the medium's clock.
+    alarm_us: Option<u64>,
+    storage: Vec<(StorageKey, Vec<u8>)>,
+    idle_handler: Option<IdleHandler>,
+}
+
+struct MediumState {
+    now_us: u64,
+    nodes: Vec<NodeState>,
+    /// Links by sending and receiving node. Nodes without a link can't
+    /// hear each other.
+    links: Vec<((usize, usize), Link)>,
+    random_state: u32,
+    /// Whether idle handlers are being run, so they don't run themselves
+    /// again while polling for frames.
+    running_idle_handlers: bool,
+}
+
+/// An in-memory radio medium connecting simulated nodes, to test flows
+/// between several devices on the host.
+///
+/// All nodes share a virtual clock, which only moves forward when a node
+/// sends a frame, waits, or polls for a frame while none is pending. Frame
+/// losses are drawn from a generator seeded when the medium is created, so
+/// a simulation always runs the same way.
+///
+/// The stack blocks while waiting for a reply, so nodes other than the one
+/// a test drives react to frames from an idle handler, which is run
+/// whenever another node polls for a frame.
+#[derive(Clone)]
+pub struct Medium {
+    state: Rc<RefCell<MediumState>>,
+}
+impl Medium {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(MediumState {
+                now_us: 0,
+                nodes: Vec::new(),
+                links: Vec::new(),
+                random_state: seed.max(1),
+                running_idle_handlers: false,
+            })),
+        }
+    }
+
+    /// Add a node listening on `MIN_CHANNEL`, without any links.
+    pub fn add_node(&self, extended_address: u64) -> SimulatedNode {
+        let mut state = self.state.borrow_mut();
+        let id = state.nodes.len();
+        state.nodes.push(NodeState {
+            extended_address,
+            channel: MIN_CHANNEL,
+            tx_power: 0,
+            noise_dbm: None,
+            inbox: VecDeque::new(),
+            transmitted: Vec::new(),
+            random_state: (extended_address as u32 ^ (extended_address >> 32) as u32).max(1),
+            alarm_us: None,
+            storage: Vec::new(),
+            idle_handler: None,
+        });
+
+        SimulatedNode {
+            medium: self.clone(),
+            id,
+        }
+    }
+
+    /// Set how frames from `from` are received by `to`, in this direction
+    /// only.
+    pub fn set_link(&self, from: &SimulatedNode, to: &SimulatedNode, link: Link) {
+        let mut state = self.state.borrow_mut();
+        let key = (from.id, to.id);
+        state.links.retain(|(existing, _)| *existing != key);
+        state.links.push((key, link));
+    }
+
+    /// Link `a` and `b` the same way in both directions.
+    pub fn connect(&self, a: &SimulatedNode, b: &SimulatedNode, link: Link) {
+        self.set_link(a, b, link);
+        self.set_link(b, a, link);
+    }
+
+    /// Remove the links between `a` and `b`, in both directions.
+    pub fn disconnect(&self, a: &SimulatedNode, b: &SimulatedNode) {
+        self.state.borrow_mut().links.retain(|((from, to), _)| {
+            !(*from == a.id && *to == b.id || *from == b.id && *to == a.id)
+        });
+    }
+
+    /// Set the energy `node` measures on its channel from sources outside
+    /// the simulation, e.g. Wi-Fi, or `None` for a quiet channel.
+    pub fn set_noise(&self, node: &SimulatedNode, noise_dbm: Option<i8>) {
+        self.state.borrow_mut().nodes[node.id].noise_dbm = noise_dbm;
+    }
+
+    /// Run `handler` for `node` whenever another node polls for a frame,
+    /// e.g. to receive and answer frames with the node's MAC.
+    pub fn set_idle_handler(
+        &self,
+        node: &SimulatedNode,
+        handler: impl FnMut(&SimulatedNode) + 'static,
+    ) {
+        self.state.borrow_mut().nodes[node.id].idle_handler = Some(Box::new(handler));
+    }
+
+    pub fn now_us(&self) -> u64 {
+        self.state.borrow().now_us
+    }
+
+    pub fn advance_us(&self, duration: u64) {
+        self.state.borrow_mut().now_us += duration;
+    }
+
+    /// Run the idle handlers of all nodes but `polling`.
+    fn run_idle_handlers(&self, polling: usize) {
+        let node_count = {
+            let mut state = self.state.borrow_mut();
+            if state.running_idle_handlers {
+                return;
+            }
+            state.running_idle_handlers = true;
+            state.nodes.len()
+        };
+
+        for id in (0..node_count).filter(|id| *id != polling) {
+            // Taken out while it runs, as it uses the medium itself.
+            let handler = self.state.borrow_mut().nodes[id].idle_handler.take();
+            if let Some(mut handler) = handler {
+                handler(&SimulatedNode {
+                    medium: self.clone(),
+                    id,
+                });
+                self.state.borrow_mut().nodes[id].idle_handler = Some(handler);
+            }
+        }
+
+        self.state.borrow_mut().running_idle_handlers = false;
+    }
+}
+
+impl MediumState {
+    /// Whether a frame sent over `link` gets lost.
+    fn is_lost(&mut self, link: &Link) -> bool {
+        if link.loss <= 0.0 {
+            return false;
+        }
+        let random = xorshift(&mut self.random_state);
+        (random as f64 / u32::MAX as f64) < link.loss as f64
+    }
+}
+
+/// One node of a `Medium`, the hardware its stack runs on.
+#[derive(Clone)]
+pub struct SimulatedNode {
+    medium: Medium,
+    id: usize,
+}
+impl SimulatedNode {
+    pub fn medium(&self) -> &Medium {
+        &self.medium
+    }
+
+    /// Frames sent by this node, oldest first.
+    pub fn transmitted(&self) -> Vec<Vec<u8>> {
+        self.medium.state.borrow().nodes[self.id]
+            .transmitted
+            .clone()
+    }
+
+    pub fn channel(&self) -> u8 {
+        self.medium.state.borrow().nodes[self.id].channel
+    }
+
+    pub fn tx_power(&self) -> i8 {
+        self.medium.state.borrow().nodes[self.id].tx_power
+    }
+
+    /// Take the next frame received by now, if any.
+    fn take_received(&self, buffer: &mut [u8]) -> Option<ReceiveInfo> {
+        let mut state = self.medium.state.borrow_mut();
+        let now_us = state.now_us;
+        let node = &mut state.nodes[self.id];
+        if node.inbox.front()?.at_us > now_us {
+            return None;
+        }
+
+        let delivery = node.inbox.pop_front()?;
+        // Like a radio, drop frames that don't fit.
+        let length = delivery.frame.len();
+        buffer.get_mut(..length)?.copy_from_slice(&delivery.frame);
+        Some(ReceiveInfo {
+            length,
+            rssi: delivery.rssi,
+            lqi: delivery.lqi,
+            timestamp_us: delivery.at_us as u32,
+        })
+    }
+}
+
+impl ZigbeeHardware for SimulatedNode {
+    fn connect(&self) -> bool {
+        true
+    }
+
+    fn extended_address(&self) -> u64 {
+        self.medium.state.borrow().nodes[self.id].extended_address
+    }
+}
+
+impl Radio for SimulatedNode {
+    fn transmit(&self, frame: &[u8]) {
+        let mut state = self.medium.state.borrow_mut();
+        let duration = (frame.len() + FRAME_OVERHEAD) as u64 * BYTE_DURATION_US;
+        state.now_us += duration;
+
+        let sender = &mut state.nodes[self.id];
+        sender.transmitted.push(frame.to_vec());
+        let (channel, tx_power) = (sender.channel, sender.tx_power);
+
+        let links: Vec<_> = state
+            .links
+            .iter()
+            .filter(|((from, _), _)| *from == self.id)
+            .map(|((_, to), link)| (*to, *link))
+            .collect();
+        for (to, link) in links {
+            if state.nodes[to].channel != channel || state.is_lost(&link) {
+                continue;
+            }
+
+            let delivery = Delivery {
+                at_us: state.now_us + link.latency_us as u64,
+                frame: frame.to_vec(),
+                rssi: link.rssi.saturating_add(tx_power),
+                lqi: link.lqi,
+            };
+            // Keep the inbox in the order frames are received, frames with
+            // the same reception time in the order they were sent.
+            let inbox = &mut state.nodes[to].inbox;
+            let position = inbox.partition_point(|queued| queued.at_us <= delivery.at_us);
+            inbox.insert(position, delivery);
+        }
+    }
+
+    fn receive(&self, buffer: &mut [u8]) -> Option<ReceiveInfo> {
+        if let Some(info) = self.take_received(buffer) {
+            return Some(info);
+        }
+
+        // Let the other nodes answer frames this node sent.
+        self.medium.run_idle_handlers(self.id);
+        if let Some(info) = self.take_received(buffer) {
+            return Some(info);
+        }
+
+        // Let time pass while nothing is received, so loops waiting for a
+        // frame eventually time out. Idle handlers polling for frames
+        // don't, so time doesn't depend on how many nodes there are.
+        if !self.medium.state.borrow().running_idle_handlers {
+            self.medium.advance_us(IDLE_TICK_US);
+        }
+        None
+    }
+
+    fn set_channel(&self, channel: u8) -> bool {
+        if !(MIN_CHANNEL..=MAX_CHANNEL).contains(&channel) {
+            return false;
+        }
+        let mut state = self.medium.state.borrow_mut();
+        let node = &mut state.nodes[self.id];
+        node.channel = channel;
+        // Frames sent on the previous channel are lost.
+        node.inbox.clear();
+        true
+    }
+
+    fn set_tx_power(&self, dbm: i8) {
+        self.medium.state.borrow_mut().nodes[self.id].tx_power = dbm;
+    }
+
+    fn clear_channel_assessment(&self) -> bool {
+        match self.medium.state.borrow().nodes[self.id].noise_dbm {
+            Some(noise_dbm) => noise_dbm < CCA_THRESHOLD_DBM,
+            None => true,
+        }
+    }
+
+    fn energy_detect(&self) -> u8 {
+        match self.medium.state.borrow().nodes[self.id].noise_dbm {
+            Some(noise_dbm) => {
+                let level = (noise_dbm as i32 - ENERGY_DETECT_FLOOR_DBM as i32) * 4;
+                level.clamp(0, 255) as u8
+            }
+            None => 0,
+        }
+    }
+}
+
+impl Clock for SimulatedNode {
+    fn now_ms(&self) -> u32 {
+        (self.medium.now_us() / 1000) as u32
+    }
+
+    fn now_us(&self) -> u32 {
+        self.medium.now_us() as u32
+    }
+
+    fn delay_us(&self, duration: u32) {
+        self.medium.advance_us(duration as u64);
+    }
+
+    fn set_alarm_us(&self, at: u32) {
+        let now_us = self.medium.now_us();
+        let delay = at.wrapping_sub(now_us as u32) as u64;
+        self.medium.state.borrow_mut().nodes[self.id].alarm_us = Some(now_us + delay);
+    }
+
+    fn alarm_expired(&self) -> bool {
+        let now_us = self.medium.now_us();
+        self.medium.state.borrow().nodes[self.id]
+            .alarm_us
+            .is_some_and(|alarm_us| now_us >= alarm_us)
+    }
+
+    fn cancel_alarm(&self) {
+        self.medium.state.borrow_mut().nodes[self.id].alarm_us = None;
+    }
+}
+
+impl Random for SimulatedNode {
+    fn random_u32(&self) -> u32 {
+        xorshift(&mut self.medium.state.borrow_mut().nodes[self.id].random_state)
+    }
+}
+
+impl Storage for SimulatedNode {
+    fn read(&self, key: StorageKey, buffer: &mut [u8]) -> Option<usize> {
+        let state = self.medium.state.borrow();
+        let storage = &state.nodes[self.id].storage;
+        let (_, value) = storage.iter().find(|(stored, _)| *stored == key)?;
+        buffer.get_mut(..value.len())?.copy_from_slice(value);
+        Some(value.len())
+    }
+
+    fn write(&self, key: StorageKey, value: &[u8]) -> Result<(), StorageError> {
+        let mut state = self.medium.state.borrow_mut();
+        let storage = &mut state.nodes[self.id].storage;
+        storage.retain(|(stored, _)| *stored != key);
+        storage.push((key, value.to_vec()));
+        Ok(())
+    }
+}
+
+fn xorshift(state: &mut u32) -> u32 {
+    let mut x = *state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    *state = x;
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::initialize_zigbee_stack;
+    use crate::mac::{Mac, MacError, MAX_FRAME_LENGTH};
+    use ieee802154::mac::{PanId, ShortAddress};
+
+    #[test]
+    fn delivers_frames_over_links() {
+        let medium = Medium::new(1);
+        let a = medium.add_node(0x1111);
+        let b = medium.add_node(0x2222);
+        let c = medium.add_node(0x3333);
+        medium.set_link(
+            &a,
+            &b,
+            Link {
+                rssi: -60,
+                lqi: 200,
+                latency_us: 100,
+                ..Link::default()
+            },
+        );
+        let mut buffer = [0u8; MAX_FRAME_LENGTH];
+
+        a.set_tx_power(4);
+        a.transmit(b"\x01\x02\x03");
+        // Sending took (3 + 8) * 32 µs.
+        assert_eq!(medium.now_us(), 352);
+        assert_eq!(a.transmitted(), [b"\x01\x02\x03".to_vec()]);
+
+        // Not received before the latency has passed.
+        assert_eq!(b.take_received(&mut buffer), None);
+        medium.advance_us(100);
+        let info = b.receive(&mut buffer).unwrap();
+        assert_eq!(&buffer[..info.length], b"\x01\x02\x03");
+        assert_eq!(info.rssi, -56);
+        assert_eq!(info.lqi, 200);
+        assert_eq!(info.timestamp_us, 452);
+
+        // No link from b to a, or to c.
+        b.transmit(b"\x04");
+        assert!(a.receive(&mut buffer).is_none());
+        assert!(c.receive(&mut buffer).is_none());
+
+        // Nothing is received on another channel.
+        assert!(b.set_channel(MAX_CHANNEL));
+        assert!(!b.set_channel(MAX_CHANNEL + 1));
+        a.transmit(b"\x05");
+        medium.advance_us(100);
+        assert!(b.receive(&mut buffer).is_none());
+    }
+
+    #[test]
+    fn loses_frames_deterministically() {
+        let received = |seed| {
+            let medium = Medium::new(seed);
+            let a = medium.add_node(0x1111);
+            let b = medium.add_node(0x2222);
+            let lossy = Link {
+                loss: 0.5,
+                ..Link::default()
+            };
+            medium.connect(&a, &b, lossy);
+
+            let mut buffer = [0u8; MAX_FRAME_LENGTH];
+            let mut received = Vec::new();
+            for i in 0..32u8 {
+                a.transmit(&[i]);
+                if b.receive(&mut buffer).is_some() {
+                    received.push(buffer[0]);
+                }
+            }
+            received
+        };
+
+        let first = received(42);
+        assert!(!first.is_empty() && first.len() < 32);
+        assert_eq!(received(42), first);
+    }
+
+    #[test]
+    fn measures_noise() {
+        let medium = Medium::new(1);
+        let node = medium.add_node(0x1111);
+        assert!(node.clear_channel_assessment());
+        assert_eq!(node.energy_detect(), 0);
+
+        medium.set_noise(&node, Some(-70));
+        assert!(!node.clear_channel_assessment());
+        assert_eq!(node.energy_detect(), 92);
+
+        medium.set_noise(&node, Some(-80));
+        assert!(node.clear_channel_assessment());
+    }
+
+    #[test]
+    fn acknowledges_frames_between_stacks() {
+        let medium = Medium::new(1);
+        let device = medium.add_node(0x00_12_4b_00_00_00_00_01);
+        let coordinator = medium.add_node(0x00_12_4b_00_00_00_00_02);
+        medium.connect(&device, &coordinator, Link::default());
+
+        // The coordinator receives, and so acknowledges, frames whenever
+        // the device waits for one.
+        let mut coordinator_mac = Mac::new(coordinator.extended_address());
+        coordinator_mac.pan_id = PanId(0xd721);
+        coordinator_mac.short_address = Some(ShortAddress(0x0000));
+        let payloads = Rc::new(RefCell::new(Vec::new()));
+        let received = payloads.clone();
+        medium.set_idle_handler(&coordinator, move |hardware| {
+            let mut buffer = [0u8; MAX_FRAME_LENGTH];
+            if let Some((frame, _)) = coordinator_mac.receive(hardware, &mut buffer) {
+                received.borrow_mut().push(frame.payload.to_vec());
+            }
+        });
+
+        let mut stack = initialize_zigbee_stack(&device).unwrap();
+        stack.mac.pan_id = PanId(0xd721);
+        stack.mac.short_address = Some(ShortAddress(0x1234));
+        stack
+            .mac
+            .send_data(&device, ShortAddress(0x0000), b"\x01")
+            .unwrap();
+        assert_eq!(*payloads.borrow(), [b"\x01".to_vec()]);
+
+        // Nothing comes back once the coordinator is out of range.
+        medium.disconnect(&device, &coordinator);
+        assert_eq!(
+            stack.mac.send_data(&device, ShortAddress(0x0000), b"\x02"),
+            Err(MacError::NoAck)
+        );
+        assert_eq!(payloads.borrow().len(), 1);
+    }
+
+    #[test]
+    fn sets_off_alarms_on_the_shared_clock() {
+        let medium = Medium::new(1);
+        let a = medium.add_node(0x1111);
+        let b = medium.add_node(0x2222);
+
+        assert!(!a.alarm_expired());
+        a.set_alarm_us(500);
+        b.delay_us(499);
+        assert!(!a.alarm_expired());
+        assert!(!b.alarm_expired());
+        medium.advance_us(1);
+        assert!(a.alarm_expired());
+        medium.advance_us(1_000);
+        assert!(a.alarm_expired());
+        a.cancel_alarm();
+        assert!(!a.alarm_expired());
+
+        // Armed across a wraparound of `now_us`.
+        medium.advance_us(u32::MAX as u64 - medium.now_us() - 0xf);
+        a.set_alarm_us(0x10);
+        medium.advance_us(0x10);
+        assert!(!a.alarm_expired());
+        medium.advance_us(0x10);
+        assert!(a.alarm_expired());
+    }
+}
@@ -0,0 +1,112 @@
+/// Fixed capacity storage for the stack's tables, as there is no allocator.
+#[derive(Debug, Clone)]
+pub struct Table<T, const N: usize> {
+    entries: [Option<T>; N],
+}
+impl<T: Copy, const N: usize> Table<T, N> {
+    pub const fn new() -> Self {
+        Self { entries: [None; N] }
+    }
+
+    /// Store `entry` in a free slot.
+    pub fn insert(&mut self, entry: T) -> Result<(), TableFull> {
+        let slot = self
+            .entries
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(TableFull)?;
+        *slot = Some(entry);
+        Ok(())
+    }
+
+    /// Replace the first entry accepted by `matches` with `entry`, or store
+    /// it in a free slot if there is none.
+    pub fn upsert(&mut self, entry: T, matches: impl Fn(&T) -> bool) -> Result<(), TableFull> {
+        match self.find_mut(matches) {
+            Some(existing) => {
+                *existing = entry;
+                Ok(())
+            }
+            None => self.insert(entry),
+        }
+    }
+
+    /// Remove every entry accepted by `matches`, returning how many were
+    /// removed.
+    pub fn remove_where(&mut self, matches: impl Fn(&T) -> bool) -> usize {
+        let mut removed = 0;
+        for slot in self.entries.iter_mut() {
+            if slot.as_ref().is_some_and(&matches) {
+                *slot = None;
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    pub fn find(&self, matches: impl Fn(&T) -> bool) -> Option<&T> {
+        self.iter().find(|entry| matches(entry))
+    }
+
+    pub fn find_mut(&mut self, matches: impl Fn(&T) -> bool) -> Option<&mut T> {
+        self.iter_mut().find(|entry| matches(entry))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().filter_map(|slot| slot.as_ref())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.entries.iter_mut().filter_map(|slot| slot.as_mut())
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+}
+impl<T: Copy, const N: usize> Default for Table<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TableFull;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserts_until_full() {
+        let mut table = Table::<u8, 2>::new();
+
+        assert_eq!(table.insert(1), Ok(()));
+        assert_eq!(table.insert(2), Ok(()));
+        assert_eq!(table.insert(3), Err(TableFull));
+        assert_eq!(table.len(), 2);
+
+        assert_eq!(table.remove_where(|entry| *entry == 1), 1);
+        assert_eq!(table.insert(3), Ok(()));
+        assert_eq!(table.iter().copied().collect::<Vec<_>>(), [3, 2]);
+    }
+
+    #[test]
+    fn upsert_replaces_matching_entry() {
+        let mut table = Table::<(u8, u8), 2>::new();
+
+        table.upsert((1, 10), |entry| entry.0 == 1).unwrap();
+        table.upsert((1, 11), |entry| entry.0 == 1).unwrap();
+
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.find(|entry| entry.0 == 1), Some(&(1, 11)));
+    }
+}
@@ -0,0 +1,727 @@
+use crate::network_layer::{ParseError, WriteError};
+use byte::ctx::Bytes;
+use byte::{BytesExt, LE};
+
+/// Identifiers for the ZCL data types, as sent before attribute values.
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum ZclDataType {
+    NoData = 0x00,
+    Data8 = 0x08,
+    Data16 = 0x09,
+    Data24 = 0x0a,
+    Data32 = 0x0b,
+    Data40 = 0x0c,
+    Data48 = 0x0d,
+    Data56 = 0x0e,
+    Data64 = 0x0f,
+    Bool = 0x10,
+    Map8 = 0x18,
+    Map16 = 0x19,
+    Map24 = 0x1a,
+    Map32 = 0x1b,
+    Map40 = 0x1c,
+    Map48 = 0x1d,
+    Map56 = 0x1e,
+    Map64 = 0x1f,
+    Uint8 = 0x20,
+    Uint16 = 0x21,
+    Uint24 = 0x22,
+    Uint32 = 0x23,
+    Uint40 = 0x24,
+    Uint48 = 0x25,
+    Uint56 = 0x26,
+    Uint64 = 0x27,
+    Int8 = 0x28,
+    Int16 = 0x29,
+    Int24 = 0x2a,
+    Int32 = 0x2b,
+    Int40 = 0x2c,
+    Int48 = 0x2d,
+    Int56 = 0x2e,
+    Int64 = 0x2f,
+    Enum8 = 0x30,
+    Enum16 = 0x31,
+    Semi = 0x38,
+    Single = 0x39,
+    Double = 0x3a,
+    OctetString = 0x41,
+    CharacterString = 0x42,
+    LongOctetString = 0x43,
+    LongCharacterString = 0x44,
+    Array = 0x48,
+    Structure = 0x4c,
+    Set = 0x50,
+    Bag = 0x51,
+    TimeOfDay = 0xe0,
+    Date = 0xe1,
+    Utc = 0xe2,
+    ClusterId = 0xe8,
+    AttributeId = 0xe9,
+    BacnetOid = 0xea,
+    Eui64 = 0xf0,
+    SecurityKey = 0xf1,
+    Unknown = 0xff,
+}
+impl TryFrom<u8> for ZclDataType {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use ZclDataType::*;
+
+        let data_type = match value {
+            0x00 => NoData,
+            0x08 => Data8,
+            0x09 => Data16,
+            0x0a => Data24,
+            0x0b => Data32,
+            0x0c => Data40,
+            0x0d => Data48,
+            0x0e => Data56,
+            0x0f => Data64,
+            0x10 => Bool,
+            0x18 => Map8,
+            0x19 => Map16,
+            0x1a => Map24,
+            0x1b => Map32,
+            0x1c => Map40,
+            0x1d => Map48,
+            0x1e => Map56,
+            0x1f => Map64,
+            0x20 => Uint8,
+            0x21 => Uint16,
+            0x22 => Uint24,
+            0x23 => Uint32,
+            0x24 => Uint40,
+            0x25 => Uint48,
+            0x26 => Uint56,
+            0x27 => Uint64,
+            0x28 => Int8,
+            0x29 => Int16,
+            0x2a => Int24,
+            0x2b => Int32,
+            0x2c => Int40,
+            0x2d => Int48,
+            0x2e => Int56,
+            0x2f => Int64,
+            0x30 => Enum8,
+            0x31 => Enum16,
+            0x38 => Semi,
+            0x39 => Single,
+            0x3a => Double,
+            0x41 => OctetString,
+            0x42 => CharacterString,
+            0x43 => LongOctetString,
+            0x44 => LongCharacterString,
+            0x48 => Array,
+            0x4c => Structure,
+            0x50 => Set,
+            0x51 => Bag,
+            0xe0 => TimeOfDay,
+            0xe1 => Date,
+            0xe2 => Utc,
+            0xe8 => ClusterId,
+            0xe9 => AttributeId,
+            0xea => BacnetOid,
+            0xf0 => Eui64,
+            0xf1 => SecurityKey,
+            0xff => Unknown,
+            _ => return Err(()),
+        };
+        Ok(data_type)
+    }
+}
+
+impl ZclDataType {
+    /// Analog types are compared by how much they changed, discrete types
+    /// only by whether they changed. Reporting configurations carry a
+    /// reportable change only for analog attributes.
+    pub fn is_analog(&self) -> bool {
+        use ZclDataType::*;
+
+        matches!(
+            self,
+            Uint8
+                | Uint16
+                | Uint24
+                | Uint32
+                | Uint40
+                | Uint48
+                | Uint56
+                | Uint64
+                | Int8
+                | Int16
+                | Int24
+                | Int32
+                | Int40
+                | Int48
+                | Int56
+                | Int64
+                | Semi
+                | Single
+                | Double
+                | TimeOfDay
+                | Date
+                | Utc
+        )
+    }
+}
+
+/// A typed ZCL value. Variable length values borrow from the frame they
+/// were parsed from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZclValue<'a> {
+    NoData,
+    Data8(u8),
+    Data16(u16),
+    Data24(u32),
+    Data32(u32),
+    Data40(u64),
+    Data48(u64),
+    Data56(u64),
+    Data64(u64),
+    /// `None` when the device reports the invalid value, 0xff.
+    Bool(Option<bool>),
+    Map8(u8),
+    Map16(u16),
+    Map24(u32),
+    Map32(u32),
+    Map40(u64),
+    Map48(u64),
+    Map56(u64),
+    Map64(u64),
+    Uint8(u8),
+    Uint16(u16),
+    Uint24(u32),
+    Uint32(u32),
+    Uint40(u64),
+    Uint48(u64),
+    Uint56(u64),
+    Uint64(u64),
+    Int8(i8),
+    Int16(i16),
+    Int24(i32),
+    Int32(i32),
+    Int40(i64),
+    Int48(i64),
+    Int56(i64),
+    Int64(i64),
+    Enum8(u8),
+    Enum16(u16),
+    /// Half precision float, kept as its raw bits.
+    Semi(u16),
+    Single(f32),
+    Double(f64),
+    OctetString(&'a [u8]),
+    /// Character strings are not guaranteed to be valid UTF-8, so they are
+    /// kept as bytes.
+    CharacterString(&'a [u8]),
+    LongOctetString(&'a [u8]),
+    LongCharacterString(&'a [u8]),
+    Array(ZclArray<'a>),
+    Structure(ZclStructure<'a>),
+    Set(ZclArray<'a>),
+    Bag(ZclArray<'a>),
+    TimeOfDay(u32),
+    Date(u32),
+    /// Seconds since 2000-01-01 00:00:00 UTC.
+    Utc(u32),
+    ClusterId(u16),
+    AttributeId(u16),
+    BacnetOid(u32),
+    Eui64(u64),
+    SecurityKey([u8; 16]),
+}
+impl<'a> ZclValue<'a> {
+    /// Parse a value of type `data_type` at `offset`. The data type itself
+    /// is not part of the value and is expected to have been read already.
+    pub fn try_parse_from(
+        data: &'a [u8],
+        offset: &mut usize,
+        data_type: ZclDataType,
+    ) -> Result<Self, ParseError> {
+        use ZclDataType as T;
+
+        let value = match data_type {
+            T::NoData | T::Unknown => ZclValue::NoData,
+            T::Data8 => ZclValue::Data8(data.read_with::<u8>(offset, LE)?),
+            T::Data16 => ZclValue::Data16(data.read_with::<u16>(offset, LE)?),
+            T::Data24 => ZclValue::Data24(read_uint(data, offset, 3)? as u32),
+            T::Data32 => ZclValue::Data32(data.read_with::<u32>(offset, LE)?),
+            T::Data40 => ZclValue::Data40(read_uint(data, offset, 5)?),
+            T::Data48 => ZclValue::Data48(read_uint(data, offset, 6)?),
+            T::Data56 => ZclValue::Data56(read_uint(data, offset, 7)?),
+            T::Data64 => ZclValue::Data64(data.read_with::<u64>(offset, LE)?),
+            T::Bool => ZclValue::Bool(match data.read_with::<u8>(offset, LE)? {
+                0x00 => Some(false),
+                0x01 => Some(true),
+                0xff => None,
+                _ => return Err(ParseError),
+            }),
+            T::Map8 => ZclValue::Map8(data.read_with::<u8>(offset, LE)?),
+            T::Map16 => ZclValue::Map16(data.read_with::<u16>(offset, LE)?),
+            T::Map24 => ZclValue::Map24(read_uint(data, offset, 3)? as u32),
+            T::Map32 => ZclValue::Map32(data.read_with::<u32>(offset, LE)?),
+            T::Map40 => ZclValue::Map40(read_uint(data, offset, 5)?),
+            T::Map48 => ZclValue::Map48(read_uint(data, offset, 6)?),
+            T::Map56 => ZclValue::Map56(read_uint(data, offset, 7)?),
+            T::Map64 => ZclValue::Map64(data.read_with::<u64>(offset, LE)?),
+            T::Uint8 => ZclValue::Uint8(data.read_with::<u8>(offset, LE)?),
+            T::Uint16 => ZclValue::Uint16(data.read_with::<u16>(offset, LE)?),
+            T::Uint24 => ZclValue::Uint24(read_uint(data, offset, 3)? as u32),
+            T::Uint32 => ZclValue::Uint32(data.read_with::<u32>(offset, LE)?),
+            T::Uint40 => ZclValue::Uint40(read_uint(data, offset, 5)?),
+            T::Uint48 => ZclValue::Uint48(read_uint(data, offset, 6)?),
+            T::Uint56 => ZclValue::Uint56(read_uint(data, offset, 7)?),
+            T::Uint64 => ZclValue::Uint64(data.read_with::<u64>(offset, LE)?),
+            T::Int8 => ZclValue::Int8(data.read_with::<i8>(offset, LE)?),
+            T::Int16 => ZclValue::Int16(data.read_with::<i16>(offset, LE)?),
+            T::Int24 => ZclValue::Int24(read_int(data, offset, 3)? as i32),
+            T::Int32 => ZclValue::Int32(data.read_with::<i32>(offset, LE)?),
+            T::Int40 => ZclValue::Int40(read_int(data, offset, 5)?),
+            T::Int48 => ZclValue::Int48(read_int(data, offset, 6)?),
+            T::Int56 => ZclValue::Int56(read_int(data, offset, 7)?),
+            T::Int64 => ZclValue::Int64(data.read_with::<i64>(offset, LE)?),
+            T::Enum8 => ZclValue::Enum8(data.read_with::<u8>(offset, LE)?),
+            T::Enum16 => ZclValue::Enum16(data.read_with::<u16>(offset, LE)?),
+            T::Semi => ZclValue::Semi(data.read_with::<u16>(offset, LE)?),
+            T::Single => ZclValue::Single(f32::from_bits(data.read_with::<u32>(offset, LE)?)),
+            T::Double => ZclValue::Double(f64::from_bits(data.read_with::<u64>(offset, LE)?)),
+            T::OctetString => ZclValue::OctetString(read_string(data, offset)?),
+            T::CharacterString => ZclValue::CharacterString(read_string(data, offset)?),
+            T::LongOctetString => ZclValue::LongOctetString(read_long_string(data, offset)?),
+            T::LongCharacterString => {
+                ZclValue::LongCharacterString(read_long_string(data, offset)?)
+            }
+            T::Array => ZclValue::Array(ZclArray::try_parse_from(data, offset)?),
+            T::Structure => ZclValue::Structure(ZclStructure::try_parse_from(data, offset)?),
+            T::Set => ZclValue::Set(ZclArray::try_parse_from(data, offset)?),
+            T::Bag => ZclValue::Bag(ZclArray::try_parse_from(data, offset)?),
+            T::TimeOfDay => ZclValue::TimeOfDay(data.read_with::<u32>(offset, LE)?),
+            T::Date => ZclValue::Date(data.read_with::<u32>(offset, LE)?),
+            T::Utc => ZclValue::Utc(data.read_with::<u32>(offset, LE)?),
+            T::ClusterId => ZclValue::ClusterId(data.read_with::<u16>(offset, LE)?),
+            T::AttributeId => ZclValue::AttributeId(data.read_with::<u16>(offset, LE)?),
+            T::BacnetOid => ZclValue::BacnetOid(data.read_with::<u32>(offset, LE)?),
+            T::Eui64 => ZclValue::Eui64(data.read_with::<u64>(offset, LE)?),
+            T::SecurityKey => {
+                let key = data.read_with::<&[u8]>(offset, Bytes::Len(16))?;
+                ZclValue::SecurityKey(key.try_into()?)
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Parse a data type identifier followed by a value of that type.
+    pub fn try_parse_typed(data: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let data_type = ZclDataType::try_from(data.read_with::<u8>(offset, LE)?)?;
+        Self::try_parse_from(data, offset, data_type)
+    }
+
+    /// Write the value at `offset`, without its data type.
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        match *self {
+            ZclValue::NoData => {}
+            ZclValue::Data8(value)
+            | ZclValue::Map8(value)
+            | ZclValue::Uint8(value)
+            | ZclValue::Enum8(value) => buffer.write_with::<u8>(offset, value, LE)?,
+            ZclValue::Data16(value)
+            | ZclValue::Map16(value)
+            | ZclValue::Uint16(value)
+            | ZclValue::Enum16(value)
+            | ZclValue::Semi(value)
+            | ZclValue::ClusterId(value)
+            | ZclValue::AttributeId(value) => buffer.write_with::<u16>(offset, value, LE)?,
+            ZclValue::Data24(value) | ZclValue::Map24(value) | ZclValue::Uint24(value) => {
+                write_uint(buffer, offset, value as u64, 3)?
+            }
+            ZclValue::Data32(value)
+            | ZclValue::Map32(value)
+            | ZclValue::Uint32(value)
+            | ZclValue::TimeOfDay(value)
+            | ZclValue::Date(value)
+            | ZclValue::Utc(value)
+            | ZclValue::BacnetOid(value) => buffer.write_with::<u32>(offset, value, LE)?,
+            ZclValue::Data40(value) | ZclValue::Map40(value) | ZclValue::Uint40(value) => {
+                write_uint(buffer, offset, value, 5)?
+            }
+            ZclValue::Data48(value) | ZclValue::Map48(value) | ZclValue::Uint48(value) => {
+                write_uint(buffer, offset, value, 6)?
+            }
+            ZclValue::Data56(value) | ZclValue::Map56(value) | ZclValue::Uint56(value) => {
+                write_uint(buffer, offset, value, 7)?
+            }
+            ZclValue::Data64(value)
+            | ZclValue::Map64(value)
+            | ZclValue::Uint64(value)
+            | ZclValue::Eui64(value) => buffer.write_with::<u64>(offset, value, LE)?,
+            ZclValue::Bool(value) => {
+                let value = match value {
+                    Some(false) => 0x00,
+                    Some(true) => 0x01,
+                    None => 0xff,
+                };
+                buffer.write_with::<u8>(offset, value, LE)?
+            }
+            ZclValue::Int8(value) => buffer.write_with::<i8>(offset, value, LE)?,
+            ZclValue::Int16(value) => buffer.write_with::<i16>(offset, value, LE)?,
+            ZclValue::Int24(value) => write_uint(buffer, offset, value as u64, 3)?,
+            ZclValue::Int32(value) => buffer.write_with::<i32>(offset, value, LE)?,
+            ZclValue::Int40(value) => write_uint(buffer, offset, value as u64, 5)?,
+            ZclValue::Int48(value) => write_uint(buffer, offset, value as u64, 6)?,
+            ZclValue::Int56(value) => write_uint(buffer, offset, value as u64, 7)?,
+            ZclValue::Int64(value) => buffer.write_with::<i64>(offset, value, LE)?,
+            ZclValue::Single(value) => buffer.write_with::<u32>(offset, value.to_bits(), LE)?,
+            ZclValue::Double(value) => buffer.write_with::<u64>(offset, value.to_bits(), LE)?,
+            ZclValue::OctetString(value) | ZclValue::CharacterString(value) => {
+                // 0xff is reserved for the invalid string.
+                let length = u8::try_from(value.len())
+                    .ok()
+                    .filter(|length| *length != 0xff)
+                    .ok_or(WriteError)?;
+                buffer.write_with::<u8>(offset, length, LE)?;
+                buffer.write(offset, value)?;
+            }
+            ZclValue::LongOctetString(value) | ZclValue::LongCharacterString(value) => {
+                let length = u16::try_from(value.len())
+                    .ok()
+                    .filter(|length| *length != 0xffff)
+                    .ok_or(WriteError)?;
+                buffer.write_with::<u16>(offset, length, LE)?;
+                buffer.write(offset, value)?;
+            }
+            ZclValue::Array(ref array) | ZclValue::Set(ref array) | ZclValue::Bag(ref array) => {
+                array.write_into(buffer, offset)?
+            }
+            ZclValue::Structure(ref structure) => structure.write_into(buffer, offset)?,
+            ZclValue::SecurityKey(ref key) => buffer.write(offset, &key[..])?,
+        }
+
+        Ok(())
+    }
+
+    /// Write the data type identifier followed by the value.
+    pub fn write_typed(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.data_type() as u8, LE)?;
+        self.write_into(buffer, offset)
+    }
+
+    pub fn data_type(&self) -> ZclDataType {
+        use ZclDataType as T;
+
+        match self {
+            ZclValue::NoData => T::NoData,
+            ZclValue::Data8(_) => T::Data8,
+            ZclValue::Data16(_) => T::Data16,
+            ZclValue::Data24(_) => T::Data24,
+            ZclValue::Data32(_) => T::Data32,
+            ZclValue::Data40(_) => T::Data40,
+            ZclValue::Data48(_) => T::Data48,
+            ZclValue::Data56(_) => T::Data56,
+            ZclValue::Data64(_) => T::Data64,
+            ZclValue::Bool(_) => T::Bool,
+            ZclValue::Map8(_) => T::Map8,
+            ZclValue::Map16(_) => T::Map16,
+            ZclValue::Map24(_) => T::Map24,
+            ZclValue::Map32(_) => T::Map32,
+            ZclValue::Map40(_) => T::Map40,
+            ZclValue::Map48(_) => T::Map48,
+            ZclValue::Map56(_) => T::Map56,
+            ZclValue::Map64(_) => T::Map64,
+            ZclValue::Uint8(_) => T::Uint8,
+            ZclValue::Uint16(_) => T::Uint16,
+            ZclValue::Uint24(_) => T::Uint24,
+            ZclValue::Uint32(_) => T::Uint32,
+            ZclValue::Uint40(_) => T::Uint40,
+            ZclValue::Uint48(_) => T::Uint48,
+            ZclValue::Uint56(_) => T::Uint56,
+            ZclValue::Uint64(_) => T::Uint64,
+            ZclValue::Int8(_) => T::Int8,
+            ZclValue::Int16(_) => T::Int16,
+            ZclValue::Int24(_) => T::Int24,
+            ZclValue::Int32(_) => T::Int32,
+            ZclValue::Int40(_) => T::Int40,
+            ZclValue::Int48(_) => T::Int48,
+            ZclValue::Int56(_) => T::Int56,
+            ZclValue::Int64(_) => T::Int64,
+            ZclValue::Enum8(_) => T::Enum8,
+            ZclValue::Enum16(_) => T::Enum16,
+            ZclValue::Semi(_) => T::Semi,
+            ZclValue::Single(_) => T::Single,
+            ZclValue::Double(_) => T::Double,
+            ZclValue::OctetString(_) => T::OctetString,
+            ZclValue::CharacterString(_) => T::CharacterString,
+            ZclValue::LongOctetString(_) => T::LongOctetString,
+            ZclValue::LongCharacterString(_) => T::LongCharacterString,
+            ZclValue::Array(_) => T::Array,
+            ZclValue::Structure(_) => T::Structure,
+            ZclValue::Set(_) => T::Set,
+            ZclValue::Bag(_) => T::Bag,
+            ZclValue::TimeOfDay(_) => T::TimeOfDay,
+            ZclValue::Date(_) => T::Date,
+            ZclValue::Utc(_) => T::Utc,
+            ZclValue::ClusterId(_) => T::ClusterId,
+            ZclValue::AttributeId(_) => T::AttributeId,
+            ZclValue::BacnetOid(_) => T::BacnetOid,
+            ZclValue::Eui64(_) => T::Eui64,
+            ZclValue::SecurityKey(_) => T::SecurityKey,
+        }
+    }
+}
+
+/// Elements of an array, set or bag, which all share the same type. The
+/// elements are kept in their over the air form and parsed when iterated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZclArray<'a> {
+    pub element_type: ZclDataType,
+    pub count: u16,
+    pub elements: &'a [u8],
+}
+impl<'a> ZclArray<'a> {
+    pub fn try_parse_from(data: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let element_type = ZclDataType::try_from(data.read_with::<u8>(offset, LE)?)?;
+        let count = data.read_with::<u16>(offset, LE)?;
+
+        // Walk over the elements to find where they end.
+        let start = *offset;
+        // 0xffff marks an invalid array with no elements.
+        if count != 0xffff {
+            for _ in 0..count {
+                ZclValue::try_parse_from(data, offset, element_type)?;
+            }
+        }
+        let elements = &data[start..*offset];
+
+        Ok(Self {
+            element_type,
+            count,
+            elements,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.element_type as u8, LE)?;
+        buffer.write_with::<u16>(offset, self.count, LE)?;
+        buffer.write(offset, self.elements)?;
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ZclValue<'a>> + 'a {
+        let elements = self.elements;
+        let element_type = self.element_type;
+        let mut offset = 0;
+        core::iter::from_fn(move || match offset < elements.len() {
+            true => ZclValue::try_parse_from(elements, &mut offset, element_type).ok(),
+            false => None,
+        })
+    }
+}
+
+/// Elements of a structure, each of which carries its own type. The
+/// elements are kept in their over the air form and parsed when iterated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZclStructure<'a> {
+    pub count: u16,
+    pub elements: &'a [u8],
+}
+impl<'a> ZclStructure<'a> {
+    pub fn try_parse_from(data: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let count = data.read_with::<u16>(offset, LE)?;
+
+        let start = *offset;
+        // 0xffff marks an invalid structure with no elements.
+        if count != 0xffff {
+            for _ in 0..count {
+                ZclValue::try_parse_typed(data, offset)?;
+            }
+        }
+        let elements = &data[start..*offset];
+
+        Ok(Self { count, elements })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.count, LE)?;
+        buffer.write(offset, self.elements)?;
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ZclValue<'a>> + 'a {
+        let elements = self.elements;
+        let mut offset = 0;
+        core::iter::from_fn(move || match offset < elements.len() {
+            true => ZclValue::try_parse_typed(elements, &mut offset).ok(),
+            false => None,
+        })
+    }
+}
+
+fn read_uint(data: &[u8], offset: &mut usize, num_bytes: usize) -> Result<u64, ParseError> {
+    let bytes = data.read_with::<&[u8]>(offset, Bytes::Len(num_bytes))?;
+
+    let mut value = [0u8; 8];
+    value[..num_bytes].copy_from_slice(bytes);
+    Ok(u64::from_le_bytes(value))
+}
+
+fn read_int(data: &[u8], offset: &mut usize, num_bytes: usize) -> Result<i64, ParseError> {
+    let value = read_uint(data, offset, num_bytes)?;
+
+    // Sign extend from the top bit of the value.
+    let unused_bits = 64 - 8 * num_bytes as u32;
+    Ok(((value << unused_bits) as i64) >> unused_bits)
+}
+
+fn write_uint(
+    buffer: &mut [u8],
+    offset: &mut usize,
+    value: u64,
+    num_bytes: usize,
+) -> Result<(), WriteError> {
+    buffer.write(offset, &value.to_le_bytes()[..num_bytes])?;
+    Ok(())
+}
+
+fn read_string<'a>(data: &'a [u8], offset: &mut usize) -> Result<&'a [u8], ParseError> {
+    let length = match data.read_with::<u8>(offset, LE)? {
+        // Invalid string, no characters follow.
+        0xff => 0,
+        length => length,
+    };
+    Ok(data.read_with::<&[u8]>(offset, Bytes::Len(length as usize))?)
+}
+
+fn read_long_string<'a>(data: &'a [u8], offset: &mut usize) -> Result<&'a [u8], ParseError> {
+    let length = match data.read_with::<u16>(offset, LE)? {
+        // Invalid string, no characters follow.
+        0xffff => 0,
+        length => length,
+    };
+    Ok(data.read_with::<&[u8]>(offset, Bytes::Len(length as usize))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trips(bytes: &[u8]) -> ZclValue<'_> {
+        let value = ZclValue::try_parse_typed(bytes, &mut 0).unwrap();
+
+        let mut buffer = [0u8; 64];
+        let offset = &mut 0;
+        value.write_typed(&mut buffer, offset).unwrap();
+        assert_eq!(&buffer[..*offset], bytes);
+
+        value
+    }
+
+    #[test]
+    fn parses_fixed_length_values() {
+        assert_eq!(assert_round_trips(b"\x10\x01"), ZclValue::Bool(Some(true)));
+        assert_eq!(assert_round_trips(b"\x10\xff"), ZclValue::Bool(None));
+        assert_eq!(assert_round_trips(b"\x18\xa5"), ZclValue::Map8(0xa5));
+        assert_eq!(
+            assert_round_trips(b"\x21\x34\x12"),
+            ZclValue::Uint16(0x1234)
+        );
+        assert_eq!(
+            assert_round_trips(b"\x22\x56\x34\x12"),
+            ZclValue::Uint24(0x123456)
+        );
+        assert_eq!(
+            assert_round_trips(b"\x25\x01\x02\x03\x04\x05\x06"),
+            ZclValue::Uint48(0x060504030201)
+        );
+        assert_eq!(assert_round_trips(b"\x29\x38\xff"), ZclValue::Int16(-200));
+        assert_eq!(assert_round_trips(b"\x30\x02"), ZclValue::Enum8(2));
+        assert_eq!(
+            assert_round_trips(b"\x39\x00\x00\xc0\x3f"),
+            ZclValue::Single(1.5)
+        );
+        assert_eq!(
+            assert_round_trips(b"\xe2\x80\x51\x01\x00"),
+            ZclValue::Utc(86400)
+        );
+        assert_eq!(
+            assert_round_trips(b"\xf0\x9e\xc0\x81\x08\x01\x88\x17\x00"),
+            ZclValue::Eui64(0x00_17_88_01_08_81_c0_9e)
+        );
+        assert_eq!(
+            assert_round_trips(b"\xf1ZigBeeAlliance09"),
+            ZclValue::SecurityKey(*b"ZigBeeAlliance09")
+        );
+    }
+
+    #[test]
+    fn sign_extends_odd_sized_integers() {
+        assert_eq!(assert_round_trips(b"\x2a\xff\xff\xff"), ZclValue::Int24(-1));
+        assert_eq!(
+            assert_round_trips(b"\x2a\xff\xff\x7f"),
+            ZclValue::Int24(0x7fffff)
+        );
+        assert_eq!(
+            assert_round_trips(b"\x2c\x00\x00\x00\x00\x80"),
+            ZclValue::Int40(-(1 << 39))
+        );
+    }
+
+    #[test]
+    fn parses_strings() {
+        assert_eq!(
+            assert_round_trips(b"\x42\x05hello"),
+            ZclValue::CharacterString(b"hello")
+        );
+        assert_eq!(
+            assert_round_trips(b"\x43\x02\x00\xaa\xbb"),
+            ZclValue::LongOctetString(b"\xaa\xbb")
+        );
+
+        let value = ZclValue::try_parse_typed(b"\x42\xff", &mut 0).unwrap();
+        assert_eq!(value, ZclValue::CharacterString(b""));
+
+        assert!(ZclValue::try_parse_typed(b"\x42\x05hell", &mut 0).is_err());
+    }
+
+    #[test]
+    fn parses_arrays() {
+        let value = assert_round_trips(b"\x48\x21\x03\x00\x01\x00\x02\x00\x03\x00");
+
+        match value {
+            ZclValue::Array(array) => {
+                assert_eq!(array.element_type, ZclDataType::Uint16);
+                assert_eq!(array.count, 3);
+
+                let mut elements = array.iter();
+                assert_eq!(elements.next(), Some(ZclValue::Uint16(1)));
+                assert_eq!(elements.next(), Some(ZclValue::Uint16(2)));
+                assert_eq!(elements.next(), Some(ZclValue::Uint16(3)));
+                assert_eq!(elements.next(), None);
+            }
+            _ => panic!("Expected an array"),
+        }
+
+        assert!(ZclValue::try_parse_typed(b"\x48\x21\x03\x00\x01\x00\x02\x00", &mut 0).is_err());
+    }
+
+    #[test]
+    fn parses_structures() {
+        let value = assert_round_trips(b"\x4c\x02\x00\x20\x07\x42\x02hi");
+
+        match value {
+            ZclValue::Structure(structure) => {
+                let mut elements = structure.iter();
+                assert_eq!(elements.next(), Some(ZclValue::Uint8(7)));
+                assert_eq!(elements.next(), Some(ZclValue::CharacterString(b"hi")));
+                assert_eq!(elements.next(), None);
+            }
+            _ => panic!("Expected a structure"),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_data_type() {
+        assert!(ZclValue::try_parse_typed(b"\x01\x00", &mut 0).is_err());
+    }
+}
@@ -0,0 +1,908 @@
+use super::data_types::{ZclDataType, ZclValue};
+use super::Status;
+use crate::network_layer::{ParseError, WriteError};
+use byte::{BytesExt, LE};
+use core::marker::PhantomData;
+
+/// Payload of a ZCL frame with `FrameType::Global`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlobalCommand<'a> {
+    ReadAttributes(RecordList<'a, u16>),
+    ReadAttributesResponse(RecordList<'a, ReadAttributeStatus<'a>>),
+    WriteAttributes(RecordList<'a, AttributeRecord<'a>>),
+    /// Either all of the attributes are written, or none of them are.
+    WriteAttributesUndivided(RecordList<'a, AttributeRecord<'a>>),
+    /// Only failed writes are listed, an empty list means every attribute
+    /// was written.
+    WriteAttributesResponse(RecordList<'a, WriteAttributeStatus>),
+    WriteAttributesNoResponse(RecordList<'a, AttributeRecord<'a>>),
+    ConfigureReporting(RecordList<'a, AttributeReportingConfiguration<'a>>),
+    /// Only failed configurations are listed, an empty list means every
+    /// configuration was accepted.
+    ConfigureReportingResponse(RecordList<'a, ConfigureReportingStatus>),
+    ReadReportingConfiguration(RecordList<'a, AttributeReportingSelector>),
+    ReadReportingConfigurationResponse(RecordList<'a, ReportingConfigurationStatus<'a>>),
+    ReportAttributes(RecordList<'a, AttributeRecord<'a>>),
+    DefaultResponse(DefaultResponse),
+    DiscoverAttributes(DiscoverAttributes),
+    DiscoverAttributesResponse(DiscoverAttributesResponse<'a>),
+    DiscoverCommandsReceived(DiscoverCommands),
+    DiscoverCommandsReceivedResponse(DiscoverCommandsResponse<'a>),
+    DiscoverCommandsGenerated(DiscoverCommands),
+    DiscoverCommandsGeneratedResponse(DiscoverCommandsResponse<'a>),
+}
+impl<'a> GlobalCommand<'a> {
+    /// Parse the payload of a global command. The command identifier is
+    /// part of the ZCL header, see `ZclHeader::command_id`.
+    pub fn try_parse_from(command_id: u8, payload: &'a [u8]) -> Result<Self, ParseError> {
+        let offset = &mut 0;
+
+        let command = match command_id {
+            0x00 => GlobalCommand::ReadAttributes(RecordList::try_parse_from(payload, offset)?),
+            0x01 => {
+                GlobalCommand::ReadAttributesResponse(RecordList::try_parse_from(payload, offset)?)
+            }
+            0x02 => GlobalCommand::WriteAttributes(RecordList::try_parse_from(payload, offset)?),
+            0x03 => GlobalCommand::WriteAttributesUndivided(RecordList::try_parse_from(
+                payload, offset,
+            )?),
+            0x04 => GlobalCommand::WriteAttributesResponse(RecordList::try_parse_statuses_from(
+                payload, offset,
+            )?),
+            0x05 => GlobalCommand::WriteAttributesNoResponse(RecordList::try_parse_from(
+                payload, offset,
+            )?),
+            0x06 => GlobalCommand::ConfigureReporting(RecordList::try_parse_from(payload, offset)?),
+            0x07 => GlobalCommand::ConfigureReportingResponse(RecordList::try_parse_statuses_from(
+                payload, offset,
+            )?),
+            0x08 => GlobalCommand::ReadReportingConfiguration(RecordList::try_parse_from(
+                payload, offset,
+            )?),
+            0x09 => GlobalCommand::ReadReportingConfigurationResponse(RecordList::try_parse_from(
+                payload, offset,
+            )?),
+            0x0a => GlobalCommand::ReportAttributes(RecordList::try_parse_from(payload, offset)?),
+            0x0b => {
+                GlobalCommand::DefaultResponse(DefaultResponse::try_parse_from(payload, offset)?)
+            }
+            0x0c => GlobalCommand::DiscoverAttributes(DiscoverAttributes::try_parse_from(
+                payload, offset,
+            )?),
+            0x0d => GlobalCommand::DiscoverAttributesResponse(
+                DiscoverAttributesResponse::try_parse_from(payload, offset)?,
+            ),
+            0x11 => GlobalCommand::DiscoverCommandsReceived(DiscoverCommands::try_parse_from(
+                payload, offset,
+            )?),
+            0x12 => GlobalCommand::DiscoverCommandsReceivedResponse(
+                DiscoverCommandsResponse::try_parse_from(payload, offset)?,
+            ),
+            0x13 => GlobalCommand::DiscoverCommandsGenerated(DiscoverCommands::try_parse_from(
+                payload, offset,
+            )?),
+            0x14 => GlobalCommand::DiscoverCommandsGeneratedResponse(
+                DiscoverCommandsResponse::try_parse_from(payload, offset)?,
+            ),
+            _ => return Err(ParseError),
+        };
+
+        Ok(command)
+    }
+
+    /// Serialize the command into `buffer` as the payload of a ZCL frame,
+    /// returning the number of bytes written.
+    pub fn write_into(&self, buffer: &mut [u8]) -> Result<usize, WriteError> {
+        let offset = &mut 0;
+
+        match self {
+            GlobalCommand::ReadAttributes(records) => records.write_into(buffer, offset)?,
+            GlobalCommand::ReadAttributesResponse(records) => records.write_into(buffer, offset)?,
+            GlobalCommand::WriteAttributes(records)
+            | GlobalCommand::WriteAttributesUndivided(records)
+            | GlobalCommand::WriteAttributesNoResponse(records)
+            | GlobalCommand::ReportAttributes(records) => records.write_into(buffer, offset)?,
+            GlobalCommand::WriteAttributesResponse(records) => {
+                records.write_statuses_into(buffer, offset)?
+            }
+            GlobalCommand::ConfigureReporting(records) => records.write_into(buffer, offset)?,
+            GlobalCommand::ConfigureReportingResponse(records) => {
+                records.write_statuses_into(buffer, offset)?
+            }
+            GlobalCommand::ReadReportingConfiguration(records) => {
+                records.write_into(buffer, offset)?
+            }
+            GlobalCommand::ReadReportingConfigurationResponse(records) => {
+                records.write_into(buffer, offset)?
+            }
+            GlobalCommand::DefaultResponse(command) => command.write_into(buffer, offset)?,
+            GlobalCommand::DiscoverAttributes(command) => command.write_into(buffer, offset)?,
+            GlobalCommand::DiscoverAttributesResponse(command) => {
+                command.write_into(buffer, offset)?
+            }
+            GlobalCommand::DiscoverCommandsReceived(command)
+            | GlobalCommand::DiscoverCommandsGenerated(command) => {
+                command.write_into(buffer, offset)?
+            }
+            GlobalCommand::DiscoverCommandsReceivedResponse(command)
+            | GlobalCommand::DiscoverCommandsGeneratedResponse(command) => {
+                command.write_into(buffer, offset)?
+            }
+        }
+
+        Ok(*offset)
+    }
+
+    pub fn command_id(&self) -> u8 {
+        match self {
+            GlobalCommand::ReadAttributes(_) => 0x00,
+            GlobalCommand::ReadAttributesResponse(_) => 0x01,
+            GlobalCommand::WriteAttributes(_) => 0x02,
+            GlobalCommand::WriteAttributesUndivided(_) => 0x03,
+            GlobalCommand::WriteAttributesResponse(_) => 0x04,
+            GlobalCommand::WriteAttributesNoResponse(_) => 0x05,
+            GlobalCommand::ConfigureReporting(_) => 0x06,
+            GlobalCommand::ConfigureReportingResponse(_) => 0x07,
+            GlobalCommand::ReadReportingConfiguration(_) => 0x08,
+            GlobalCommand::ReadReportingConfigurationResponse(_) => 0x09,
+            GlobalCommand::ReportAttributes(_) => 0x0a,
+            GlobalCommand::DefaultResponse(_) => 0x0b,
+            GlobalCommand::DiscoverAttributes(_) => 0x0c,
+            GlobalCommand::DiscoverAttributesResponse(_) => 0x0d,
+            GlobalCommand::DiscoverCommandsReceived(_) => 0x11,
+            GlobalCommand::DiscoverCommandsReceivedResponse(_) => 0x12,
+            GlobalCommand::DiscoverCommandsGenerated(_) => 0x13,
+            GlobalCommand::DiscoverCommandsGeneratedResponse(_) => 0x14,
+        }
+    }
+}
+
+/// A single entry of a record list.
+pub trait Record<'a>: Sized {
+    fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError>;
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError>;
+}
+
+/// Records that take up the rest of a command payload. The records are kept
+/// in their over the air form and parsed when iterated.
+///
+/// To send a list, write the records into a scratch buffer with
+/// `Record::write_into` and parse that buffer back into a `RecordList`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordList<'a, R> {
+    pub bytes: &'a [u8],
+    record: PhantomData<R>,
+}
+impl<'a, R: Record<'a>> RecordList<'a, R> {
+    /// Parse records until the end of `payload`, failing if any of them is
+    /// malformed or truncated.
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let start = *offset;
+        while *offset < payload.len() {
+            R::try_parse_from(payload, offset)?;
+        }
+
+        Ok(Self {
+            bytes: &payload[start..*offset],
+            record: PhantomData,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write(offset, self.bytes)?;
+        Ok(())
+    }
+
+    /// Status lists hold only failures. When every entry succeeded a
+    /// single success status is sent instead of the list.
+    fn try_parse_statuses_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        if payload[*offset..] == [Status::Success as u8] {
+            *offset += 1;
+            return Ok(Self {
+                bytes: &[],
+                record: PhantomData,
+            });
+        }
+        Self::try_parse_from(payload, offset)
+    }
+
+    fn write_statuses_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        match self.is_empty() {
+            true => buffer.write_with::<u8>(offset, Status::Success as u8, LE)?,
+            false => self.write_into(buffer, offset)?,
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = R> + 'a {
+        let bytes = self.bytes;
+        let mut offset = 0;
+        core::iter::from_fn(move || match offset < bytes.len() {
+            true => R::try_parse_from(bytes, &mut offset).ok(),
+            false => None,
+        })
+    }
+}
+
+/// Attribute identifiers, as listed in Read Attributes.
+impl<'a> Record<'a> for u16 {
+    fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        Ok(payload.read_with::<u16>(offset, LE)?)
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, *self, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadAttributeStatus<'a> {
+    pub attribute_id: u16,
+    pub status: Status,
+    /// Only present when the attribute was read successfully.
+    pub value: Option<ZclValue<'a>>,
+}
+impl<'a> Record<'a> for ReadAttributeStatus<'a> {
+    fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let attribute_id = payload.read_with::<u16>(offset, LE)?;
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let value = match status {
+            Status::Success => Some(ZclValue::try_parse_typed(payload, offset)?),
+            _ => None,
+        };
+
+        Ok(Self {
+            attribute_id,
+            status,
+            value,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.attribute_id, LE)?;
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        match (self.status, self.value) {
+            (Status::Success, Some(value)) => value.write_typed(buffer, offset)?,
+            (Status::Success, None) => return Err(WriteError),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// An attribute together with its value, as used by Write Attributes and
+/// Report Attributes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttributeRecord<'a> {
+    pub attribute_id: u16,
+    pub value: ZclValue<'a>,
+}
+impl<'a> Record<'a> for AttributeRecord<'a> {
+    fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let attribute_id = payload.read_with::<u16>(offset, LE)?;
+        let value = ZclValue::try_parse_typed(payload, offset)?;
+
+        Ok(Self {
+            attribute_id,
+            value,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.attribute_id, LE)?;
+        self.value.write_typed(buffer, offset)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WriteAttributeStatus {
+    pub status: Status,
+    pub attribute_id: u16,
+}
+impl<'a> Record<'a> for WriteAttributeStatus {
+    fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let attribute_id = payload.read_with::<u16>(offset, LE)?;
+
+        Ok(Self {
+            status,
+            attribute_id,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        buffer.write_with::<u16>(offset, self.attribute_id, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ReportingDirection {
+    /// The attribute is reported by the device receiving the configuration.
+    Sent,
+    /// The attribute is reported to the device receiving the configuration.
+    Received,
+}
+impl TryFrom<u8> for ReportingDirection {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(ReportingDirection::Sent),
+            0x01 => Ok(ReportingDirection::Received),
+            _ => Err(()),
+        }
+    }
+}
+impl From<ReportingDirection> for u8 {
+    fn from(direction: ReportingDirection) -> Self {
+        match direction {
+            ReportingDirection::Sent => 0x00,
+            ReportingDirection::Received => 0x01,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reporting<'a> {
+    Sent {
+        data_type: ZclDataType,
+        minimum_interval: u16,
+        /// 0xffff turns reporting off, 0x0000 only reports on change.
+        maximum_interval: u16,
+        /// Only present for analog data types, see `ZclDataType::is_analog`.
+        reportable_change: Option<ZclValue<'a>>,
+    },
+    Received {
+        /// Seconds without a report after which something is wrong, 0 to
+        /// never time out.
+        timeout_period: u16,
+    },
+}
+impl<'a> Reporting<'a> {
+    pub fn try_parse_from(
+        payload: &'a [u8],
+        offset: &mut usize,
+        direction: ReportingDirection,
+    ) -> Result<Self, ParseError> {
+        let reporting = match direction {
+            ReportingDirection::Sent => {
+                let data_type = ZclDataType::try_from(payload.read_with::<u8>(offset, LE)?)?;
+                let minimum_interval = payload.read_with::<u16>(offset, LE)?;
+                let maximum_interval = payload.read_with::<u16>(offset, LE)?;
+                let reportable_change = match data_type.is_analog() {
+                    true => Some(ZclValue::try_parse_from(payload, offset, data_type)?),
+                    false => None,
+                };
+                Reporting::Sent {
+                    data_type,
+                    minimum_interval,
+                    maximum_interval,
+                    reportable_change,
+                }
+            }
+            ReportingDirection::Received => Reporting::Received {
+                timeout_period: payload.read_with::<u16>(offset, LE)?,
+            },
+        };
+
+        Ok(reporting)
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        match *self {
+            Reporting::Sent {
+                data_type,
+                minimum_interval,
+                maximum_interval,
+                reportable_change,
+            } => {
+                buffer.write_with::<u8>(offset, data_type as u8, LE)?;
+                buffer.write_with::<u16>(offset, minimum_interval, LE)?;
+                buffer.write_with::<u16>(offset, maximum_interval, LE)?;
+                match (data_type.is_analog(), reportable_change) {
+                    (true, Some(change)) if change.data_type() == data_type => {
+                        change.write_into(buffer, offset)?
+                    }
+                    (false, None) => {}
+                    _ => return Err(WriteError),
+                }
+            }
+            Reporting::Received { timeout_period } => {
+                buffer.write_with::<u16>(offset, timeout_period, LE)?
+            }
+        }
+        Ok(())
+    }
+
+    pub fn direction(&self) -> ReportingDirection {
+        match self {
+            Reporting::Sent { .. } => ReportingDirection::Sent,
+            Reporting::Received { .. } => ReportingDirection::Received,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttributeReportingConfiguration<'a> {
+    pub attribute_id: u16,
+    pub reporting: Reporting<'a>,
+}
+impl<'a> Record<'a> for AttributeReportingConfiguration<'a> {
+    fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let direction = ReportingDirection::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let attribute_id = payload.read_with::<u16>(offset, LE)?;
+        let reporting = Reporting::try_parse_from(payload, offset, direction)?;
+
+        Ok(Self {
+            attribute_id,
+            reporting,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.reporting.direction().into(), LE)?;
+        buffer.write_with::<u16>(offset, self.attribute_id, LE)?;
+        self.reporting.write_into(buffer, offset)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfigureReportingStatus {
+    pub status: Status,
+    pub direction: ReportingDirection,
+    pub attribute_id: u16,
+}
+impl<'a> Record<'a> for ConfigureReportingStatus {
+    fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let direction = ReportingDirection::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let attribute_id = payload.read_with::<u16>(offset, LE)?;
+
+        Ok(Self {
+            status,
+            direction,
+            attribute_id,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        buffer.write_with::<u8>(offset, self.direction.into(), LE)?;
+        buffer.write_with::<u16>(offset, self.attribute_id, LE)?;
+        Ok(())
+    }
+}
+
+/// Selects the reporting configuration to read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttributeReportingSelector {
+    pub direction: ReportingDirection,
+    pub attribute_id: u16,
+}
+impl<'a> Record<'a> for AttributeReportingSelector {
+    fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let direction = ReportingDirection::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let attribute_id = payload.read_with::<u16>(offset, LE)?;
+
+        Ok(Self {
+            direction,
+            attribute_id,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.direction.into(), LE)?;
+        buffer.write_with::<u16>(offset, self.attribute_id, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReportingConfigurationStatus<'a> {
+    pub status: Status,
+    pub direction: ReportingDirection,
+    pub attribute_id: u16,
+    /// Only present when the configuration was read successfully.
+    pub reporting: Option<Reporting<'a>>,
+}
+impl<'a> Record<'a> for ReportingConfigurationStatus<'a> {
+    fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let direction = ReportingDirection::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let attribute_id = payload.read_with::<u16>(offset, LE)?;
+        let reporting = match status {
+            Status::Success => Some(Reporting::try_parse_from(payload, offset, direction)?),
+            _ => None,
+        };
+
+        Ok(Self {
+            status,
+            direction,
+            attribute_id,
+            reporting,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        buffer.write_with::<u8>(offset, self.direction.into(), LE)?;
+        buffer.write_with::<u16>(offset, self.attribute_id, LE)?;
+        match (self.status, self.reporting) {
+            (Status::Success, Some(reporting)) if reporting.direction() == self.direction => {
+                reporting.write_into(buffer, offset)?
+            }
+            (Status::Success, _) => return Err(WriteError),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Sent when a command has no other response, or when it failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefaultResponse {
+    pub command_id: u8,
+    pub status: Status,
+}
+impl DefaultResponse {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let command_id = payload.read_with::<u8>(offset, LE)?;
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+
+        Ok(Self { command_id, status })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.command_id, LE)?;
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoverAttributes {
+    pub start_attribute_id: u16,
+    pub maximum_attribute_ids: u8,
+}
+impl DiscoverAttributes {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let start_attribute_id = payload.read_with::<u16>(offset, LE)?;
+        let maximum_attribute_ids = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            start_attribute_id,
+            maximum_attribute_ids,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.start_attribute_id, LE)?;
+        buffer.write_with::<u8>(offset, self.maximum_attribute_ids, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiscoveredAttribute {
+    pub attribute_id: u16,
+    pub data_type: ZclDataType,
+}
+impl<'a> Record<'a> for DiscoveredAttribute {
+    fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let attribute_id = payload.read_with::<u16>(offset, LE)?;
+        let data_type = ZclDataType::try_from(payload.read_with::<u8>(offset, LE)?)?;
+
+        Ok(Self {
+            attribute_id,
+            data_type,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.attribute_id, LE)?;
+        buffer.write_with::<u8>(offset, self.data_type as u8, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoverAttributesResponse<'a> {
+    /// False when there are more attributes left to discover.
+    pub discovery_complete: bool,
+    pub attributes: RecordList<'a, DiscoveredAttribute>,
+}
+impl<'a> DiscoverAttributesResponse<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let discovery_complete = payload.read_with::<u8>(offset, LE)? == 1;
+        let attributes = RecordList::try_parse_from(payload, offset)?;
+
+        Ok(Self {
+            discovery_complete,
+            attributes,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.discovery_complete as u8, LE)?;
+        self.attributes.write_into(buffer, offset)
+    }
+}
+
+/// Discover the commands received or generated by a cluster.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoverCommands {
+    pub start_command_id: u8,
+    pub maximum_command_ids: u8,
+}
+impl DiscoverCommands {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let start_command_id = payload.read_with::<u8>(offset, LE)?;
+        let maximum_command_ids = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            start_command_id,
+            maximum_command_ids,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.start_command_id, LE)?;
+        buffer.write_with::<u8>(offset, self.maximum_command_ids, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoverCommandsResponse<'a> {
+    /// False when there are more commands left to discover.
+    pub discovery_complete: bool,
+    pub command_ids: &'a [u8],
+}
+impl<'a> DiscoverCommandsResponse<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let discovery_complete = payload.read_with::<u8>(offset, LE)? == 1;
+        let command_ids = &payload[*offset..];
+        *offset = payload.len();
+
+        Ok(Self {
+            discovery_complete,
+            command_ids,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.discovery_complete as u8, LE)?;
+        buffer.write(offset, self.command_ids)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trips(command_id: u8, payload: &[u8]) -> GlobalCommand<'_> {
+        let command = GlobalCommand::try_parse_from(command_id, payload).unwrap();
+        assert_eq!(command.command_id(), command_id);
+
+        let mut buffer = [0u8; 64];
+        let len = command.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], payload);
+
+        command
+    }
+
+    #[test]
+    fn parses_read_attributes() {
+        let command = assert_round_trips(0x00, b"\x04\x00\x05\x00");
+
+        match command {
+            GlobalCommand::ReadAttributes(attributes) => {
+                let mut attributes = attributes.iter();
+                assert_eq!(attributes.next(), Some(0x0004));
+                assert_eq!(attributes.next(), Some(0x0005));
+                assert_eq!(attributes.next(), None);
+            }
+            _ => panic!("Expected Read Attributes"),
+        }
+
+        assert!(GlobalCommand::try_parse_from(0x00, b"\x04\x00\x05").is_err());
+    }
+
+    #[test]
+    fn parses_read_attributes_response() {
+        let command = assert_round_trips(0x01, b"\x05\x00\x00\x42\x04TRDF\x06\x00\x86");
+
+        match command {
+            GlobalCommand::ReadAttributesResponse(records) => {
+                let mut records = records.iter();
+                assert_eq!(
+                    records.next(),
+                    Some(ReadAttributeStatus {
+                        attribute_id: 0x0005,
+                        status: Status::Success,
+                        value: Some(ZclValue::CharacterString(b"TRDF")),
+                    })
+                );
+                assert_eq!(
+                    records.next(),
+                    Some(ReadAttributeStatus {
+                        attribute_id: 0x0006,
+                        status: Status::UnsupportedAttribute,
+                        value: None,
+                    })
+                );
+                assert_eq!(records.next(), None);
+            }
+            _ => panic!("Expected Read Attributes Response"),
+        }
+    }
+
+    #[test]
+    fn parses_write_attributes_and_response() {
+        let command = assert_round_trips(0x02, b"\x10\x00\x21\x2c\x01");
+        match command {
+            GlobalCommand::WriteAttributes(records) => assert_eq!(
+                records.iter().next(),
+                Some(AttributeRecord {
+                    attribute_id: 0x0010,
+                    value: ZclValue::Uint16(300),
+                })
+            ),
+            _ => panic!("Expected Write Attributes"),
+        }
+
+        match assert_round_trips(0x04, b"\x00") {
+            GlobalCommand::WriteAttributesResponse(records) => assert!(records.is_empty()),
+            _ => panic!("Expected Write Attributes Response"),
+        }
+
+        match assert_round_trips(0x04, b"\x88\x10\x00") {
+            GlobalCommand::WriteAttributesResponse(records) => assert_eq!(
+                records.iter().next(),
+                Some(WriteAttributeStatus {
+                    status: Status::ReadOnly,
+                    attribute_id: 0x0010,
+                })
+            ),
+            _ => panic!("Expected Write Attributes Response"),
+        }
+    }
+
+    #[test]
+    fn parses_configure_reporting() {
+        // Report the temperature every 10 to 300 seconds, or on a 0.5C change,
+        // and expect the on/off attribute at least every 60 seconds.
+        let command = assert_round_trips(
+            0x06,
+            b"\x00\x00\x00\x29\x0a\x00\x2c\x01\x32\x00\x01\x00\x00\x3c\x00",
+        );
+
+        match command {
+            GlobalCommand::ConfigureReporting(records) => {
+                let mut records = records.iter();
+                assert_eq!(
+                    records.next(),
+                    Some(AttributeReportingConfiguration {
+                        attribute_id: 0x0000,
+                        reporting: Reporting::Sent {
+                            data_type: ZclDataType::Int16,
+                            minimum_interval: 10,
+                            maximum_interval: 300,
+                            reportable_change: Some(ZclValue::Int16(50)),
+                        },
+                    })
+                );
+                assert_eq!(
+                    records.next(),
+                    Some(AttributeReportingConfiguration {
+                        attribute_id: 0x0000,
+                        reporting: Reporting::Received { timeout_period: 60 },
+                    })
+                );
+                assert_eq!(records.next(), None);
+            }
+            _ => panic!("Expected Configure Reporting"),
+        }
+
+        // Discrete types have no reportable change.
+        assert_round_trips(0x06, b"\x00\x00\x00\x10\x01\x00\x10\x0e");
+    }
+
+    #[test]
+    fn parses_reporting_configuration_response() {
+        let command = assert_round_trips(
+            0x09,
+            b"\x00\x00\x00\x00\x10\x01\x00\x10\x0e\x8c\x00\x01\x00",
+        );
+
+        match command {
+            GlobalCommand::ReadReportingConfigurationResponse(records) => {
+                let mut records = records.iter();
+                assert_eq!(
+                    records.next().unwrap().reporting,
+                    Some(Reporting::Sent {
+                        data_type: ZclDataType::Bool,
+                        minimum_interval: 1,
+                        maximum_interval: 3600,
+                        reportable_change: None,
+                    })
+                );
+                assert_eq!(
+                    records.next(),
+                    Some(ReportingConfigurationStatus {
+                        status: Status::UnreportableAttribute,
+                        direction: ReportingDirection::Sent,
+                        attribute_id: 0x0001,
+                        reporting: None,
+                    })
+                );
+            }
+            _ => panic!("Expected Read Reporting Configuration Response"),
+        }
+    }
+
+    #[test]
+    fn parses_report_attributes_and_default_response() {
+        match assert_round_trips(0x0a, b"\x00\x00\x10\x01") {
+            GlobalCommand::ReportAttributes(records) => assert_eq!(
+                records.iter().next(),
+                Some(AttributeRecord {
+                    attribute_id: 0x0000,
+                    value: ZclValue::Bool(Some(true)),
+                })
+            ),
+            _ => panic!("Expected Report Attributes"),
+        }
+
+        assert_eq!(
+            assert_round_trips(0x0b, b"\x02\x81"),
+            GlobalCommand::DefaultResponse(DefaultResponse {
+                command_id: 0x02,
+                status: Status::UnsupportedClusterCommand,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_discovery() {
+        assert_eq!(
+            assert_round_trips(0x0c, b"\x00\x00\x10"),
+            GlobalCommand::DiscoverAttributes(DiscoverAttributes {
+                start_attribute_id: 0x0000,
+                maximum_attribute_ids: 16,
+            })
+        );
+
+        match assert_round_trips(0x0d, b"\x01\x00\x00\x10\x00\x40\x10") {
+            GlobalCommand::DiscoverAttributesResponse(response) => {
+                assert!(response.discovery_complete);
+                let mut attributes = response.attributes.iter();
+                assert_eq!(attributes.next().unwrap().data_type, ZclDataType::Bool);
+                assert_eq!(attributes.next().unwrap().attribute_id, 0x4000);
+                assert_eq!(attributes.next(), None);
+            }
+            _ => panic!("Expected Discover Attributes Response"),
+        }
+
+        assert_eq!(
+            assert_round_trips(0x12, b"\x00\x00\x01\x02"),
+            GlobalCommand::DiscoverCommandsReceivedResponse(DiscoverCommandsResponse {
+                discovery_complete: false,
+                command_ids: b"\x00\x01\x02",
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_command() {
+        assert!(GlobalCommand::try_parse_from(0x0e, b"").is_err());
+    }
+}
@@ -0,0 +1,285 @@
+use crate::network_layer::{ParseError, WriteError};
+use byte::{BytesExt, LE};
+use global_commands::GlobalCommand;
+
+pub mod data_types;
+pub mod global_commands;
+
+/// A ZCL frame, carried in the payload of an APS data frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZclFrame<'a> {
+    pub header: ZclHeader,
+    pub payload: &'a [u8],
+}
+impl<'a> ZclFrame<'a> {
+    pub fn try_parse_from(frame: &'a [u8]) -> Result<Self, ParseError> {
+        let offset = &mut 0;
+
+        let header = ZclHeader::try_parse_from(frame, offset)?;
+        let payload = &frame[*offset..];
+
+        Ok(Self { header, payload })
+    }
+
+    /// Parse the payload of a global command frame. Cluster specific
+    /// commands are left to the cluster implementations.
+    pub fn global_command(&self) -> Result<GlobalCommand<'a>, ParseError> {
+        if self.header.frame_type != FrameType::Global {
+            return Err(ParseError);
+        }
+        GlobalCommand::try_parse_from(self.header.command_id, self.payload)
+    }
+
+    /// Serialize the frame into `buffer`, returning the number of bytes
+    /// written.
+    pub fn write_into(&self, buffer: &mut [u8]) -> Result<usize, WriteError> {
+        let offset = &mut 0;
+
+        self.header.write_into(buffer, offset)?;
+        buffer.write(offset, self.payload)?;
+
+        Ok(*offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZclHeader {
+    pub frame_type: FrameType,
+    /// Set for manufacturer specific commands and attributes.
+    pub manufacturer_code: Option<u16>,
+    pub direction: Direction,
+    pub disable_default_response: bool,
+    pub sequence_number: u8,
+    pub command_id: u8,
+}
+impl ZclHeader {
+    pub fn try_parse_from(frame: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let frame_control = frame.read_with::<u8>(offset, LE)?;
+        let frame_type = FrameType::try_from(frame_control & 0b11)?;
+        let manufacturer_specific = ((frame_control >> 2) & 1) == 1;
+        let direction = match (frame_control >> 3) & 1 {
+            0 => Direction::ClientToServer,
+            _ => Direction::ServerToClient,
+        };
+        let disable_default_response = ((frame_control >> 4) & 1) == 1;
+
+        let manufacturer_code = match manufacturer_specific {
+            true => Some(frame.read_with::<u16>(offset, LE)?),
+            false => None,
+        };
+        let sequence_number = frame.read_with::<u8>(offset, LE)?;
+        let command_id = frame.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            frame_type,
+            manufacturer_code,
+            direction,
+            disable_default_response,
+            sequence_number,
+            command_id,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let frame_type: u8 = match self.frame_type {
+            FrameType::Global => 0b00,
+            FrameType::ClusterSpecific => 0b01,
+        };
+        let direction: u8 = match self.direction {
+            Direction::ClientToServer => 0,
+            Direction::ServerToClient => 1,
+        };
+        let frame_control = frame_type
+            | ((self.manufacturer_code.is_some() as u8) << 2)
+            | (direction << 3)
+            | ((self.disable_default_response as u8) << 4);
+
+        buffer.write_with::<u8>(offset, frame_control, LE)?;
+        if let Some(manufacturer_code) = self.manufacturer_code {
+            buffer.write_with::<u16>(offset, manufacturer_code, LE)?;
+        }
+        buffer.write_with::<u8>(offset, self.sequence_number, LE)?;
+        buffer.write_with::<u8>(offset, self.command_id, LE)?;
+
+        Ok(())
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum FrameType {
+    /// Command that acts across all clusters, e.g. reading attributes.
+    Global,
+    ClusterSpecific,
+}
+impl TryFrom<u8> for FrameType {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b00 => Ok(FrameType::Global),
+            0b01 => Ok(FrameType::ClusterSpecific),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+/// Result of a ZCL command, as carried in responses.
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum Status {
+    Success = 0x00,
+    Failure = 0x01,
+    NotAuthorized = 0x7e,
+    MalformedCommand = 0x80,
+    UnsupportedClusterCommand = 0x81,
+    UnsupportedGeneralCommand = 0x82,
+    UnsupportedManufacturerClusterCommand = 0x83,
+    UnsupportedManufacturerGeneralCommand = 0x84,
+    InvalidField = 0x85,
+    UnsupportedAttribute = 0x86,
+    InvalidValue = 0x87,
+    ReadOnly = 0x88,
+    InsufficientSpace = 0x89,
+    DuplicateExists = 0x8a,
+    NotFound = 0x8b,
+    UnreportableAttribute = 0x8c,
+    InvalidDataType = 0x8d,
+    InvalidSelector = 0x8e,
+    WriteOnly = 0x8f,
+    InconsistentStartupState = 0x90,
+    DefinedOutOfBand = 0x91,
+    Inconsistent = 0x92,
+    ActionDenied = 0x93,
+    Timeout = 0x94,
+    Abort = 0x95,
+    InvalidImage = 0x96,
+    WaitForData = 0x97,
+    NoImageAvailable = 0x98,
+    RequireMoreImage = 0x99,
+    NotificationPending = 0x9a,
+    HardwareFailure = 0xc0,
+    SoftwareFailure = 0xc1,
+    CalibrationError = 0xc2,
+    UnsupportedCluster = 0xc3,
+    LimitReached = 0xc4,
+}
+impl TryFrom<u8> for Status {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use Status::*;
+
+        let status = match value {
+            0x00 => Success,
+            0x01 => Failure,
+            0x7e => NotAuthorized,
+            0x80 => MalformedCommand,
+            0x81 => UnsupportedClusterCommand,
+            0x82 => UnsupportedGeneralCommand,
+            0x83 => UnsupportedManufacturerClusterCommand,
+            0x84 => UnsupportedManufacturerGeneralCommand,
+            0x85 => InvalidField,
+            0x86 => UnsupportedAttribute,
+            0x87 => InvalidValue,
+            0x88 => ReadOnly,
+            0x89 => InsufficientSpace,
+            0x8a => DuplicateExists,
+            0x8b => NotFound,
+            0x8c => UnreportableAttribute,
+            0x8d => InvalidDataType,
+            0x8e => InvalidSelector,
+            0x8f => WriteOnly,
+            0x90 => InconsistentStartupState,
+            0x91 => DefinedOutOfBand,
+            0x92 => Inconsistent,
+            0x93 => ActionDenied,
+            0x94 => Timeout,
+            0x95 => Abort,
+            0x96 => InvalidImage,
+            0x97 => WaitForData,
+            0x98 => NoImageAvailable,
+            0x99 => RequireMoreImage,
+            0x9a => NotificationPending,
+            0xc0 => HardwareFailure,
+            0xc1 => SoftwareFailure,
+            0xc2 => CalibrationError,
+            0xc3 => UnsupportedCluster,
+            0xc4 => LimitReached,
+            _ => return Err(()),
+        };
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cluster_specific_frame() {
+        // On/Off cluster "Toggle" command.
+        let bytes = b"\x01\x2a\x02";
+
+        let frame = ZclFrame::try_parse_from(bytes).unwrap();
+
+        assert_eq!(
+            frame.header,
+            ZclHeader {
+                frame_type: FrameType::ClusterSpecific,
+                manufacturer_code: None,
+                direction: Direction::ClientToServer,
+                disable_default_response: false,
+                sequence_number: 0x2a,
+                command_id: 0x02,
+            }
+        );
+        assert!(frame.payload.is_empty());
+    }
+
+    #[test]
+    fn parses_manufacturer_specific_frame() {
+        let bytes = b"\x1c\x5f\x11\x07\x0a\x00\x00\x20\x01";
+
+        let frame = ZclFrame::try_parse_from(bytes).unwrap();
+
+        assert_eq!(frame.header.frame_type, FrameType::Global);
+        assert_eq!(frame.header.manufacturer_code, Some(0x115f));
+        assert_eq!(frame.header.direction, Direction::ServerToClient);
+        assert!(frame.header.disable_default_response);
+        assert_eq!(frame.header.sequence_number, 0x07);
+        assert_eq!(frame.header.command_id, 0x0a);
+        assert_eq!(frame.payload, b"\x00\x00\x20\x01");
+
+        let mut buffer = [0u8; 16];
+        let len = frame.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], bytes);
+    }
+
+    #[test]
+    fn parses_global_command() {
+        // Read Attributes for the model identifier.
+        let frame = ZclFrame::try_parse_from(b"\x00\x01\x00\x05\x00").unwrap();
+
+        match frame.global_command().unwrap() {
+            GlobalCommand::ReadAttributes(attributes) => {
+                assert_eq!(attributes.iter().next(), Some(0x0005))
+            }
+            _ => panic!("Expected Read Attributes"),
+        }
+
+        let frame = ZclFrame::try_parse_from(b"\x01\x2a\x02").unwrap();
+        assert!(frame.global_command().is_err());
+    }
+
+    #[test]
+    fn rejects_reserved_frame_type_and_truncated_header() {
+        assert!(ZclFrame::try_parse_from(b"\x02\x01\x00").is_err());
+        assert!(ZclFrame::try_parse_from(b"\x04\x5f\x11\x07").is_err());
+    }
+}
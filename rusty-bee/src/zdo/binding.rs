@@ -0,0 +1,169 @@
+use super::{Status, ZdoCommand, ZdoFrame};
+use crate::aps::binding::{Binding, BindingDestination, BindingTable};
+use crate::table::TableFull;
+
+/// The binding table of this device, used to answer bind and unbind
+/// requests.
+#[derive(Debug)]
+pub struct LocalBindings<'a, const N: usize> {
+    pub ieee_address: u64,
+    pub bindings: &'a mut BindingTable<N>,
+}
+impl<'a, const N: usize> LocalBindings<'a, N> {
+    /// Apply a bind or unbind request to the binding table and build the
+    /// response. Returns `None` for other frames.
+    pub fn respond(&mut self, request: &ZdoFrame) -> Option<ZdoFrame<'static>> {
+        let command = match &request.command {
+            ZdoCommand::BindRequest(binding) => {
+                let status = match self.validate(binding) {
+                    Status::Success => match self.bindings.bind(*binding) {
+                        Ok(()) => Status::Success,
+                        Err(TableFull) => Status::TableFull,
+                    },
+                    status => status,
+                };
+                ZdoCommand::BindResponse(status)
+            }
+            ZdoCommand::UnbindRequest(binding) => {
+                let status = match self.validate(binding) {
+                    Status::Success => match self.bindings.unbind(binding) {
+                        true => Status::Success,
+                        false => Status::NoEntry,
+                    },
+                    status => status,
+                };
+                ZdoCommand::UnbindResponse(status)
+            }
+            _ => return None,
+        };
+
+        Some(ZdoFrame {
+            transaction_sequence_number: request.transaction_sequence_number,
+            command,
+        })
+    }
+
+    fn validate(&self, binding: &Binding) -> Status {
+        let valid_endpoint = |endpoint: u8| (1..=240).contains(&endpoint);
+
+        if binding.source_address != self.ieee_address {
+            // Bindings are only stored on the source device.
+            return Status::NotSupported;
+        }
+        let destination_endpoint_valid = match binding.destination {
+            BindingDestination::Group(_) => true,
+            BindingDestination::Device { endpoint, .. } => valid_endpoint(endpoint),
+        };
+        match valid_endpoint(binding.source_endpoint) && destination_endpoint_valid {
+            true => Status::Success,
+            false => Status::InvalidEndpoint,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IEEE_ADDRESS: u64 = 0x0017_8801_0881_c09e;
+
+    #[test]
+    fn binds_and_unbinds() {
+        // Bind the On/Off cluster of endpoint 1 to endpoint 11 of a light.
+        let bytes = b"\x42\x9e\xc0\x81\x08\x01\x88\x17\x00\x01\x06\x00\
+\x03\x11\x11\x11\x11\x11\x11\x11\x11\x0b";
+        let bind = ZdoFrame::try_parse_from(0x0021, bytes).unwrap();
+        assert_eq!(
+            bind.command,
+            ZdoCommand::BindRequest(Binding {
+                source_address: IEEE_ADDRESS,
+                source_endpoint: 1,
+                cluster_id: 0x0006,
+                destination: BindingDestination::Device {
+                    ieee_address: 0x1111_1111_1111_1111,
+                    endpoint: 11,
+                },
+            })
+        );
+        let unbind = ZdoFrame::try_parse_from(0x0022, bytes).unwrap();
+
+        let mut table = BindingTable::<1>::new();
+        let mut bindings = LocalBindings {
+            ieee_address: IEEE_ADDRESS,
+            bindings: &mut table,
+        };
+
+        let response = bindings.respond(&bind).unwrap();
+        assert_eq!(response.transaction_sequence_number, 0x42);
+        assert_eq!(response.command, ZdoCommand::BindResponse(Status::Success));
+        let mut buffer = [0u8; 2];
+        assert_eq!(response.write_into(&mut buffer).unwrap(), 2);
+        assert_eq!(response.cluster_id(), 0x8021);
+        assert_eq!(&buffer, b"\x42\x00");
+
+        assert_eq!(
+            bindings.respond(&unbind).unwrap().command,
+            ZdoCommand::UnbindResponse(Status::Success)
+        );
+        assert_eq!(
+            bindings.respond(&unbind).unwrap().command,
+            ZdoCommand::UnbindResponse(Status::NoEntry)
+        );
+    }
+
+    fn bind_status(bindings: &mut LocalBindings<1>, binding: Binding) -> Status {
+        let request = ZdoFrame {
+            transaction_sequence_number: 0,
+            command: ZdoCommand::BindRequest(binding),
+        };
+        match bindings.respond(&request).unwrap().command {
+            ZdoCommand::BindResponse(status) => status,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_bindings() {
+        let binding = Binding {
+            source_address: IEEE_ADDRESS,
+            source_endpoint: 1,
+            cluster_id: 0x0006,
+            destination: BindingDestination::Group(0x0001),
+        };
+        let mut table = BindingTable::<1>::new();
+        let mut bindings = LocalBindings {
+            ieee_address: IEEE_ADDRESS,
+            bindings: &mut table,
+        };
+
+        let other_device = Binding {
+            source_address: 0x1111_1111_1111_1111,
+            ..binding
+        };
+        assert_eq!(
+            bind_status(&mut bindings, other_device),
+            Status::NotSupported
+        );
+        let zdo_endpoint = Binding {
+            source_endpoint: 0,
+            ..binding
+        };
+        assert_eq!(
+            bind_status(&mut bindings, zdo_endpoint),
+            Status::InvalidEndpoint
+        );
+        assert_eq!(bind_status(&mut bindings, binding), Status::Success);
+        let level_control = Binding {
+            cluster_id: 0x0008,
+            ..binding
+        };
+        assert_eq!(bind_status(&mut bindings, level_control), Status::TableFull);
+
+        assert!(bindings
+            .respond(&ZdoFrame {
+                transaction_sequence_number: 0,
+                command: ZdoCommand::MgmtLeaveResponse(Status::Success),
+            })
+            .is_none());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::network_layer::commands::{AddressList, CapabilityInformation};
+use crate::network_layer::{ParseError, WriteError};
+use byte::{BytesExt, LE};
+
+/// Value of `NodeDescriptor::frequency_bands` for devices on 2.4 GHz.
+pub const FREQUENCY_BAND_2400_MHZ: u8 = 0b01000;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum LogicalType {
+    Coordinator,
+    Router,
+    EndDevice,
+}
+impl TryFrom<u8> for LogicalType {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0b000 => Ok(LogicalType::Coordinator),
+            0b001 => Ok(LogicalType::Router),
+            0b010 => Ok(LogicalType::EndDevice),
+            _ => Err(()),
+        }
+    }
+}
+impl From<LogicalType> for u8 {
+    fn from(logical_type: LogicalType) -> Self {
+        match logical_type {
+            LogicalType::Coordinator => 0b000,
+            LogicalType::Router => 0b001,
+            LogicalType::EndDevice => 0b010,
+        }
+    }
+}
+
+/// Type and capabilities of a node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeDescriptor {
+    pub logical_type: LogicalType,
+    pub complex_descriptor_available: bool,
+    pub user_descriptor_available: bool,
+    /// Bitmask of the supported frequency bands, e.g.
+    /// `FREQUENCY_BAND_2400_MHZ`.
+    pub frequency_bands: u8,
+    pub mac_capabilities: CapabilityInformation,
+    pub manufacturer_code: u16,
+    /// Largest NSDU the node can handle, in bytes.
+    pub maximum_buffer_size: u8,
+    /// Largest APSDU the node can receive, in bytes, fragmented or not.
+    pub maximum_incoming_transfer_size: u16,
+    /// Server roles of the node in bits 0-6, and the stack compliance
+    /// revision in bits 9-15.
+    pub server_mask: u16,
+    /// Largest APSDU the node can send, in bytes, fragmented or not.
+    pub maximum_outgoing_transfer_size: u16,
+    pub descriptor_capabilities: u8,
+}
+impl NodeDescriptor {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let flags = payload.read_with::<u8>(offset, LE)?;
+        let logical_type = LogicalType::try_from(flags & 0b111)?;
+        let complex_descriptor_available = ((flags >> 3) & 1) == 1;
+        let user_descriptor_available = ((flags >> 4) & 1) == 1;
+
+        // The APS flags in the low bits are unused.
+        let frequency_bands = payload.read_with::<u8>(offset, LE)? >> 3;
+        let mac_capabilities = CapabilityInformation::from(payload.read_with::<u8>(offset, LE)?);
+        let manufacturer_code = payload.read_with::<u16>(offset, LE)?;
+        let maximum_buffer_size = payload.read_with::<u8>(offset, LE)?;
+        let maximum_incoming_transfer_size = payload.read_with::<u16>(offset, LE)?;
+        let server_mask = payload.read_with::<u16>(offset, LE)?;
+        let maximum_outgoing_transfer_size = payload.read_with::<u16>(offset, LE)?;
+        let descriptor_capabilities = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            logical_type,
+            complex_descriptor_available,
+            user_descriptor_available,
+            frequency_bands,
+            mac_capabilities,
+            manufacturer_code,
+            maximum_buffer_size,
+            maximum_incoming_transfer_size,
+            server_mask,
+            maximum_outgoing_transfer_size,
+            descriptor_capabilities,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let flags = u8::from(self.logical_type)
+            | ((self.complex_descriptor_available as u8) << 3)
+            | ((self.user_descriptor_available as u8) << 4);
+
+        buffer.write_with::<u8>(offset, flags, LE)?;
+        buffer.write_with::<u8>(offset, self.frequency_bands << 3, LE)?;
+        buffer.write_with::<u8>(offset, u8::from(&self.mac_capabilities), LE)?;
+        buffer.write_with::<u16>(offset, self.manufacturer_code, LE)?;
+        buffer.write_with::<u8>(offset, self.maximum_buffer_size, LE)?;
+        buffer.write_with::<u16>(offset, self.maximum_incoming_transfer_size, LE)?;
+        buffer.write_with::<u16>(offset, self.server_mask, LE)?;
+        buffer.write_with::<u16>(offset, self.maximum_outgoing_transfer_size, LE)?;
+        buffer.write_with::<u8>(offset, self.descriptor_capabilities, LE)?;
+
+        Ok(())
+    }
+}
+
+/// Power source and level of a node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerDescriptor {
+    /// 0 when the receiver is synchronized with the receiver on when idle
+    /// setting, 1 and 2 for periodic and stimulated wake-ups.
+    pub current_power_mode: u8,
+    /// Bitmask of constant (mains) power, rechargeable battery and
+    /// disposable battery.
+    pub available_power_sources: u8,
+    /// Same bitmask as `available_power_sources`.
+    pub current_power_source: u8,
+    /// 0 for critical, 4 for 33%, 8 for 66% and 12 for 100%.
+    pub current_power_source_level: u8,
+}
+impl PowerDescriptor {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let first = payload.read_with::<u8>(offset, LE)?;
+        let second = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            current_power_mode: first & 0b1111,
+            available_power_sources: first >> 4,
+            current_power_source: second & 0b1111,
+            current_power_source_level: second >> 4,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let first = (self.current_power_mode & 0b1111) | (self.available_power_sources << 4);
+        let second = (self.current_power_source & 0b1111) | (self.current_power_source_level << 4);
+
+        buffer.write_with::<u8>(offset, first, LE)?;
+        buffer.write_with::<u8>(offset, second, LE)?;
+        Ok(())
+    }
+}
+
+/// Description of a single endpoint and the clusters it implements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimpleDescriptor<'a> {
+    pub endpoint: u8,
+    pub profile_id: u16,
+    pub device_id: u16,
+    pub device_version: u8,
+    /// Clusters implemented as a server.
+    pub input_clusters: AddressList<'a>,
+    /// Clusters implemented as a client.
+    pub output_clusters: AddressList<'a>,
+}
+impl<'a> SimpleDescriptor<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let endpoint = payload.read_with::<u8>(offset, LE)?;
+        let profile_id = payload.read_with::<u16>(offset, LE)?;
+        let device_id = payload.read_with::<u16>(offset, LE)?;
+        let device_version = payload.read_with::<u8>(offset, LE)? & 0b1111;
+        let input_clusters = AddressList::try_parse_counted_from(payload, offset)?;
+        let output_clusters = AddressList::try_parse_counted_from(payload, offset)?;
+
+        Ok(Self {
+            endpoint,
+            profile_id,
+            device_id,
+            device_version,
+            input_clusters,
+            output_clusters,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.endpoint, LE)?;
+        buffer.write_with::<u16>(offset, self.profile_id, LE)?;
+        buffer.write_with::<u16>(offset, self.device_id, LE)?;
+        buffer.write_with::<u8>(offset, self.device_version & 0b1111, LE)?;
+        self.input_clusters.write_counted_into(buffer, offset)?;
+        self.output_clusters.write_counted_into(buffer, offset)?;
+        Ok(())
+    }
+
+    /// Length of the descriptor over the air, in bytes.
+    pub fn length(&self) -> usize {
+        8 + self.input_clusters.bytes.len() + self.output_clusters.bytes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_node_descriptor() {
+        let bytes = b"\x02\x40\x80\x5f\x11\x52\x80\x00\x00\x2c\x80\x00\x00";
+
+        let descriptor = NodeDescriptor::try_parse_from(bytes, &mut 0).unwrap();
+
+        assert_eq!(descriptor.logical_type, LogicalType::EndDevice);
+        assert_eq!(descriptor.frequency_bands, FREQUENCY_BAND_2400_MHZ);
+        assert!(descriptor.mac_capabilities.allocate_address);
+        assert!(!descriptor.mac_capabilities.receiver_on_when_idle);
+        assert_eq!(descriptor.manufacturer_code, 0x115f);
+        assert_eq!(descriptor.maximum_buffer_size, 0x52);
+        assert_eq!(descriptor.maximum_incoming_transfer_size, 0x80);
+        assert_eq!(descriptor.server_mask, 0x2c00);
+        assert_eq!(descriptor.maximum_outgoing_transfer_size, 0x80);
+
+        let mut buffer = [0u8; 16];
+        let offset = &mut 0;
+        descriptor.write_into(&mut buffer, offset).unwrap();
+        assert_eq!(&buffer[..*offset], bytes);
+    }
+
+    #[test]
+    fn parses_power_descriptor() {
+        let bytes = b"\x60\xc4";
+
+        let descriptor = PowerDescriptor::try_parse_from(bytes, &mut 0).unwrap();
+
+        assert_eq!(
+            descriptor,
+            PowerDescriptor {
+                current_power_mode: 0,
+                available_power_sources: 0b0110,
+                current_power_source: 0b0100,
+                current_power_source_level: 12,
+            }
+        );
+
+        let mut buffer = [0u8; 2];
+        descriptor.write_into(&mut buffer, &mut 0).unwrap();
+        assert_eq!(&buffer, bytes);
+    }
+
+    #[test]
+    fn parses_simple_descriptor() {
+        // Home automation on/off light.
+        let bytes = b"\x01\x04\x01\x00\x01\x01\x03\x00\x00\x03\x00\x06\x00\x01\x19\x00";
+
+        let descriptor = SimpleDescriptor::try_parse_from(bytes, &mut 0).unwrap();
+
+        assert_eq!(descriptor.endpoint, 1);
+        assert_eq!(descriptor.profile_id, 0x0104);
+        assert_eq!(descriptor.device_id, 0x0100);
+        assert_eq!(descriptor.device_version, 1);
+        let input_clusters: Vec<u16> = descriptor.input_clusters.iter().collect();
+        assert_eq!(input_clusters, [0x0000, 0x0003, 0x0006]);
+        let output_clusters: Vec<u16> = descriptor.output_clusters.iter().collect();
+        assert_eq!(output_clusters, [0x0019]);
+        assert_eq!(descriptor.length(), bytes.len());
+
+        let mut buffer = [0u8; 32];
+        let offset = &mut 0;
+        descriptor.write_into(&mut buffer, offset).unwrap();
+        assert_eq!(&buffer[..*offset], bytes);
+
+        assert!(SimpleDescriptor::try_parse_from(&bytes[..14], &mut 0).is_err());
+    }
+}
@@ -0,0 +1,426 @@
+use super::descriptors::{NodeDescriptor, PowerDescriptor, SimpleDescriptor};
+use super::Status;
+use crate::network_layer::commands::{AddressList, CapabilityInformation};
+use crate::network_layer::{ParseError, WriteError};
+use byte::ctx::Bytes;
+use byte::{BytesExt, LE};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AddressRequestType {
+    SingleDevice,
+    /// Also list the devices associated with the remote device.
+    Extended,
+}
+impl TryFrom<u8> for AddressRequestType {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(AddressRequestType::SingleDevice),
+            0x01 => Ok(AddressRequestType::Extended),
+            _ => Err(()),
+        }
+    }
+}
+impl From<AddressRequestType> for u8 {
+    fn from(request_type: AddressRequestType) -> Self {
+        match request_type {
+            AddressRequestType::SingleDevice => 0x00,
+            AddressRequestType::Extended => 0x01,
+        }
+    }
+}
+
+/// Looks up the network address of a device from its IEEE address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NwkAddressRequest {
+    pub ieee_address: u64,
+    pub request_type: AddressRequestType,
+    /// First associated device to list in an extended response.
+    pub start_index: u8,
+}
+impl NwkAddressRequest {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let ieee_address = payload.read_with::<u64>(offset, LE)?;
+        let request_type = AddressRequestType::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let start_index = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            ieee_address,
+            request_type,
+            start_index,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u64>(offset, self.ieee_address, LE)?;
+        buffer.write_with::<u8>(offset, self.request_type.into(), LE)?;
+        buffer.write_with::<u8>(offset, self.start_index, LE)?;
+        Ok(())
+    }
+}
+
+/// Looks up the IEEE address of a device from its network address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IeeeAddressRequest {
+    pub nwk_address_of_interest: u16,
+    pub request_type: AddressRequestType,
+    /// First associated device to list in an extended response.
+    pub start_index: u8,
+}
+impl IeeeAddressRequest {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let nwk_address_of_interest = payload.read_with::<u16>(offset, LE)?;
+        let request_type = AddressRequestType::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let start_index = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            nwk_address_of_interest,
+            request_type,
+            start_index,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.nwk_address_of_interest, LE)?;
+        buffer.write_with::<u8>(offset, self.request_type.into(), LE)?;
+        buffer.write_with::<u8>(offset, self.start_index, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AssociatedDevices<'a> {
+    pub start_index: u8,
+    pub addresses: AddressList<'a>,
+}
+
+/// Response to both `NwkAddressRequest` and `IeeeAddressRequest`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressResponse<'a> {
+    pub status: Status,
+    pub ieee_address: u64,
+    pub nwk_address: u16,
+    /// Only present in responses to extended requests.
+    pub associated_devices: Option<AssociatedDevices<'a>>,
+}
+impl<'a> AddressResponse<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let ieee_address = payload.read_with::<u64>(offset, LE)?;
+        let nwk_address = payload.read_with::<u16>(offset, LE)?;
+
+        let associated_devices = match *offset < payload.len() {
+            true => {
+                let count = payload.read_with::<u8>(offset, LE)?;
+                // The start index and list are left out when there are no
+                // associated devices.
+                let start_index = match count {
+                    0 => 0,
+                    _ => payload.read_with::<u8>(offset, LE)?,
+                };
+                let addresses = AddressList {
+                    bytes: payload.read_with::<&[u8]>(offset, Bytes::Len(count as usize * 2))?,
+                };
+                Some(AssociatedDevices {
+                    start_index,
+                    addresses,
+                })
+            }
+            false => None,
+        };
+
+        Ok(Self {
+            status,
+            ieee_address,
+            nwk_address,
+            associated_devices,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        buffer.write_with::<u64>(offset, self.ieee_address, LE)?;
+        buffer.write_with::<u16>(offset, self.nwk_address, LE)?;
+
+        if let Some(associated_devices) = self.associated_devices {
+            let count = u8::try_from(associated_devices.addresses.len()).map_err(|_| WriteError)?;
+            buffer.write_with::<u8>(offset, count, LE)?;
+            if count > 0 {
+                buffer.write_with::<u8>(offset, associated_devices.start_index, LE)?;
+                buffer.write(offset, associated_devices.addresses.bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Request for the node, power descriptor or active endpoints of a device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorRequest {
+    pub nwk_address_of_interest: u16,
+}
+impl DescriptorRequest {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let nwk_address_of_interest = payload.read_with::<u16>(offset, LE)?;
+        Ok(Self {
+            nwk_address_of_interest,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.nwk_address_of_interest, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeDescriptorResponse {
+    pub status: Status,
+    pub nwk_address_of_interest: u16,
+    /// Only present on success.
+    pub node_descriptor: Option<NodeDescriptor>,
+}
+impl NodeDescriptorResponse {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let nwk_address_of_interest = payload.read_with::<u16>(offset, LE)?;
+        let node_descriptor = match status {
+            Status::Success => Some(NodeDescriptor::try_parse_from(payload, offset)?),
+            _ => None,
+        };
+
+        Ok(Self {
+            status,
+            nwk_address_of_interest,
+            node_descriptor,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        buffer.write_with::<u16>(offset, self.nwk_address_of_interest, LE)?;
+        if let Some(node_descriptor) = &self.node_descriptor {
+            node_descriptor.write_into(buffer, offset)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerDescriptorResponse {
+    pub status: Status,
+    pub nwk_address_of_interest: u16,
+    /// Only present on success.
+    pub power_descriptor: Option<PowerDescriptor>,
+}
+impl PowerDescriptorResponse {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let nwk_address_of_interest = payload.read_with::<u16>(offset, LE)?;
+        let power_descriptor = match status {
+            Status::Success => Some(PowerDescriptor::try_parse_from(payload, offset)?),
+            _ => None,
+        };
+
+        Ok(Self {
+            status,
+            nwk_address_of_interest,
+            power_descriptor,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        buffer.write_with::<u16>(offset, self.nwk_address_of_interest, LE)?;
+        if let Some(power_descriptor) = &self.power_descriptor {
+            power_descriptor.write_into(buffer, offset)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimpleDescriptorRequest {
+    pub nwk_address_of_interest: u16,
+    pub endpoint: u8,
+}
+impl SimpleDescriptorRequest {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let nwk_address_of_interest = payload.read_with::<u16>(offset, LE)?;
+        let endpoint = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            nwk_address_of_interest,
+            endpoint,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.nwk_address_of_interest, LE)?;
+        buffer.write_with::<u8>(offset, self.endpoint, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimpleDescriptorResponse<'a> {
+    pub status: Status,
+    pub nwk_address_of_interest: u16,
+    /// Only present on success.
+    pub simple_descriptor: Option<SimpleDescriptor<'a>>,
+}
+impl<'a> SimpleDescriptorResponse<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let nwk_address_of_interest = payload.read_with::<u16>(offset, LE)?;
+        let length = payload.read_with::<u8>(offset, LE)? as usize;
+
+        let simple_descriptor = match length {
+            0 => None,
+            _ => {
+                let descriptor = payload.read_with::<&[u8]>(offset, Bytes::Len(length))?;
+                let descriptor_offset = &mut 0;
+                let simple_descriptor =
+                    SimpleDescriptor::try_parse_from(descriptor, descriptor_offset)?;
+                if *descriptor_offset != length {
+                    return Err(ParseError);
+                }
+                Some(simple_descriptor)
+            }
+        };
+
+        Ok(Self {
+            status,
+            nwk_address_of_interest,
+            simple_descriptor,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        buffer.write_with::<u16>(offset, self.nwk_address_of_interest, LE)?;
+        match &self.simple_descriptor {
+            Some(simple_descriptor) => {
+                let length = u8::try_from(simple_descriptor.length()).map_err(|_| WriteError)?;
+                buffer.write_with::<u8>(offset, length, LE)?;
+                simple_descriptor.write_into(buffer, offset)?;
+            }
+            None => buffer.write_with::<u8>(offset, 0, LE)?,
+        }
+        Ok(())
+    }
+}
+
+/// Finds the endpoints of a device that implement any of the given
+/// clusters for a profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchDescriptorRequest<'a> {
+    pub nwk_address_of_interest: u16,
+    pub profile_id: u16,
+    pub input_clusters: AddressList<'a>,
+    pub output_clusters: AddressList<'a>,
+}
+impl<'a> MatchDescriptorRequest<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let nwk_address_of_interest = payload.read_with::<u16>(offset, LE)?;
+        let profile_id = payload.read_with::<u16>(offset, LE)?;
+        let input_clusters = AddressList::try_parse_counted_from(payload, offset)?;
+        let output_clusters = AddressList::try_parse_counted_from(payload, offset)?;
+
+        Ok(Self {
+            nwk_address_of_interest,
+            profile_id,
+            input_clusters,
+            output_clusters,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.nwk_address_of_interest, LE)?;
+        buffer.write_with::<u16>(offset, self.profile_id, LE)?;
+        self.input_clusters.write_counted_into(buffer, offset)?;
+        self.output_clusters.write_counted_into(buffer, offset)?;
+        Ok(())
+    }
+
+    /// Whether `descriptor` shares the profile and at least one input or
+    /// output cluster with the request.
+    pub fn matches(&self, descriptor: &SimpleDescriptor) -> bool {
+        if self.profile_id != descriptor.profile_id {
+            return false;
+        }
+
+        let input_match = self
+            .input_clusters
+            .iter()
+            .any(|cluster| descriptor.input_clusters.iter().any(|c| c == cluster));
+        let output_match = self
+            .output_clusters
+            .iter()
+            .any(|cluster| descriptor.output_clusters.iter().any(|c| c == cluster));
+
+        input_match || output_match
+    }
+}
+
+/// Response to both the active endpoints and the match descriptor request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointListResponse<'a> {
+    pub status: Status,
+    pub nwk_address_of_interest: u16,
+    pub endpoints: &'a [u8],
+}
+impl<'a> EndpointListResponse<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let nwk_address_of_interest = payload.read_with::<u16>(offset, LE)?;
+        let count = payload.read_with::<u8>(offset, LE)?;
+        let endpoints = payload.read_with::<&[u8]>(offset, Bytes::Len(count as usize))?;
+
+        Ok(Self {
+            status,
+            nwk_address_of_interest,
+            endpoints,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let count = u8::try_from(self.endpoints.len()).map_err(|_| WriteError)?;
+
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        buffer.write_with::<u16>(offset, self.nwk_address_of_interest, LE)?;
+        buffer.write_with::<u8>(offset, count, LE)?;
+        buffer.write(offset, self.endpoints)?;
+        Ok(())
+    }
+}
+
+/// Broadcast by a device after joining or rejoining the network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceAnnounce {
+    pub nwk_address: u16,
+    pub ieee_address: u64,
+    pub capability: CapabilityInformation,
+}
+impl DeviceAnnounce {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let nwk_address = payload.read_with::<u16>(offset, LE)?;
+        let ieee_address = payload.read_with::<u64>(offset, LE)?;
+        let capability = CapabilityInformation::from(payload.read_with::<u8>(offset, LE)?);
+
+        Ok(Self {
+            nwk_address,
+            ieee_address,
+            capability,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u16>(offset, self.nwk_address, LE)?;
+        buffer.write_with::<u64>(offset, self.ieee_address, LE)?;
+        buffer.write_with::<u8>(offset, u8::from(&self.capability), LE)?;
+        Ok(())
+    }
+}
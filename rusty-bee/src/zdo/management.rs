@@ -0,0 +1,726 @@
+use super::{Status, ZdoCommand, ZdoFrame};
+use crate::aps::binding::{Binding, BindingDestination, BindingTable};
+use crate::network_layer::neighbor_table::{DeviceType, Neighbor, NeighborTable, Relationship};
+use crate::network_layer::routing_table::{Route, RouteStatus, RoutingTable};
+use crate::network_layer::{ParseError, WriteError};
+use byte::ctx::Bytes;
+use byte::{BytesExt, LE};
+use core::marker::PhantomData;
+
+/// An entry of a table, as listed in management responses.
+pub trait ManagementRecord: Sized {
+    fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError>;
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError>;
+}
+
+impl ManagementRecord for Neighbor {
+    fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let extended_pan_id = payload.read_with::<u64>(offset, LE)?;
+        let extended_address = payload.read_with::<u64>(offset, LE)?;
+        let nwk_address = payload.read_with::<u16>(offset, LE)?;
+
+        let flags = payload.read_with::<u8>(offset, LE)?;
+        let device_type = match flags & 0b11 {
+            0b00 => DeviceType::Coordinator,
+            0b01 => DeviceType::Router,
+            0b10 => DeviceType::EndDevice,
+            _ => DeviceType::Unknown,
+        };
+        let receiver_on_when_idle = match (flags >> 2) & 0b11 {
+            0b00 => Some(false),
+            0b01 => Some(true),
+            _ => None,
+        };
+        let relationship = match (flags >> 4) & 0b111 {
+            0b000 => Relationship::Parent,
+            0b001 => Relationship::Child,
+            0b010 => Relationship::Sibling,
+            0b011 => Relationship::None,
+            0b100 => Relationship::PreviousChild,
+            0b101 => Relationship::UnauthenticatedChild,
+            _ => return Err(ParseError),
+        };
+        let permit_joining = match payload.read_with::<u8>(offset, LE)? & 0b11 {
+            0b00 => Some(false),
+            0b01 => Some(true),
+            _ => None,
+        };
+        let depth = payload.read_with::<u8>(offset, LE)?;
+        let lqi = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            extended_pan_id,
+            extended_address,
+            nwk_address,
+            device_type,
+            receiver_on_when_idle,
+            relationship,
+            permit_joining,
+            depth,
+            lqi,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let device_type: u8 = match self.device_type {
+            DeviceType::Coordinator => 0b00,
+            DeviceType::Router => 0b01,
+            DeviceType::EndDevice => 0b10,
+            DeviceType::Unknown => 0b11,
+        };
+        let relationship: u8 = match self.relationship {
+            Relationship::Parent => 0b000,
+            Relationship::Child => 0b001,
+            Relationship::Sibling => 0b010,
+            Relationship::None => 0b011,
+            Relationship::PreviousChild => 0b100,
+            Relationship::UnauthenticatedChild => 0b101,
+        };
+        let flags =
+            device_type | (optional_bool(self.receiver_on_when_idle) << 2) | (relationship << 4);
+
+        buffer.write_with::<u64>(offset, self.extended_pan_id, LE)?;
+        buffer.write_with::<u64>(offset, self.extended_address, LE)?;
+        buffer.write_with::<u16>(offset, self.nwk_address, LE)?;
+        buffer.write_with::<u8>(offset, flags, LE)?;
+        buffer.write_with::<u8>(offset, optional_bool(self.permit_joining), LE)?;
+        buffer.write_with::<u8>(offset, self.depth, LE)?;
+        buffer.write_with::<u8>(offset, self.lqi, LE)?;
+        Ok(())
+    }
+}
+
+/// Fields that can be unknown are sent as 2.
+fn optional_bool(value: Option<bool>) -> u8 {
+    match value {
+        Some(false) => 0b00,
+        Some(true) => 0b01,
+        None => 0b10,
+    }
+}
+
+impl ManagementRecord for Route {
+    fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let destination = payload.read_with::<u16>(offset, LE)?;
+        let flags = payload.read_with::<u8>(offset, LE)?;
+        let status = match flags & 0b111 {
+            0b000 => RouteStatus::Active,
+            0b001 => RouteStatus::DiscoveryUnderway,
+            0b010 => RouteStatus::DiscoveryFailed,
+            0b011 => RouteStatus::Inactive,
+            0b100 => RouteStatus::ValidationUnderway,
+            _ => return Err(ParseError),
+        };
+        let next_hop = payload.read_with::<u16>(offset, LE)?;
+
+        Ok(Self {
+            destination,
+            status,
+            memory_constrained: ((flags >> 3) & 1) == 1,
+            many_to_one: ((flags >> 4) & 1) == 1,
+            route_record_required: ((flags >> 5) & 1) == 1,
+            next_hop,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let status: u8 = match self.status {
+            RouteStatus::Active => 0b000,
+            RouteStatus::DiscoveryUnderway => 0b001,
+            RouteStatus::DiscoveryFailed => 0b010,
+            RouteStatus::Inactive => 0b011,
+            RouteStatus::ValidationUnderway => 0b100,
+        };
+        let flags = status
+            | ((self.memory_constrained as u8) << 3)
+            | ((self.many_to_one as u8) << 4)
+            | ((self.route_record_required as u8) << 5);
+
+        buffer.write_with::<u16>(offset, self.destination, LE)?;
+        buffer.write_with::<u8>(offset, flags, LE)?;
+        buffer.write_with::<u16>(offset, self.next_hop, LE)?;
+        Ok(())
+    }
+}
+
+impl ManagementRecord for Binding {
+    fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let source_address = payload.read_with::<u64>(offset, LE)?;
+        let source_endpoint = payload.read_with::<u8>(offset, LE)?;
+        let cluster_id = payload.read_with::<u16>(offset, LE)?;
+        let destination = match payload.read_with::<u8>(offset, LE)? {
+            0x01 => BindingDestination::Group(payload.read_with::<u16>(offset, LE)?),
+            0x03 => BindingDestination::Device {
+                ieee_address: payload.read_with::<u64>(offset, LE)?,
+                endpoint: payload.read_with::<u8>(offset, LE)?,
+            },
+            _ => return Err(ParseError),
+        };
+
+        Ok(Self {
+            source_address,
+            source_endpoint,
+            cluster_id,
+            destination,
+        })
+    }
+
+    fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u64>(offset, self.source_address, LE)?;
+        buffer.write_with::<u8>(offset, self.source_endpoint, LE)?;
+        buffer.write_with::<u16>(offset, self.cluster_id, LE)?;
+        match self.destination {
+            BindingDestination::Group(group_address) => {
+                buffer.write_with::<u8>(offset, 0x01, LE)?;
+                buffer.write_with::<u16>(offset, group_address, LE)?;
+            }
+            BindingDestination::Device {
+                ieee_address,
+                endpoint,
+            } => {
+                buffer.write_with::<u8>(offset, 0x03, LE)?;
+                buffer.write_with::<u64>(offset, ieee_address, LE)?;
+                buffer.write_with::<u8>(offset, endpoint, LE)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Request for part of the neighbor, routing or binding table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRequest {
+    pub start_index: u8,
+}
+impl TableRequest {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let start_index = payload.read_with::<u8>(offset, LE)?;
+        Ok(Self { start_index })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.start_index, LE)?;
+        Ok(())
+    }
+}
+
+/// Part of a table, starting at `start_index`. The entries are kept in
+/// their over the air form and parsed when iterated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableResponse<'a, T> {
+    pub status: Status,
+    /// Number of entries in the whole table.
+    pub total_entries: u8,
+    pub start_index: u8,
+    pub count: u8,
+    pub entry_list: &'a [u8],
+    entry: PhantomData<T>,
+}
+impl<'a, T: ManagementRecord> TableResponse<'a, T> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        // Failed responses only carry the status.
+        if status != Status::Success {
+            return Ok(Self::failed(status));
+        }
+
+        let total_entries = payload.read_with::<u8>(offset, LE)?;
+        let start_index = payload.read_with::<u8>(offset, LE)?;
+        let count = payload.read_with::<u8>(offset, LE)?;
+
+        let start = *offset;
+        for _ in 0..count {
+            T::try_parse_from(payload, offset)?;
+        }
+        let entry_list = &payload[start..*offset];
+
+        Ok(Self {
+            status,
+            total_entries,
+            start_index,
+            count,
+            entry_list,
+            entry: PhantomData,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        if self.status == Status::Success {
+            buffer.write_with::<u8>(offset, self.total_entries, LE)?;
+            buffer.write_with::<u8>(offset, self.start_index, LE)?;
+            buffer.write_with::<u8>(offset, self.count, LE)?;
+            buffer.write(offset, self.entry_list)?;
+        }
+        Ok(())
+    }
+
+    /// A response listing the entries of a table with `total_entries`
+    /// entries from `start_index` on, as many as fit in `scratch`.
+    pub fn from_entries<'t>(
+        entries: impl Iterator<Item = &'t T>,
+        total_entries: usize,
+        start_index: u8,
+        scratch: &'a mut [u8],
+    ) -> Result<Self, WriteError>
+    where
+        T: 't,
+    {
+        let total_entries = u8::try_from(total_entries).map_err(|_| WriteError)?;
+        let offset = &mut 0;
+        let mut count = 0;
+        for entry in entries.skip(start_index as usize) {
+            let entry_start = *offset;
+            if entry.write_into(scratch, offset).is_err() {
+                *offset = entry_start;
+                break;
+            }
+            count += 1;
+        }
+
+        Ok(Self {
+            status: Status::Success,
+            total_entries,
+            start_index,
+            count,
+            entry_list: &scratch[..*offset],
+            entry: PhantomData,
+        })
+    }
+
+    pub fn failed(status: Status) -> Self {
+        Self {
+            status,
+            total_entries: 0,
+            start_index: 0,
+            count: 0,
+            entry_list: &[],
+            entry: PhantomData,
+        }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = T> + 'a {
+        let entry_list = self.entry_list;
+        let mut offset = 0;
+        core::iter::from_fn(move || match offset < entry_list.len() {
+            true => T::try_parse_from(entry_list, &mut offset).ok(),
+            false => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MgmtLeaveRequest {
+    /// 0 for the device receiving the request.
+    pub device_address: u64,
+    pub remove_children: bool,
+    pub rejoin: bool,
+}
+impl MgmtLeaveRequest {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let device_address = payload.read_with::<u64>(offset, LE)?;
+        let flags = payload.read_with::<u8>(offset, LE)?;
+
+        Ok(Self {
+            device_address,
+            remove_children: ((flags >> 6) & 1) == 1,
+            rejoin: ((flags >> 7) & 1) == 1,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let flags = ((self.remove_children as u8) << 6) | ((self.rejoin as u8) << 7);
+
+        buffer.write_with::<u64>(offset, self.device_address, LE)?;
+        buffer.write_with::<u8>(offset, flags, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MgmtPermitJoiningRequest {
+    /// Seconds to allow joining for, 0 to stop and 0xff to allow forever.
+    pub permit_duration: u8,
+    pub trust_center_significance: bool,
+}
+impl MgmtPermitJoiningRequest {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let permit_duration = payload.read_with::<u8>(offset, LE)?;
+        let trust_center_significance = payload.read_with::<u8>(offset, LE)? == 1;
+
+        Ok(Self {
+            permit_duration,
+            trust_center_significance,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u8>(offset, self.permit_duration, LE)?;
+        buffer.write_with::<u8>(offset, self.trust_center_significance as u8, LE)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NwkUpdate {
+    /// Scan the channels `scan_count` times, for a duration of
+    /// `(2^scan_duration + 1) * 15.36 ms` each.
+    EnergyScan { scan_duration: u8, scan_count: u8 },
+    /// Move to the single channel in `scan_channels`.
+    ChangeChannel { nwk_update_id: u8 },
+    /// Change the channel mask and network manager.
+    ChangeManager {
+        nwk_update_id: u8,
+        nwk_manager_address: u16,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MgmtNwkUpdateRequest {
+    /// Bitmask of channels, bit 11 is channel 11.
+    pub scan_channels: u32,
+    pub update: NwkUpdate,
+}
+impl MgmtNwkUpdateRequest {
+    pub fn try_parse_from(payload: &[u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let scan_channels = payload.read_with::<u32>(offset, LE)?;
+        let update = match payload.read_with::<u8>(offset, LE)? {
+            scan_duration @ 0x00..=0x05 => NwkUpdate::EnergyScan {
+                scan_duration,
+                scan_count: payload.read_with::<u8>(offset, LE)?,
+            },
+            0xfe => NwkUpdate::ChangeChannel {
+                nwk_update_id: payload.read_with::<u8>(offset, LE)?,
+            },
+            0xff => NwkUpdate::ChangeManager {
+                nwk_update_id: payload.read_with::<u8>(offset, LE)?,
+                nwk_manager_address: payload.read_with::<u16>(offset, LE)?,
+            },
+            _ => return Err(ParseError),
+        };
+
+        Ok(Self {
+            scan_channels,
+            update,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        buffer.write_with::<u32>(offset, self.scan_channels, LE)?;
+        match self.update {
+            NwkUpdate::EnergyScan {
+                scan_duration,
+                scan_count,
+            } => {
+                if scan_duration > 0x05 {
+                    return Err(WriteError);
+                }
+                buffer.write_with::<u8>(offset, scan_duration, LE)?;
+                buffer.write_with::<u8>(offset, scan_count, LE)?;
+            }
+            NwkUpdate::ChangeChannel { nwk_update_id } => {
+                buffer.write_with::<u8>(offset, 0xfe, LE)?;
+                buffer.write_with::<u8>(offset, nwk_update_id, LE)?;
+            }
+            NwkUpdate::ChangeManager {
+                nwk_update_id,
+                nwk_manager_address,
+            } => {
+                buffer.write_with::<u8>(offset, 0xff, LE)?;
+                buffer.write_with::<u8>(offset, nwk_update_id, LE)?;
+                buffer.write_with::<u16>(offset, nwk_manager_address, LE)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Result of an energy scan, or a report of transmission failures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MgmtNwkUpdateNotify<'a> {
+    pub status: Status,
+    pub scanned_channels: u32,
+    pub total_transmissions: u16,
+    pub transmission_failures: u16,
+    /// Energy measured on each scanned channel, in order.
+    pub energy_values: &'a [u8],
+}
+impl<'a> MgmtNwkUpdateNotify<'a> {
+    pub fn try_parse_from(payload: &'a [u8], offset: &mut usize) -> Result<Self, ParseError> {
+        let status = Status::try_from(payload.read_with::<u8>(offset, LE)?)?;
+        let scanned_channels = payload.read_with::<u32>(offset, LE)?;
+        let total_transmissions = payload.read_with::<u16>(offset, LE)?;
+        let transmission_failures = payload.read_with::<u16>(offset, LE)?;
+        let count = payload.read_with::<u8>(offset, LE)?;
+        let energy_values = payload.read_with::<&[u8]>(offset, Bytes::Len(count as usize))?;
+
+        Ok(Self {
+            status,
+            scanned_channels,
+            total_transmissions,
+            transmission_failures,
+            energy_values,
+        })
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        let count = u8::try_from(self.energy_values.len()).map_err(|_| WriteError)?;
+
+        buffer.write_with::<u8>(offset, self.status as u8, LE)?;
+        buffer.write_with::<u32>(offset, self.scanned_channels, LE)?;
+        buffer.write_with::<u16>(offset, self.total_transmissions, LE)?;
+        buffer.write_with::<u16>(offset, self.transmission_failures, LE)?;
+        buffer.write_with::<u8>(offset, count, LE)?;
+        buffer.write(offset, self.energy_values)?;
+        Ok(())
+    }
+}
+
+/// The stack's tables, used to answer management requests.
+#[derive(Debug, Clone, Copy)]
+pub struct ManagementTables<'a, const NEIGHBORS: usize, const ROUTES: usize, const BINDINGS: usize>
+{
+    pub neighbors: &'a NeighborTable<NEIGHBORS>,
+    pub routes: &'a RoutingTable<ROUTES>,
+    pub bindings: &'a BindingTable<BINDINGS>,
+}
+impl<'a, const NEIGHBORS: usize, const ROUTES: usize, const BINDINGS: usize>
+    ManagementTables<'a, NEIGHBORS, ROUTES, BINDINGS>
+{
+    /// Build the response to a management request. Table entries are
+    /// stored in `scratch`, and only as many as fit are listed, so its size
+    /// should be the space left in the frame.
+    ///
+    /// The tables can't act on leave and permit joining requests, so they
+    /// are answered with `Status::NotSupported`, see
+    /// `ZigbeeStack::answer_management_request` for leave requests. Network
+    /// update requests need the radio and are left to the caller, as are
+    /// frames that aren't management requests, for which `None` is returned.
+    pub fn respond<'b>(
+        &self,
+        request: &ZdoFrame,
+        scratch: &'b mut [u8],
+    ) -> Result<Option<ZdoFrame<'b>>, WriteError> {
+        let command = match &request.command {
+            ZdoCommand::MgmtLqiRequest(request) => {
+                ZdoCommand::MgmtLqiResponse(TableResponse::from_entries(
+                    self.neighbors.iter(),
+                    self.neighbors.len(),
+                    request.start_index,
+                    scratch,
+                )?)
+            }
+            ZdoCommand::MgmtRoutingRequest(request) => {
+                ZdoCommand::MgmtRoutingResponse(TableResponse::from_entries(
+                    self.routes.iter(),
+                    self.routes.len(),
+                    request.start_index,
+                    scratch,
+                )?)
+            }
+            ZdoCommand::MgmtBindRequest(request) => {
+                ZdoCommand::MgmtBindResponse(TableResponse::from_entries(
+                    self.bindings.iter(),
+                    self.bindings.len(),
+                    request.start_index,
+                    scratch,
+                )?)
+            }
+            ZdoCommand::MgmtLeaveRequest(_) => ZdoCommand::MgmtLeaveResponse(Status::NotSupported),
+            ZdoCommand::MgmtPermitJoiningRequest(_) => {
+                ZdoCommand::MgmtPermitJoiningResponse(Status::NotSupported)
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(ZdoFrame {
+            transaction_sequence_number: request.transaction_sequence_number,
+            command,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neighbor(nwk_address: u16) -> Neighbor {
+        Neighbor {
+            extended_pan_id: 0xdddd_dddd_dddd_dddd,
+            extended_address: 0x0017_8801_0881_0000 | nwk_address as u64,
+            nwk_address,
+            device_type: DeviceType::Router,
+            receiver_on_when_idle: Some(true),
+            relationship: Relationship::Sibling,
+            permit_joining: None,
+            depth: 1,
+            lqi: 0xa0,
+        }
+    }
+
+    fn assert_round_trips(cluster_id: u16, payload: &[u8]) -> ZdoFrame<'_> {
+        let frame = ZdoFrame::try_parse_from(cluster_id, payload).unwrap();
+        assert_eq!(frame.cluster_id(), cluster_id);
+
+        let mut buffer = [0u8; 96];
+        let len = frame.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], payload);
+
+        frame
+    }
+
+    #[test]
+    fn parses_lqi_response() {
+        let frame = assert_round_trips(
+            0x8031,
+            b"\x01\x00\x05\x00\x01\xdd\xdd\xdd\xdd\xdd\xdd\xdd\xdd\x01\x00\x81\x08\x01\x88\x17\x00\x01\x00\x25\x02\x01\xa0",
+        );
+
+        match frame.command {
+            ZdoCommand::MgmtLqiResponse(response) => {
+                assert_eq!(response.total_entries, 5);
+                let mut neighbors = response.entries();
+                assert_eq!(neighbors.next(), Some(neighbor(0x0001)));
+                assert_eq!(neighbors.next(), None);
+            }
+            _ => panic!("Expected Mgmt_Lqi_rsp"),
+        }
+
+        assert_round_trips(0x8031, b"\x02\x84");
+    }
+
+    #[test]
+    fn parses_routing_and_bind_responses() {
+        match assert_round_trips(0x8032, b"\x01\x00\x01\x00\x01\x34\x12\x10\x00\x00").command {
+            ZdoCommand::MgmtRoutingResponse(response) => assert_eq!(
+                response.entries().next(),
+                Some(Route {
+                    destination: 0x1234,
+                    status: RouteStatus::Active,
+                    memory_constrained: false,
+                    many_to_one: true,
+                    route_record_required: false,
+                    next_hop: 0x0000,
+                })
+            ),
+            _ => panic!("Expected Mgmt_Rtg_rsp"),
+        }
+
+        match assert_round_trips(
+            0x8033,
+            b"\x02\x00\x02\x00\x02\x9e\xc0\x81\x08\x01\x88\x17\x00\x01\x06\x00\x01\x01\x00\x9e\xc0\x81\x08\x01\x88\x17\x00\x01\x06\x00\x03\x01\x00\x00\x00\x00\x00\x00\x00\x01",
+        )
+        .command
+        {
+            ZdoCommand::MgmtBindResponse(response) => {
+                let destinations: Vec<_> =
+                    response.entries().map(|binding| binding.destination).collect();
+                assert_eq!(
+                    destinations,
+                    [
+                        BindingDestination::Group(0x0001),
+                        BindingDestination::Device {
+                            ieee_address: 0x0000_0000_0000_0001,
+                            endpoint: 1,
+                        },
+                    ]
+                );
+            }
+            _ => panic!("Expected Mgmt_Bind_rsp"),
+        }
+    }
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(
+            assert_round_trips(0x0034, b"\x01\x00\x00\x00\x00\x00\x00\x00\x00\x80").command,
+            ZdoCommand::MgmtLeaveRequest(MgmtLeaveRequest {
+                device_address: 0,
+                remove_children: false,
+                rejoin: true,
+            })
+        );
+        assert_eq!(
+            assert_round_trips(0x0036, b"\x02\xb4\x01").command,
+            ZdoCommand::MgmtPermitJoiningRequest(MgmtPermitJoiningRequest {
+                permit_duration: 180,
+                trust_center_significance: true,
+            })
+        );
+        assert_eq!(
+            assert_round_trips(0x0038, b"\x03\x00\xf8\xff\x07\x03\x02").command,
+            ZdoCommand::MgmtNwkUpdateRequest(MgmtNwkUpdateRequest {
+                scan_channels: 0x07fff800,
+                update: NwkUpdate::EnergyScan {
+                    scan_duration: 3,
+                    scan_count: 2,
+                },
+            })
+        );
+        assert_round_trips(0x0038, b"\x04\x00\x80\x00\x00\xfe\x05");
+        assert!(ZdoFrame::try_parse_from(0x0038, b"\x04\x00\x80\x00\x00\x10\x05").is_err());
+
+        assert_round_trips(
+            0x8038,
+            b"\x05\x00\x00\x00\x18\x00\x10\x00\x01\x00\x02\xa0\xb0",
+        );
+    }
+
+    #[test]
+    fn answers_lqi_request_from_neighbor_table() {
+        let mut neighbors = NeighborTable::<4>::new();
+        for nwk_address in 1..=3 {
+            neighbors.update(neighbor(nwk_address)).unwrap();
+        }
+        let tables = ManagementTables {
+            neighbors: &neighbors,
+            routes: &RoutingTable::<1>::new(),
+            bindings: &BindingTable::<1>::new(),
+        };
+        let request = ZdoFrame::try_parse_from(0x0031, b"\x07\x01").unwrap();
+
+        // Only room for a single entry.
+        let mut scratch = [0u8; 30];
+        let response = tables.respond(&request, &mut scratch).unwrap().unwrap();
+
+        assert_eq!(response.transaction_sequence_number, 7);
+        match response.command {
+            ZdoCommand::MgmtLqiResponse(response) => {
+                assert_eq!(response.total_entries, 3);
+                assert_eq!(response.start_index, 1);
+                assert_eq!(response.count, 1);
+                assert_eq!(response.entries().next(), Some(neighbor(2)));
+            }
+            _ => panic!("Expected Mgmt_Lqi_rsp"),
+        }
+    }
+
+    #[test]
+    fn refuses_leave_and_ignores_other_frames() {
+        let neighbors = NeighborTable::<1>::new();
+        let routes = RoutingTable::<1>::new();
+        let bindings = BindingTable::<1>::new();
+        let tables = ManagementTables {
+            neighbors: &neighbors,
+            routes: &routes,
+            bindings: &bindings,
+        };
+
+        let request =
+            ZdoFrame::try_parse_from(0x0034, b"\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00").unwrap();
+        let response = tables.respond(&request, &mut []).unwrap().unwrap();
+        assert_eq!(
+            response.command,
+            ZdoCommand::MgmtLeaveResponse(Status::NotSupported)
+        );
+        let request = ZdoFrame::try_parse_from(0x0036, b"\x02\xb4\x01").unwrap();
+        let response = tables.respond(&request, &mut []).unwrap().unwrap();
+        assert_eq!(
+            response.command,
+            ZdoCommand::MgmtPermitJoiningResponse(Status::NotSupported)
+        );
+
+        let request = ZdoFrame::try_parse_from(0x0005, b"\x01\x00\x00").unwrap();
+        assert_eq!(tables.respond(&request, &mut []).unwrap(), None);
+    }
+}
@@ -0,0 +1,687 @@
+use crate::aps::binding::Binding;
+use crate::network_layer::commands::AddressList;
+use crate::network_layer::neighbor_table::Neighbor;
+use crate::network_layer::routing_table::Route;
+use crate::network_layer::{ParseError, WriteError};
+use byte::{BytesExt, LE};
+use descriptors::{NodeDescriptor, PowerDescriptor, SimpleDescriptor};
+use discovery::*;
+use management::*;
+
+pub mod binding;
+pub mod descriptors;
+pub mod discovery;
+pub mod management;
+
+/// The ZDO lives on endpoint 0 of every device.
+pub const ZDO_ENDPOINT: u8 = 0x00;
+pub const ZDO_PROFILE_ID: u16 = 0x0000;
+
+/// A ZDO frame, carried in the payload of an APS data frame sent to
+/// `ZDO_ENDPOINT`. The command is identified by the APS cluster identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZdoFrame<'a> {
+    pub transaction_sequence_number: u8,
+    pub command: ZdoCommand<'a>,
+}
+impl<'a> ZdoFrame<'a> {
+    pub fn try_parse_from(cluster_id: u16, payload: &'a [u8]) -> Result<Self, ParseError> {
+        let offset = &mut 0;
+
+        let transaction_sequence_number = payload.read_with::<u8>(offset, LE)?;
+        let command = ZdoCommand::try_parse_from(cluster_id, payload, offset)?;
+
+        Ok(Self {
+            transaction_sequence_number,
+            command,
+        })
+    }
+
+    /// Serialize the frame into `buffer` as the payload of an APS frame,
+    /// returning the number of bytes written. The APS cluster identifier
+    /// should be set to `cluster_id()`.
+    pub fn write_into(&self, buffer: &mut [u8]) -> Result<usize, WriteError> {
+        let offset = &mut 0;
+
+        buffer.write_with::<u8>(offset, self.transaction_sequence_number, LE)?;
+        self.command.write_into(buffer, offset)?;
+
+        Ok(*offset)
+    }
+
+    pub fn cluster_id(&self) -> u16 {
+        self.command.cluster_id()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZdoCommand<'a> {
+    NwkAddressRequest(NwkAddressRequest),
+    IeeeAddressRequest(IeeeAddressRequest),
+    NodeDescriptorRequest(DescriptorRequest),
+    PowerDescriptorRequest(DescriptorRequest),
+    SimpleDescriptorRequest(SimpleDescriptorRequest),
+    ActiveEndpointsRequest(DescriptorRequest),
+    MatchDescriptorRequest(MatchDescriptorRequest<'a>),
+    DeviceAnnounce(DeviceAnnounce),
+    BindRequest(Binding),
+    UnbindRequest(Binding),
+    MgmtLqiRequest(TableRequest),
+    MgmtRoutingRequest(TableRequest),
+    MgmtBindRequest(TableRequest),
+    MgmtLeaveRequest(MgmtLeaveRequest),
+    MgmtPermitJoiningRequest(MgmtPermitJoiningRequest),
+    MgmtNwkUpdateRequest(MgmtNwkUpdateRequest),
+    NwkAddressResponse(AddressResponse<'a>),
+    IeeeAddressResponse(AddressResponse<'a>),
+    NodeDescriptorResponse(NodeDescriptorResponse),
+    PowerDescriptorResponse(PowerDescriptorResponse),
+    SimpleDescriptorResponse(SimpleDescriptorResponse<'a>),
+    ActiveEndpointsResponse(EndpointListResponse<'a>),
+    MatchDescriptorResponse(EndpointListResponse<'a>),
+    BindResponse(Status),
+    UnbindResponse(Status),
+    MgmtLqiResponse(TableResponse<'a, Neighbor>),
+    MgmtRoutingResponse(TableResponse<'a, Route>),
+    MgmtBindResponse(TableResponse<'a, Binding>),
+    MgmtLeaveResponse(Status),
+    MgmtPermitJoiningResponse(Status),
+    MgmtNwkUpdateNotify(MgmtNwkUpdateNotify<'a>),
+}
+impl<'a> ZdoCommand<'a> {
+    pub fn try_parse_from(
+        cluster_id: u16,
+        payload: &'a [u8],
+        offset: &mut usize,
+    ) -> Result<Self, ParseError> {
+        let command = match cluster_id {
+            0x0000 => {
+                ZdoCommand::NwkAddressRequest(NwkAddressRequest::try_parse_from(payload, offset)?)
+            }
+            0x0001 => {
+                ZdoCommand::IeeeAddressRequest(IeeeAddressRequest::try_parse_from(payload, offset)?)
+            }
+            0x0002 => ZdoCommand::NodeDescriptorRequest(DescriptorRequest::try_parse_from(
+                payload, offset,
+            )?),
+            0x0003 => ZdoCommand::PowerDescriptorRequest(DescriptorRequest::try_parse_from(
+                payload, offset,
+            )?),
+            0x0004 => ZdoCommand::SimpleDescriptorRequest(SimpleDescriptorRequest::try_parse_from(
+                payload, offset,
+            )?),
+            0x0005 => ZdoCommand::ActiveEndpointsRequest(DescriptorRequest::try_parse_from(
+                payload, offset,
+            )?),
+            0x0006 => ZdoCommand::MatchDescriptorRequest(MatchDescriptorRequest::try_parse_from(
+                payload, offset,
+            )?),
+            0x0013 => ZdoCommand::DeviceAnnounce(DeviceAnnounce::try_parse_from(payload, offset)?),
+            0x0021 => ZdoCommand::BindRequest(Binding::try_parse_from(payload, offset)?),
+            0x0022 => ZdoCommand::UnbindRequest(Binding::try_parse_from(payload, offset)?),
+            0x0031 => ZdoCommand::MgmtLqiRequest(TableRequest::try_parse_from(payload, offset)?),
+            0x0032 => {
+                ZdoCommand::MgmtRoutingRequest(TableRequest::try_parse_from(payload, offset)?)
+            }
+            0x0033 => ZdoCommand::MgmtBindRequest(TableRequest::try_parse_from(payload, offset)?),
+            0x0034 => {
+                ZdoCommand::MgmtLeaveRequest(MgmtLeaveRequest::try_parse_from(payload, offset)?)
+            }
+            0x0036 => ZdoCommand::MgmtPermitJoiningRequest(
+                MgmtPermitJoiningRequest::try_parse_from(payload, offset)?,
+            ),
+            0x0038 => ZdoCommand::MgmtNwkUpdateRequest(MgmtNwkUpdateRequest::try_parse_from(
+                payload, offset,
+            )?),
+            0x8000 => {
+                ZdoCommand::NwkAddressResponse(AddressResponse::try_parse_from(payload, offset)?)
+            }
+            0x8001 => {
+                ZdoCommand::IeeeAddressResponse(AddressResponse::try_parse_from(payload, offset)?)
+            }
+            0x8002 => ZdoCommand::NodeDescriptorResponse(NodeDescriptorResponse::try_parse_from(
+                payload, offset,
+            )?),
+            0x8003 => ZdoCommand::PowerDescriptorResponse(PowerDescriptorResponse::try_parse_from(
+                payload, offset,
+            )?),
+            0x8004 => ZdoCommand::SimpleDescriptorResponse(
+                SimpleDescriptorResponse::try_parse_from(payload, offset)?,
+            ),
+            0x8005 => ZdoCommand::ActiveEndpointsResponse(EndpointListResponse::try_parse_from(
+                payload, offset,
+            )?),
+            0x8006 => ZdoCommand::MatchDescriptorResponse(EndpointListResponse::try_parse_from(
+                payload, offset,
+            )?),
+            0x8021 => {
+                ZdoCommand::BindResponse(Status::try_from(payload.read_with::<u8>(offset, LE)?)?)
+            }
+            0x8022 => {
+                ZdoCommand::UnbindResponse(Status::try_from(payload.read_with::<u8>(offset, LE)?)?)
+            }
+            0x8031 => ZdoCommand::MgmtLqiResponse(TableResponse::try_parse_from(payload, offset)?),
+            0x8032 => {
+                ZdoCommand::MgmtRoutingResponse(TableResponse::try_parse_from(payload, offset)?)
+            }
+            0x8033 => ZdoCommand::MgmtBindResponse(TableResponse::try_parse_from(payload, offset)?),
+            0x8034 => ZdoCommand::MgmtLeaveResponse(Status::try_from(
+                payload.read_with::<u8>(offset, LE)?,
+            )?),
+            0x8036 => ZdoCommand::MgmtPermitJoiningResponse(Status::try_from(
+                payload.read_with::<u8>(offset, LE)?,
+            )?),
+            0x8038 => ZdoCommand::MgmtNwkUpdateNotify(MgmtNwkUpdateNotify::try_parse_from(
+                payload, offset,
+            )?),
+            _ => return Err(ParseError),
+        };
+
+        Ok(command)
+    }
+
+    pub fn write_into(&self, buffer: &mut [u8], offset: &mut usize) -> Result<(), WriteError> {
+        match self {
+            ZdoCommand::NwkAddressRequest(command) => command.write_into(buffer, offset),
+            ZdoCommand::IeeeAddressRequest(command) => command.write_into(buffer, offset),
+            ZdoCommand::NodeDescriptorRequest(command)
+            | ZdoCommand::PowerDescriptorRequest(command)
+            | ZdoCommand::ActiveEndpointsRequest(command) => command.write_into(buffer, offset),
+            ZdoCommand::SimpleDescriptorRequest(command) => command.write_into(buffer, offset),
+            ZdoCommand::MatchDescriptorRequest(command) => command.write_into(buffer, offset),
+            ZdoCommand::DeviceAnnounce(command) => command.write_into(buffer, offset),
+            ZdoCommand::NwkAddressResponse(command) | ZdoCommand::IeeeAddressResponse(command) => {
+                command.write_into(buffer, offset)
+            }
+            ZdoCommand::NodeDescriptorResponse(command) => command.write_into(buffer, offset),
+            ZdoCommand::PowerDescriptorResponse(command) => command.write_into(buffer, offset),
+            ZdoCommand::SimpleDescriptorResponse(command) => command.write_into(buffer, offset),
+            ZdoCommand::ActiveEndpointsResponse(command)
+            | ZdoCommand::MatchDescriptorResponse(command) => command.write_into(buffer, offset),
+            ZdoCommand::BindRequest(command) | ZdoCommand::UnbindRequest(command) => {
+                command.write_into(buffer, offset)
+            }
+            ZdoCommand::MgmtLqiRequest(command)
+            | ZdoCommand::MgmtRoutingRequest(command)
+            | ZdoCommand::MgmtBindRequest(command) => command.write_into(buffer, offset),
+            ZdoCommand::MgmtLeaveRequest(command) => command.write_into(buffer, offset),
+            ZdoCommand::MgmtPermitJoiningRequest(command) => command.write_into(buffer, offset),
+            ZdoCommand::MgmtNwkUpdateRequest(command) => command.write_into(buffer, offset),
+            ZdoCommand::MgmtLqiResponse(command) => command.write_into(buffer, offset),
+            ZdoCommand::MgmtRoutingResponse(command) => command.write_into(buffer, offset),
+            ZdoCommand::MgmtBindResponse(command) => command.write_into(buffer, offset),
+            ZdoCommand::BindResponse(status)
+            | ZdoCommand::UnbindResponse(status)
+            | ZdoCommand::MgmtLeaveResponse(status)
+            | ZdoCommand::MgmtPermitJoiningResponse(status) => {
+                buffer.write_with::<u8>(offset, *status as u8, LE)?;
+                Ok(())
+            }
+            ZdoCommand::MgmtNwkUpdateNotify(command) => command.write_into(buffer, offset),
+        }
+    }
+
+    pub fn cluster_id(&self) -> u16 {
+        match self {
+            ZdoCommand::NwkAddressRequest(_) => 0x0000,
+            ZdoCommand::IeeeAddressRequest(_) => 0x0001,
+            ZdoCommand::NodeDescriptorRequest(_) => 0x0002,
+            ZdoCommand::PowerDescriptorRequest(_) => 0x0003,
+            ZdoCommand::SimpleDescriptorRequest(_) => 0x0004,
+            ZdoCommand::ActiveEndpointsRequest(_) => 0x0005,
+            ZdoCommand::MatchDescriptorRequest(_) => 0x0006,
+            ZdoCommand::DeviceAnnounce(_) => 0x0013,
+            ZdoCommand::BindRequest(_) => 0x0021,
+            ZdoCommand::UnbindRequest(_) => 0x0022,
+            ZdoCommand::MgmtLqiRequest(_) => 0x0031,
+            ZdoCommand::MgmtRoutingRequest(_) => 0x0032,
+            ZdoCommand::MgmtBindRequest(_) => 0x0033,
+            ZdoCommand::MgmtLeaveRequest(_) => 0x0034,
+            ZdoCommand::MgmtPermitJoiningRequest(_) => 0x0036,
+            ZdoCommand::MgmtNwkUpdateRequest(_) => 0x0038,
+            ZdoCommand::NwkAddressResponse(_) => 0x8000,
+            ZdoCommand::IeeeAddressResponse(_) => 0x8001,
+            ZdoCommand::NodeDescriptorResponse(_) => 0x8002,
+            ZdoCommand::PowerDescriptorResponse(_) => 0x8003,
+            ZdoCommand::SimpleDescriptorResponse(_) => 0x8004,
+            ZdoCommand::ActiveEndpointsResponse(_) => 0x8005,
+            ZdoCommand::MatchDescriptorResponse(_) => 0x8006,
+            ZdoCommand::BindResponse(_) => 0x8021,
+            ZdoCommand::UnbindResponse(_) => 0x8022,
+            ZdoCommand::MgmtLqiResponse(_) => 0x8031,
+            ZdoCommand::MgmtRoutingResponse(_) => 0x8032,
+            ZdoCommand::MgmtBindResponse(_) => 0x8033,
+            ZdoCommand::MgmtLeaveResponse(_) => 0x8034,
+            ZdoCommand::MgmtPermitJoiningResponse(_) => 0x8036,
+            ZdoCommand::MgmtNwkUpdateNotify(_) => 0x8038,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum Status {
+    Success = 0x00,
+    InvalidRequestType = 0x80,
+    DeviceNotFound = 0x81,
+    InvalidEndpoint = 0x82,
+    NotActive = 0x83,
+    NotSupported = 0x84,
+    Timeout = 0x85,
+    NoMatch = 0x86,
+    NoEntry = 0x88,
+    NoDescriptor = 0x89,
+    InsufficientSpace = 0x8a,
+    NotPermitted = 0x8b,
+    TableFull = 0x8c,
+    NotAuthorized = 0x8d,
+    DeviceBindingTableFull = 0x8e,
+    InvalidIndex = 0x8f,
+}
+impl TryFrom<u8> for Status {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use Status::*;
+
+        let status = match value {
+            0x00 => Success,
+            0x80 => InvalidRequestType,
+            0x81 => DeviceNotFound,
+            0x82 => InvalidEndpoint,
+            0x83 => NotActive,
+            0x84 => NotSupported,
+            0x85 => Timeout,
+            0x86 => NoMatch,
+            0x88 => NoEntry,
+            0x89 => NoDescriptor,
+            0x8a => InsufficientSpace,
+            0x8b => NotPermitted,
+            0x8c => TableFull,
+            0x8d => NotAuthorized,
+            0x8e => DeviceBindingTableFull,
+            0x8f => InvalidIndex,
+            _ => return Err(()),
+        };
+        Ok(status)
+    }
+}
+
+/// Description of this device, used to answer the discovery requests a
+/// coordinator sends after the device joins.
+#[derive(Debug, Clone)]
+pub struct LocalDevice<'a> {
+    pub nwk_address: u16,
+    pub ieee_address: u64,
+    pub node_descriptor: NodeDescriptor,
+    pub power_descriptor: PowerDescriptor,
+    /// Application endpoints, the ZDO endpoint is not listed.
+    pub endpoints: &'a [SimpleDescriptor<'a>],
+}
+impl<'a> LocalDevice<'a> {
+    /// Build the response to a discovery request. Endpoint lists in the
+    /// response are stored in `scratch`, which needs a byte per endpoint.
+    ///
+    /// Returns `None` for frames that need no answer, such as responses or
+    /// address requests for other devices. Match descriptor requests always
+    /// get an answer, which should be dropped when it has no endpoints and
+    /// the request was broadcast.
+    pub fn respond<'b>(
+        &'b self,
+        request: &ZdoFrame,
+        scratch: &'b mut [u8],
+    ) -> Result<Option<ZdoFrame<'b>>, WriteError> {
+        let command = match &request.command {
+            ZdoCommand::NwkAddressRequest(request) => {
+                if request.ieee_address != self.ieee_address {
+                    return Ok(None);
+                }
+                ZdoCommand::NwkAddressResponse(self.address_response(request.request_type))
+            }
+            ZdoCommand::IeeeAddressRequest(request) => {
+                let mut response = self.address_response(request.request_type);
+                if request.nwk_address_of_interest != self.nwk_address {
+                    response.status = Status::DeviceNotFound;
+                    response.nwk_address = request.nwk_address_of_interest;
+                }
+                ZdoCommand::IeeeAddressResponse(response)
+            }
+            ZdoCommand::NodeDescriptorRequest(request) => {
+                let status = self.status_for(request.nwk_address_of_interest);
+                ZdoCommand::NodeDescriptorResponse(NodeDescriptorResponse {
+                    status,
+                    nwk_address_of_interest: request.nwk_address_of_interest,
+                    node_descriptor: match status {
+                        Status::Success => Some(self.node_descriptor.clone()),
+                        _ => None,
+                    },
+                })
+            }
+            ZdoCommand::PowerDescriptorRequest(request) => {
+                let status = self.status_for(request.nwk_address_of_interest);
+                ZdoCommand::PowerDescriptorResponse(PowerDescriptorResponse {
+                    status,
+                    nwk_address_of_interest: request.nwk_address_of_interest,
+                    power_descriptor: match status {
+                        Status::Success => Some(self.power_descriptor.clone()),
+                        _ => None,
+                    },
+                })
+            }
+            ZdoCommand::SimpleDescriptorRequest(request) => {
+                let descriptor = self
+                    .endpoints
+                    .iter()
+                    .find(|descriptor| descriptor.endpoint == request.endpoint);
+                let status = match (self.status_for(request.nwk_address_of_interest), descriptor) {
+                    (Status::Success, _) if !(1..=240).contains(&request.endpoint) => {
+                        Status::InvalidEndpoint
+                    }
+                    (Status::Success, None) => Status::NotActive,
+                    (status, _) => status,
+                };
+                ZdoCommand::SimpleDescriptorResponse(SimpleDescriptorResponse {
+                    status,
+                    nwk_address_of_interest: request.nwk_address_of_interest,
+                    simple_descriptor: match status {
+                        Status::Success => descriptor.cloned(),
+                        _ => None,
+                    },
+                })
+            }
+            ZdoCommand::ActiveEndpointsRequest(request) => {
+                let status = self.status_for(request.nwk_address_of_interest);
+                let count = match status {
+                    Status::Success => self.endpoint_list(scratch, |_| true)?,
+                    _ => 0,
+                };
+                ZdoCommand::ActiveEndpointsResponse(EndpointListResponse {
+                    status,
+                    nwk_address_of_interest: request.nwk_address_of_interest,
+                    endpoints: &scratch[..count],
+                })
+            }
+            ZdoCommand::MatchDescriptorRequest(request) => {
+                // Match requests are usually broadcast to find devices.
+                let status = match request.nwk_address_of_interest {
+                    0xfff8..=0xffff => Status::Success,
+                    address => self.status_for(address),
+                };
+                let (nwk_address_of_interest, count) = match status {
+                    Status::Success => (
+                        self.nwk_address,
+                        self.endpoint_list(scratch, |descriptor| request.matches(descriptor))?,
+                    ),
+                    _ => (request.nwk_address_of_interest, 0),
+                };
+                ZdoCommand::MatchDescriptorResponse(EndpointListResponse {
+                    status,
+                    nwk_address_of_interest,
+                    endpoints: &scratch[..count],
+                })
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(ZdoFrame {
+            transaction_sequence_number: request.transaction_sequence_number,
+            command,
+        }))
+    }
+
+    /// The announcement to broadcast after joining the network.
+    pub fn device_announce(&self) -> DeviceAnnounce {
+        DeviceAnnounce {
+            nwk_address: self.nwk_address,
+            ieee_address: self.ieee_address,
+            capability: self.node_descriptor.mac_capabilities,
+        }
+    }
+
+    fn address_response(&self, request_type: AddressRequestType) -> AddressResponse<'static> {
+        AddressResponse {
+            status: Status::Success,
+            ieee_address: self.ieee_address,
+            nwk_address: self.nwk_address,
+            // Devices without children answer extended requests with an
+            // empty list.
+            associated_devices: match request_type {
+                AddressRequestType::SingleDevice => None,
+                AddressRequestType::Extended => Some(AssociatedDevices {
+                    start_index: 0,
+                    addresses: AddressList { bytes: &[] },
+                }),
+            },
+        }
+    }
+
+    fn status_for(&self, nwk_address_of_interest: u16) -> Status {
+        match nwk_address_of_interest == self.nwk_address {
+            true => Status::Success,
+            false => Status::DeviceNotFound,
+        }
+    }
+
+    /// Write the endpoints accepted by `filter` to `scratch`, returning how
+    /// many were written.
+    fn endpoint_list(
+        &self,
+        scratch: &mut [u8],
+        filter: impl Fn(&SimpleDescriptor) -> bool,
+    ) -> Result<usize, WriteError> {
+        let offset = &mut 0;
+        for descriptor in self
+            .endpoints
+            .iter()
+            .filter(|descriptor| filter(descriptor))
+        {
+            scratch.write_with::<u8>(offset, descriptor.endpoint, LE)?;
+        }
+        Ok(*offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::descriptors::{LogicalType, FREQUENCY_BAND_2400_MHZ};
+    use super::*;
+    use crate::network_layer::commands::{AddressList, CapabilityInformation};
+
+    const LIGHT: SimpleDescriptor = SimpleDescriptor {
+        endpoint: 1,
+        profile_id: 0x0104,
+        device_id: 0x0100,
+        device_version: 1,
+        input_clusters: AddressList {
+            bytes: b"\x00\x00\x03\x00\x06\x00",
+        },
+        output_clusters: AddressList { bytes: b"\x19\x00" },
+    };
+
+    fn local_device() -> LocalDevice<'static> {
+        LocalDevice {
+            nwk_address: 0x1234,
+            ieee_address: 0x0017_8801_0881_c09e,
+            node_descriptor: NodeDescriptor {
+                logical_type: LogicalType::EndDevice,
+                complex_descriptor_available: false,
+                user_descriptor_available: false,
+                frequency_bands: FREQUENCY_BAND_2400_MHZ,
+                mac_capabilities: CapabilityInformation::from(0x80),
+                manufacturer_code: 0x1234,
+                maximum_buffer_size: 0x52,
+                maximum_incoming_transfer_size: 0x80,
+                server_mask: 0x2c00,
+                maximum_outgoing_transfer_size: 0x80,
+                descriptor_capabilities: 0,
+            },
+            power_descriptor: PowerDescriptor {
+                current_power_mode: 0,
+                available_power_sources: 0b0100,
+                current_power_source: 0b0100,
+                current_power_source_level: 12,
+            },
+            endpoints: &[LIGHT],
+        }
+    }
+
+    fn respond(request: &[u8], cluster_id: u16) -> Option<(u16, Vec<u8>)> {
+        let device = local_device();
+        let request = ZdoFrame::try_parse_from(cluster_id, request).unwrap();
+
+        let mut scratch = [0u8; 8];
+        let response = device.respond(&request, &mut scratch).unwrap()?;
+
+        let mut buffer = [0u8; 64];
+        let len = response.write_into(&mut buffer).unwrap();
+        Some((response.cluster_id(), buffer[..len].to_vec()))
+    }
+
+    fn assert_round_trips(cluster_id: u16, payload: &[u8]) -> ZdoFrame<'_> {
+        let frame = ZdoFrame::try_parse_from(cluster_id, payload).unwrap();
+        assert_eq!(frame.cluster_id(), cluster_id);
+
+        let mut buffer = [0u8; 64];
+        let len = frame.write_into(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], payload);
+
+        frame
+    }
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(
+            assert_round_trips(0x0000, b"\x01\x9e\xc0\x81\x08\x01\x88\x17\x00\x00\x00").command,
+            ZdoCommand::NwkAddressRequest(NwkAddressRequest {
+                ieee_address: 0x0017_8801_0881_c09e,
+                request_type: AddressRequestType::SingleDevice,
+                start_index: 0,
+            })
+        );
+        assert_eq!(
+            assert_round_trips(0x0004, b"\x02\x34\x12\x01").command,
+            ZdoCommand::SimpleDescriptorRequest(SimpleDescriptorRequest {
+                nwk_address_of_interest: 0x1234,
+                endpoint: 1,
+            })
+        );
+
+        match assert_round_trips(0x0006, b"\x03\xfd\xff\x04\x01\x01\x19\x00\x00").command {
+            ZdoCommand::MatchDescriptorRequest(request) => {
+                assert_eq!(request.nwk_address_of_interest, 0xfffd);
+                assert_eq!(request.input_clusters.iter().next(), Some(0x0019));
+                assert!(request.output_clusters.is_empty());
+            }
+            _ => panic!("Expected Match_Desc_req"),
+        }
+
+        assert!(ZdoFrame::try_parse_from(0x0004, b"\x02\x34\x12").is_err());
+        assert!(ZdoFrame::try_parse_from(0x0010, b"\x02").is_err());
+    }
+
+    #[test]
+    fn parses_device_announce() {
+        let frame = assert_round_trips(0x0013, b"\x81\x34\x12\x9e\xc0\x81\x08\x01\x88\x17\x00\x8e");
+
+        match frame.command {
+            ZdoCommand::DeviceAnnounce(announce) => {
+                assert_eq!(announce.nwk_address, 0x1234);
+                assert_eq!(announce.ieee_address, 0x0017_8801_0881_c09e);
+                assert!(announce.capability.allocate_address);
+                assert!(announce.capability.receiver_on_when_idle);
+            }
+            _ => panic!("Expected Device_annce"),
+        }
+    }
+
+    #[test]
+    fn parses_responses() {
+        match assert_round_trips(
+            0x8001,
+            b"\x05\x00\x9e\xc0\x81\x08\x01\x88\x17\x00\x00\x00\x02\x00\x01\x00\x02\x00",
+        )
+        .command
+        {
+            ZdoCommand::IeeeAddressResponse(response) => {
+                let associated_devices = response.associated_devices.unwrap();
+                let addresses: Vec<u16> = associated_devices.addresses.iter().collect();
+                assert_eq!(addresses, [0x0001, 0x0002]);
+            }
+            _ => panic!("Expected IEEE_addr_rsp"),
+        }
+
+        assert_round_trips(0x8002, b"\x06\x81\x34\x12");
+        assert_round_trips(0x8004, b"\x07\x83\x34\x12\x00");
+        assert_round_trips(0x8005, b"\x08\x00\x34\x12\x02\x01\x02");
+    }
+
+    #[test]
+    fn answers_discovery_requests() {
+        assert_eq!(
+            respond(b"\x01\x34\x12", 0x0002),
+            Some((
+                0x8002,
+                b"\x01\x00\x34\x12\x02\x40\x80\x34\x12\x52\x80\x00\x00\x2c\x80\x00\x00".to_vec()
+            ))
+        );
+        assert_eq!(
+            respond(b"\x02\x34\x12", 0x0005),
+            Some((0x8005, b"\x02\x00\x34\x12\x01\x01".to_vec()))
+        );
+        assert_eq!(
+            respond(b"\x03\x34\x12\x01", 0x0004),
+            Some((
+                0x8004,
+                b"\x03\x00\x34\x12\x10\x01\x04\x01\x00\x01\x01\x03\x00\x00\x03\x00\x06\x00\x01\x19\x00".to_vec()
+            ))
+        );
+        assert_eq!(
+            respond(b"\x04\x34\x12\x02", 0x0004),
+            Some((0x8004, b"\x04\x83\x34\x12\x00".to_vec()))
+        );
+        assert_eq!(
+            respond(b"\x05\x35\x12", 0x0003),
+            Some((0x8003, b"\x05\x81\x35\x12".to_vec()))
+        );
+    }
+
+    #[test]
+    fn answers_match_descriptor_requests() {
+        // Looking for an on/off server, as a broadcast and as a unicast.
+        assert_eq!(
+            respond(b"\x01\xfd\xff\x04\x01\x01\x06\x00\x00", 0x0006),
+            Some((0x8006, b"\x01\x00\x34\x12\x01\x01".to_vec()))
+        );
+        assert_eq!(
+            respond(b"\x02\x34\x12\x04\x01\x01\x06\x00\x00", 0x0006),
+            Some((0x8006, b"\x02\x00\x34\x12\x01\x01".to_vec()))
+        );
+        assert_eq!(
+            respond(b"\x03\x34\x12\x04\x01\x00\x01\x06\x00", 0x0006),
+            Some((0x8006, b"\x03\x00\x34\x12\x00".to_vec()))
+        );
+    }
+
+    #[test]
+    fn answers_address_requests_for_this_device_only() {
+        assert_eq!(
+            respond(b"\x01\x9e\xc0\x81\x08\x01\x88\x17\x00\x00\x00", 0x0000),
+            Some((
+                0x8000,
+                b"\x01\x00\x9e\xc0\x81\x08\x01\x88\x17\x00\x34\x12".to_vec()
+            ))
+        );
+        assert_eq!(
+            respond(b"\x02\x9e\xc0\x81\x08\x01\x88\x17\x00\x01\x00", 0x0000),
+            Some((
+                0x8000,
+                b"\x02\x00\x9e\xc0\x81\x08\x01\x88\x17\x00\x34\x12\x00".to_vec()
+            ))
+        );
+        assert_eq!(
+            respond(b"\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00", 0x0000),
+            None
+        );
+        assert_eq!(respond(b"\x04\x00\x34\x12\x01\x01", 0x8006), None);
+    }
+}